@@ -56,6 +56,7 @@ impl MockStorage {
             l0_sstables: Vec::new(),
             levels: Vec::new(),
             sstables: Default::default(),
+            compacting_sstables: Default::default(),
         };
         Self {
             snapshot,
@@ -246,6 +247,24 @@ fn main() {
                     controller.generate_compaction_task(&storage.snapshot)
                 } {
                     let mut sst_ids = Vec::new();
+                    if task.is_trivial_move {
+                        println!(
+                            "Trivial move L{} {:?} -> L{}",
+                            task.upper_level.unwrap_or_default(),
+                            task.upper_level_sst_ids,
+                            task.lower_level
+                        );
+                        let (snapshot, del) = controller.apply_compaction_result(
+                            &storage.snapshot,
+                            &task,
+                            &task.upper_level_sst_ids,
+                            false,
+                        );
+                        storage.snapshot = snapshot;
+                        storage.remove(&del);
+                        num_compactions += 1;
+                        continue;
+                    }
                     let split_num = task.upper_level_sst_ids.len() + task.lower_level_sst_ids.len();
                     let mut first_keys = Vec::new();
                     let mut last_keys = Vec::new();
@@ -340,8 +359,12 @@ fn main() {
                             .join(", ")
                     );
                     max_space = max_space.max(storage.file_list.len());
-                    let (snapshot, del) =
-                        controller.apply_compaction_result(&storage.snapshot, &task, &sst_ids);
+                    let (snapshot, del) = controller.apply_compaction_result(
+                        &storage.snapshot,
+                        &task,
+                        &sst_ids,
+                        false,
+                    );
                     storage.snapshot = snapshot;
                     storage.remove(&del);
                     println!("--- After Compaction ---");
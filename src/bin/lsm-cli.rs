@@ -4,7 +4,10 @@ use clap::{Parser, ValueEnum};
 use lsm::compact::{CompactionOptions, LeveledCompactionOptions};
 use lsm::iterators::StorageIterator;
 use lsm::lsm_storage::{LsmStorageOptions, MiniLsm};
+use lsm::mem_table::MemTableRepKind;
+use lsm::table::FilterPolicy;
 use rustyline::DefaultEditor;
+use std::collections::HashMap;
 use std::fmt::Write;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -221,8 +224,8 @@ impl ReplHandler {
 
                 let mut key_format = String::new();
                 let mut value_format = String::new();
-                write!(&mut key_format, "{}", "{}").unwrap();
-                write!(&mut value_format, "value{}@{}", "{}", self.epoch).unwrap();
+                write!(&mut key_format, "{{}}").unwrap();
+                write!(&mut value_format, "value{{}}@{}", self.epoch).unwrap();
 
                 let mut success_count = 0;
                 for i in *begin..=*end {
@@ -340,6 +343,23 @@ fn main() -> Result<()> {
                     })
                 }
             },
+            max_subcompactions: 4,
+            max_background_compactions: 2,
+            prefix_extractor: None,
+            block_hash_index: true,
+            filter_policy: FilterPolicy::default(),
+            level_filter_policies: HashMap::new(),
+            filter_bottommost_level: true,
+            block_cache_capacity: 64 << 20,
+            block_cache: None,
+            cache_index_and_filter_blocks: true,
+            pin_l0_index_and_filter_blocks: true,
+            mmap_reads: false,
+            use_direct_io_for_compaction: false,
+            max_open_files: None,
+            enable_pipelined_write: false,
+            write_buffer_manager: None,
+            memtable_rep: MemTableRepKind::default(),
             enable_wal: args.enable_wal,
             serializable: args.serializable,
         },
@@ -1,35 +1,134 @@
+use std::ops::Deref;
+
 use bytes::{Buf, BufMut, Bytes};
+
 pub mod builder;
 pub mod iterator;
 
 pub(crate) const SIZEOF_U16: usize = std::mem::size_of::<u16>();
+pub(crate) const SIZEOF_U64: usize = std::mem::size_of::<u64>();
+/// a full key is written every `RESTART_INTERVAL` entries,
+/// the entries in between only store what differs from the previous key.
+pub(crate) const RESTART_INTERVAL: usize = 16;
+/// set in the trailing #restarts when the block carries a hash index.
+const HASH_INDEX_FLAG: u16 = 1 << 15;
+/// hash index buckets hold a restart index, or one of these markers.
+pub(crate) const BUCKET_EMPTY: u8 = u8::MAX;
+pub(crate) const BUCKET_COLLISION: u8 = u8::MAX - 1;
+/// blocks with more restarts than a bucket can address get no hash index.
+pub(crate) const MAX_HASHED_RESTARTS: usize = BUCKET_COLLISION as usize;
+
+/// The entries of a block, decoded into memory or borrowed from a memory-mapped SST.
+pub(crate) enum BlockData {
+    Owned(Vec<u8>),
+    Mapped(Bytes),
+}
+
+impl Deref for BlockData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            BlockData::Owned(data) => data,
+            BlockData::Mapped(data) => data,
+        }
+    }
+}
+
+impl PartialEq for BlockData {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl std::fmt::Debug for BlockData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl From<Vec<u8>> for BlockData {
+    fn from(data: Vec<u8>) -> Self {
+        BlockData::Owned(data)
+    }
+}
 
 pub struct Block {
-    pub(crate) data: Vec<u8>,
-    pub(crate) offsets: Vec<u16>,
+    pub(crate) data: BlockData,
+    /// offsets of the entries holding a full key.
+    pub(crate) restarts: Vec<u16>,
+    /// optional buckets mapping a user key hash to the restart interval
+    /// holding the key's first entry.
+    pub(crate) hash_index: Option<Vec<u8>>,
 }
 
 impl Block {
-    /// Block = entries + offset of each enry + #entries.
+    /// Block = entries + offset of each restart point + [buckets + #buckets] + #restarts.
+    /// the highest bit of #restarts tells whether the buckets are there.
     pub fn encode(&self) -> Bytes {
-        let mut buf = self.data.clone();
-        let offsets_len = self.offsets.len();
-        for offset in &self.offsets {
+        let mut buf = self.data.to_vec();
+        let mut restarts_len = self.restarts.len() as u16;
+        for offset in &self.restarts {
             buf.put_u16(*offset);
         }
-        buf.put_u16(offsets_len as u16);
+        if let Some(buckets) = &self.hash_index {
+            buf.put_slice(buckets);
+            buf.put_u16(buckets.len() as u16);
+            restarts_len |= HASH_INDEX_FLAG;
+        }
+        buf.put_u16(restarts_len);
         buf.into()
     }
 
     pub fn decode(data: &[u8]) -> Self {
-        let entry_offsets_len = (&data[data.len() - SIZEOF_U16..]).get_u16() as usize;
-        let data_end = data.len() - SIZEOF_U16 - entry_offsets_len * SIZEOF_U16;
-        let offsets_raw = &data[data_end..data.len() - SIZEOF_U16];
-        let offsets = offsets_raw
+        let (data_end, restarts, hash_index) = Self::decode_trailer(data);
+        Self {
+            data: data[0..data_end].to_vec().into(),
+            restarts,
+            hash_index,
+        }
+    }
+
+    /// decode a block of a memory-mapped SST, the entries are not copied.
+    pub(crate) fn decode_mapped(data: Bytes) -> Self {
+        let (data_end, restarts, hash_index) = Self::decode_trailer(&data);
+        Self {
+            data: BlockData::Mapped(data.slice(0..data_end)),
+            restarts,
+            hash_index,
+        }
+    }
+
+    /// decode the restarts and the hash index, and find where the entries end.
+    fn decode_trailer(data: &[u8]) -> (usize, Vec<u16>, Option<Vec<u8>>) {
+        let mut end = data.len() - SIZEOF_U16;
+        let restarts_len = (&data[end..]).get_u16();
+        let hash_index = if restarts_len & HASH_INDEX_FLAG != 0 {
+            let buckets_len = (&data[end - SIZEOF_U16..end]).get_u16() as usize;
+            end -= SIZEOF_U16 + buckets_len;
+            Some(data[end..end + buckets_len].to_vec())
+        } else {
+            None
+        };
+        let restarts_len = (restarts_len & !HASH_INDEX_FLAG) as usize;
+        let data_end = end - restarts_len * SIZEOF_U16;
+        let restarts_raw = &data[data_end..end];
+        let restarts = restarts_raw
             .chunks(SIZEOF_U16)
             .map(|mut x| x.get_u16())
             .collect();
-        let data = data[0..data_end].to_vec();
-        Self { data, offsets }
+        (data_end, restarts, hash_index)
+    }
+
+    /// get the restart interval the hash index points `user_key` at,
+    /// `None` without an index or when the bucket is empty or shared.
+    pub(crate) fn hash_index_lookup(&self, user_key: &[u8]) -> Option<usize> {
+        let buckets = self.hash_index.as_ref()?;
+        let bucket = buckets[farmhash::fingerprint32(user_key) as usize % buckets.len()];
+        if bucket == BUCKET_EMPTY || bucket == BUCKET_COLLISION {
+            None
+        } else {
+            Some(bucket as usize)
+        }
     }
 }
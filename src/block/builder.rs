@@ -1,30 +1,34 @@
 use bytes::BufMut;
 
 use super::Block;
-use super::SIZEOF_U16;
+use super::{BUCKET_COLLISION, BUCKET_EMPTY, MAX_HASHED_RESTARTS, RESTART_INTERVAL, SIZEOF_U16};
 use crate::key::{KeySlice, KeyVec};
 
 /// Builds a block
 pub struct BlockBuilder {
     // block data
     data: Vec<u8>,
-    offsets: Vec<u16>,
+    restarts: Vec<u16>,
     // metadata
-    first_key: KeyVec,
+    last_key: KeyVec,
+    num_entries: usize,
     block_size: usize,
+    // hash index: (user key hash, restart index) of every distinct user key.
+    hash_index: bool,
+    key_restarts: Vec<(u32, usize)>,
 }
 
-/// to compare how many common places between the first_key and the selected key
+/// to compare how many common places between the last_key and the selected key
 /// and return the place they differs First time from each other
-fn common_prefix(first_key: KeySlice, key: KeySlice) -> usize {
+fn common_prefix(last_key: KeySlice, key: KeySlice) -> usize {
     let mut i = 0;
     loop {
         // boundary check.
-        if i >= first_key.key_len() || i >= key.key_len() {
+        if i >= last_key.key_len() || i >= key.key_len() {
             break;
         }
         // compare to find the common.
-        if first_key.key_ref()[i] != key.key_ref()[i] {
+        if last_key.key_ref()[i] != key.key_ref()[i] {
             break;
         }
         i += 1;
@@ -37,16 +41,35 @@ impl BlockBuilder {
     pub fn new(block_size: usize) -> Self {
         Self {
             data: Vec::new(),
-            offsets: Vec::new(),
-            first_key: KeyVec::new(),
+            restarts: Vec::new(),
+            last_key: KeyVec::new(),
+            num_entries: 0,
             block_size,
+            hash_index: false,
+            key_restarts: Vec::new(),
         }
     }
 
+    /// also append a hash index mapping user keys to restart intervals.
+    pub fn with_hash_index(mut self, hash_index: bool) -> Self {
+        self.hash_index = hash_index;
+        self
+    }
+
+    /// the number of hash index buckets, about 4/3 of the distinct user keys.
+    fn num_buckets(&self) -> usize {
+        (self.key_restarts.len() * 4 / 3).max(1)
+    }
+
     /// return the estimated_size of the `current`` Block
-    /// Entries + offsets + #Entry
+    /// Entries + restarts + [buckets + #buckets] + #Restarts
     fn estimated_size(&self) -> usize {
-        self.data.len() + self.offsets.len() * SIZEOF_U16 + SIZEOF_U16
+        let hash_index_size = if self.hash_index {
+            self.num_buckets() + SIZEOF_U16
+        } else {
+            0
+        };
+        self.data.len() + self.restarts.len() * SIZEOF_U16 + hash_index_size + SIZEOF_U16
     }
 
     /// Adds a new k-v pair(entry) to the block, return false when block is full
@@ -58,23 +81,37 @@ impl BlockBuilder {
         if size_expect > self.block_size && !self.is_empty() {
             return false;
         }
-        self.offsets.push(self.data.len() as u16);
-        let prefix = common_prefix(self.first_key.as_key_slice(), key);
+        if self.hash_index && (self.is_empty() || self.last_key.key_ref() != key.key_ref()) {
+            let restart_idx = self.restarts.len() - usize::from(!self.is_restart_point());
+            self.key_restarts
+                .push((farmhash::fingerprint32(key.key_ref()), restart_idx));
+        }
+        // restart points hold the full key, the others share a prefix with the previous key.
+        let prefix = if self.is_restart_point() {
+            self.restarts.push(self.data.len() as u16);
+            0
+        } else {
+            common_prefix(self.last_key.as_key_slice(), key)
+        };
         self.data.put_u16(prefix as u16);
         self.data.put_u16((key.key_len() - prefix) as u16);
         self.data.put(&key.key_ref()[prefix..]);
         self.data.put_u64(key.ts());
         self.data.put_u16(value.len() as u16);
         self.data.put(value);
-        if self.first_key.is_empty() {
-            self.first_key = key.to_key_vec();
-        }
+        self.last_key.set_from_slice(key);
+        self.num_entries += 1;
         true
     }
 
+    /// the next entry starts a new restart interval.
+    fn is_restart_point(&self) -> bool {
+        self.num_entries.is_multiple_of(RESTART_INTERVAL)
+    }
+
     /// check the blockbuilder whether it's empty or not.
     pub fn is_empty(&self) -> bool {
-        self.offsets.is_empty()
+        self.num_entries == 0
     }
 
     /// using BlockBuidler to build a block.
@@ -82,9 +119,26 @@ impl BlockBuilder {
         if self.is_empty() {
             panic!("block should not be empty!")
         }
+        let hash_index = (self.hash_index && self.restarts.len() <= MAX_HASHED_RESTARTS)
+            .then(|| self.build_hash_index());
         Block {
-            data: self.data,
-            offsets: self.offsets,
+            data: self.data.into(),
+            restarts: self.restarts,
+            hash_index,
+        }
+    }
+
+    fn build_hash_index(&self) -> Vec<u8> {
+        let num_buckets = self.num_buckets();
+        let mut buckets = vec![BUCKET_EMPTY; num_buckets];
+        for (hash, restart_idx) in &self.key_restarts {
+            let bucket = &mut buckets[*hash as usize % num_buckets];
+            *bucket = if *bucket == BUCKET_EMPTY || *bucket == *restart_idx as u8 {
+                *restart_idx as u8
+            } else {
+                BUCKET_COLLISION
+            };
         }
+        buckets
     }
 }
@@ -3,44 +3,24 @@ use bytes::Buf;
 use crate::key::{KeySlice, KeyVec};
 use std::sync::Arc;
 
-use super::{Block, SIZEOF_U16};
+use super::{Block, SIZEOF_U16, SIZEOF_U64};
 
 /// The Iterator Over Blocks
 /// So you can see all the key here is `KeyVec` means that
 /// Block has the true ownership of the data.
 pub struct BlockIterator {
     block: Arc<Block>,
-    // Block Metadata
-    idx: usize,
-    first_key: KeyVec,
+    // Current Entry's value, it ends where the next entry starts.
     value_range: (usize, usize),
-    // Current Entry's key
+    // Current Entry's key, the next entry is decoded against it.
     key: KeyVec,
 }
 
-impl Block {
-    /// get the first_key(the key and ts) from One Block
-    fn get_first_key(&self) -> KeyVec {
-        let mut buf = &self.data[..];
-        // skip the overlap(CommonPrefix)
-        buf.get_u16();
-        // get the key_len.
-        let key_len = buf.get_u16() as usize;
-        // get the key.
-        let key = &buf[..key_len as usize];
-        buf.advance(key_len);
-        // type convert: Merge-up the elements(the key, and the timestamp) to `KeyVec`.
-        KeyVec::from_vec_with_ts(key.to_vec(), buf.get_u64())
-    }
-}
-
 impl BlockIterator {
     // Constructor(Associate Function)
     fn new(block: Arc<Block>) -> Self {
         Self {
-            first_key: block.get_first_key(),
             block,
-            idx: 0,
             value_range: (0, 0),
             key: KeyVec::new(),
         }
@@ -48,7 +28,7 @@ impl BlockIterator {
 
     /*----------------- Accessors------------------*/
 
-    pub fn key(&self) -> KeySlice {
+    pub fn key(&self) -> KeySlice<'_> {
         self.key.as_key_slice()
     }
 
@@ -77,64 +57,91 @@ impl BlockIterator {
     }
 
     /// find the key (or first greater than the key)
+    /// binary search for the last restart point not after the key,
+    /// then scan forward through its restart interval.
     pub fn seek_to_key(&mut self, key: KeySlice) {
+        if self.seek_to_key_hashed(key) {
+            return;
+        }
         let mut low = 0;
-        let mut high = self.block.offsets.len();
+        let mut high = self.block.restarts.len();
         while low < high {
             let mid = low + (high - low) / 2;
-            self.seek_to(mid);
+            self.seek_to_restart(mid);
             assert!(self.is_valid());
-            match self.key().cmp(&key) {
-                std::cmp::Ordering::Less => low = mid + 1,
-                std::cmp::Ordering::Greater => high = mid,
-                std::cmp::Ordering::Equal => return,
+            if self.key() <= key {
+                low = mid + 1;
+            } else {
+                high = mid;
             }
         }
-        self.seek_to(low)
+        self.seek_to_restart(low.saturating_sub(1));
+        while self.is_valid() && self.key() < key {
+            self.next();
+        }
+    }
+
+    /// jump to the restart interval the hash index points the user key at and scan from there.
+    /// returns false when the index cannot tell where the key is, or the key is not in the block.
+    fn seek_to_key_hashed(&mut self, key: KeySlice) -> bool {
+        let Some(restart_idx) = self.block.hash_index_lookup(key.key_ref()) else {
+            return false;
+        };
+        self.seek_to_restart(restart_idx);
+        while self.is_valid() && self.key() < key {
+            self.next();
+        }
+        // the bucket may belong to another key, only trust it when ours is found.
+        self.is_valid() && self.key().key_ref() == key.key_ref()
     }
 
     /*------------------Util Methods-------------------- */
 
     /// find the first entry.
     pub fn seek_to_first(&mut self) {
-        self.seek_to(0);
+        self.seek_to_restart(0);
     }
 
     /// move to next entry.
     pub fn next(&mut self) {
-        self.idx += 1;
-        self.seek_to(self.idx);
+        if self.is_valid() {
+            self.seek_to_offset(self.value_range.1);
+        }
     }
 
-    /// seek to a specific index (the idx is `the ith of the entries `).
-    fn seek_to(&mut self, idx: usize) {
+    /// seek to a restart point (the idx is `the ith of the restarts `).
+    fn seek_to_restart(&mut self, idx: usize) {
         // check boundary.
-        if idx >= self.block.offsets.len() {
+        if idx >= self.block.restarts.len() {
             self.key.clear();
             self.value_range = (0, 0);
             return;
         }
-        // normal process.
-        let offset = self.block.offsets[idx] as usize;
+        // normal process: a restart point shares nothing with the previous key.
+        let offset = self.block.restarts[idx] as usize;
         self.seek_to_offset(offset);
-        self.idx = idx;
     }
 
     /// move to specified offset("per Bytes") and update the current key-value pair.
-    /// index update will be handled by caller
+    /// the entry's shared prefix is taken from the current key.
     fn seek_to_offset(&mut self, offset: usize) {
+        if offset >= self.block.data.len() {
+            self.key.clear();
+            self.value_range = (0, 0);
+            return;
+        }
         let mut entry = &self.block.data[offset..];
         let prefix = entry.get_u16() as usize;
         let key_len = entry.get_u16() as usize;
         let key = &entry[..key_len];
-        self.key.clear();
-        self.key.append(&self.first_key.key_ref()[..prefix]);
+        self.key.truncate(prefix);
         self.key.append(key);
         entry.advance(key_len);
+        self.key.set_ts(entry.get_u64());
         let value_len = entry.get_u16() as usize;
-        let value_offset_begin = offset + SIZEOF_U16 + SIZEOF_U16 + key_len + SIZEOF_U16;
+        let value_offset_begin =
+            offset + SIZEOF_U16 + SIZEOF_U16 + key_len + SIZEOF_U64 + SIZEOF_U16;
         let value_offset_end = value_offset_begin + value_len;
         self.value_range = (value_offset_begin, value_offset_end);
-        entry.advance(value_len);
     }
 }
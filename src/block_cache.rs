@@ -0,0 +1,341 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::{anyhow, Result};
+use moka::{notification::RemovalCause, policy::EvictionPolicy};
+use parking_lot::Mutex;
+
+use crate::{
+    block::Block,
+    table::{BlockMeta, Filter},
+};
+
+/// An entry of the block cache: a data block, or an index or filter partition
+/// loaded on demand.
+#[derive(Clone)]
+pub enum CachedBlock {
+    Data(Arc<Block>),
+    Index(Arc<Vec<BlockMeta>>),
+    Filter(Arc<Filter>),
+    /// bytes of memory held elsewhere charged against the capacity of a pool, see
+    /// [`BlockCache::reserve`].
+    Reservation(u64),
+}
+
+impl CachedBlock {
+    /// the bytes this entry is charged against the cache capacity.
+    pub fn charge(&self) -> usize {
+        match self {
+            CachedBlock::Data(block) => {
+                block.data.len()
+                    + block.restarts.len() * std::mem::size_of::<u16>()
+                    + block.hash_index.as_ref().map_or(0, |buckets| buckets.len())
+            }
+            CachedBlock::Index(metas) => metas.iter().map(BlockMeta::encoded_size).sum(),
+            CachedBlock::Filter(filter) => filter.size(),
+            CachedBlock::Reservation(bytes) => *bytes as usize,
+        }
+    }
+}
+
+/// Counters of a block cache, see [`BlockCache::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlockCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub inserts: u64,
+    /// entries dropped to stay within the capacity.
+    pub evictions: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    inserts: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// share of the capacity reserved for high priority entries by [`BlockCache::new`].
+pub const DEFAULT_HIGH_PRIORITY_RATIO: f64 = 0.1;
+
+type Pool = moka::sync::Cache<(usize, usize), CachedBlock>;
+
+/// the key of the entry charging the reservations to a pool, table ids start at 1.
+const RESERVATION_KEY: (usize, usize) = (0, 0);
+
+/// BlockCache for `read block from disk`, with a capacity in bytes.
+/// Entries are keyed by `(table cache id, file offset)`. Every SST opened on the cache
+/// gets its own cache id, so one cache can be shared by several storage instances.
+///
+/// Index and filter partitions go to a high priority pool of their own, so a scan
+/// streaming data blocks through the cache cannot evict them. The partitions it has
+/// no room for overflow into the low priority pool instead of being dropped.
+///
+/// Memory held elsewhere is charged by an entry of each pool weighing its share of the
+/// reservations, the low priority pool first, so moka evicts the blocks making room for
+/// it in LRU order.
+pub struct BlockCache {
+    high_priority: Pool,
+    low_priority: Pool,
+    capacity: u64,
+    // bytes of memory held elsewhere charged against the capacity.
+    reserved: AtomicU64,
+    // serializes the updates of the reservation entries.
+    reservation_lock: Mutex<()>,
+    next_table_id: AtomicUsize,
+    counters: Arc<Counters>,
+}
+
+impl BlockCache {
+    /// create a cache holding at most `capacity` bytes of blocks.
+    pub fn new(capacity: u64) -> Self {
+        Self::with_high_priority_ratio(capacity, DEFAULT_HIGH_PRIORITY_RATIO)
+    }
+
+    /// create a cache reserving `ratio` of its `capacity` for index and filter partitions.
+    pub fn with_high_priority_ratio(capacity: u64, ratio: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&ratio),
+            "high priority ratio must be within [0, 1]"
+        );
+        let counters = Arc::new(Counters::default());
+        let high_capacity = (capacity as f64 * ratio) as u64;
+        let low_priority = Self::create_pool(capacity - high_capacity, &counters, None);
+        Self {
+            high_priority: Self::create_pool(high_capacity, &counters, Some(low_priority.clone())),
+            low_priority,
+            capacity,
+            reserved: AtomicU64::new(0),
+            reservation_lock: Mutex::new(()),
+            next_table_id: AtomicUsize::new(1),
+            counters,
+        }
+    }
+
+    /// a pool of `capacity` bytes, moving the entries it evicts to `overflow` if any.
+    fn create_pool(capacity: u64, counters: &Arc<Counters>, overflow: Option<Pool>) -> Pool {
+        let counters = counters.clone();
+        moka::sync::Cache::builder()
+            .max_capacity(capacity)
+            .weigher(|_, block: &CachedBlock| block.charge().try_into().unwrap_or(u32::MAX))
+            .eviction_policy(EvictionPolicy::lru())
+            .eviction_listener(move |key, block, cause| {
+                if cause != RemovalCause::Size || *key == RESERVATION_KEY {
+                    return;
+                }
+                match overflow {
+                    Some(ref overflow) => overflow.insert(*key, block),
+                    None => {
+                        counters.evictions.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })
+            .build()
+    }
+
+    /// the pool of the entries of a priority, a high priority pool without capacity keeps
+    /// nothing so its entries go to the low priority pool.
+    fn pool(&self, high_priority: bool) -> &Pool {
+        if high_priority && self.high_priority.policy().max_capacity() != Some(0) {
+            &self.high_priority
+        } else {
+            &self.low_priority
+        }
+    }
+
+    /// look `key` up where an entry of its priority may be: the high priority entries
+    /// may have overflowed into the low priority pool.
+    fn lookup(&self, key: (usize, usize), high_priority: bool) -> Option<CachedBlock> {
+        let block = self.pool(high_priority).get(&key);
+        if high_priority {
+            block.or_else(|| self.low_priority.get(&key))
+        } else {
+            block
+        }
+    }
+
+    /// a cache id for a newly opened SST.
+    pub(crate) fn new_table_id(&self) -> usize {
+        self.next_table_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// look `key` up in the pool of its `priority`, reading the block with `read` on a miss.
+    pub(crate) fn try_get_with(
+        &self,
+        key: (usize, usize),
+        high_priority: bool,
+        read: impl FnOnce() -> Result<CachedBlock>,
+    ) -> Result<CachedBlock> {
+        if high_priority {
+            if let Some(block) = self.lookup(key, high_priority) {
+                self.counters.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(block);
+            }
+        }
+        let pool = self.pool(high_priority);
+        let mut missed = false;
+        let block = pool.try_get_with(key, || {
+            missed = true;
+            read()
+        });
+        if !missed {
+            self.counters.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.counters.misses.fetch_add(1, Ordering::Relaxed);
+            if block.is_ok() {
+                self.counters.inserts.fetch_add(1, Ordering::Relaxed);
+                self.touch_reservations();
+            }
+        }
+        block.map_err(|e| anyhow!("{}", e))
+    }
+
+    /// insert a block read without going through [`BlockCache::try_get_with`].
+    pub(crate) fn insert(&self, key: (usize, usize), high_priority: bool, block: CachedBlock) {
+        self.pool(high_priority).insert(key, block);
+        self.counters.inserts.fetch_add(1, Ordering::Relaxed);
+        self.touch_reservations();
+    }
+
+    /// charge `bytes` of memory held elsewhere, such as memtables, against the capacity,
+    /// evicting blocks to make room for them.
+    pub(crate) fn reserve(&self, bytes: u64) {
+        let _guard = self.reservation_lock.lock();
+        let reserved = self.reserved.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.update_reservations(reserved, true);
+    }
+
+    /// give back bytes taken by [`BlockCache::reserve`].
+    pub(crate) fn release(&self, bytes: u64) {
+        let _guard = self.reservation_lock.lock();
+        let reserved = self.reserved.fetch_sub(bytes, Ordering::Relaxed) - bytes;
+        self.update_reservations(reserved, true);
+    }
+
+    /// the share of `reserved` charged to each pool, the low priority pool first.
+    fn reservation_shares(&self, reserved: u64) -> [(&Pool, u64); 2] {
+        let low = reserved.min(self.low_priority.policy().max_capacity().unwrap_or(0));
+        let high = (reserved - low).min(self.high_priority.policy().max_capacity().unwrap_or(0));
+        [(&self.low_priority, low), (&self.high_priority, high)]
+    }
+
+    /// charge `reserved` with the reservation entries, replacing them unless `update` is
+    /// false and they are still in their pools.
+    fn update_reservations(&self, reserved: u64, update: bool) {
+        for (pool, charge) in self.reservation_shares(reserved) {
+            if charge == 0 {
+                pool.invalidate(&RESERVATION_KEY);
+            } else if update || !pool.contains_key(&RESERVATION_KEY) {
+                pool.insert(RESERVATION_KEY, CachedBlock::Reservation(charge));
+            }
+        }
+    }
+
+    /// move the reservation entries to the most recently used end of their pools, so the
+    /// blocks are evicted before them, and put back the ones evicted anyway.
+    fn touch_reservations(&self) {
+        let reserved = self.reserved.load(Ordering::Relaxed);
+        if reserved == 0 {
+            return;
+        }
+        for (pool, charge) in self.reservation_shares(reserved) {
+            if charge > 0 && pool.get(&RESERVATION_KEY).is_none() {
+                let _guard = self.reservation_lock.lock();
+                self.update_reservations(self.reserved.load(Ordering::Relaxed), false);
+                return;
+            }
+        }
+    }
+
+    /// the bytes the reservation entry of `pool` charges.
+    fn reservation_of(pool: &Pool) -> u64 {
+        match pool.get(&RESERVATION_KEY) {
+            Some(CachedBlock::Reservation(bytes)) => bytes,
+            _ => 0,
+        }
+    }
+
+    /// look `key` up without inserting anything on a miss.
+    pub(crate) fn get(&self, key: (usize, usize), high_priority: bool) -> Option<CachedBlock> {
+        let block = self.lookup(key, high_priority);
+        let counter = if block.is_some() {
+            &self.counters.hits
+        } else {
+            &self.counters.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        block
+    }
+
+    /// a snapshot of the counters since the cache was created.
+    pub fn stats(&self) -> BlockCacheStats {
+        BlockCacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            inserts: self.counters.inserts.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    /// the capacity in bytes.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// the bytes currently charged by the blocks, up to date after [`BlockCache::sync`].
+    pub fn usage(&self) -> u64 {
+        [&self.high_priority, &self.low_priority]
+            .into_iter()
+            .map(|pool| {
+                pool.weighted_size()
+                    .saturating_sub(Self::reservation_of(pool))
+            })
+            .sum()
+    }
+
+    /// the bytes of memory held elsewhere charged against the capacity.
+    pub fn reserved(&self) -> u64 {
+        self.reserved.load(Ordering::Relaxed)
+    }
+
+    /// the bytes charged by the index and filter partitions in the high priority pool.
+    pub fn high_priority_usage(&self) -> u64 {
+        self.high_priority
+            .weighted_size()
+            .saturating_sub(Self::reservation_of(&self.high_priority))
+    }
+
+    /// the blocks currently cached, up to date after [`BlockCache::sync`].
+    pub fn entry_count(&self) -> u64 {
+        [&self.high_priority, &self.low_priority]
+            .into_iter()
+            .map(|pool| {
+                pool.entry_count()
+                    .saturating_sub(pool.contains_key(&RESERVATION_KEY) as u64)
+            })
+            .sum()
+    }
+
+    /// run the pending evictions and refresh `usage` and `entry_count`.
+    pub fn sync(&self) {
+        self.high_priority.run_pending_tasks();
+        self.low_priority.run_pending_tasks();
+    }
+}
+
+impl fmt::Debug for BlockCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockCache")
+            .field("capacity", &self.capacity)
+            .field("usage", &self.usage())
+            .field("reserved", &self.reserved())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
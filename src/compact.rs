@@ -1,16 +1,27 @@
 #![allow(dead_code)]
 #![allow(unused)]
+mod filter;
 mod leveled;
 
 use crate::iterators::*;
-use crate::key::KeySlice;
+use crate::key::{self, KeySlice};
 use crate::table::{SsTable, SsTableBuilder, SsTableIterator};
-use crate::{iterators::StorageIterator, manifest::ManifestRecord};
-use anyhow::Result;
+use crate::{
+    iterators::StorageIterator,
+    manifest::{ManifestRecord, SstMeta},
+};
+use anyhow::{bail, Result};
+use bytes::Bytes;
 use crossbeam::channel::{self, Receiver};
+pub use filter::{
+    CompactionFilter, CompactionFilterContext, CompactionFilterDecision, CompactionFilterFactory,
+    PrefixCompactionFilter,
+};
 pub use leveled::{LeveledCompactionController, LeveledCompactionTask};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::ops::Bound;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
@@ -19,9 +30,12 @@ use self::concat_iterator::SstConcatIterator;
 pub use self::leveled::LeveledCompactionOptions;
 use self::merge_iterator::MergeIterator;
 use self::two_merge_iterator::TwoMergeIterator;
-use crate::lsm_storage::{CompactionFilter, LsmStorageInner, LsmStorageState};
+use crate::lsm_storage::{LsmStorageInner, LsmStorageState, ReadOptions};
 use crossbeam::select;
 
+/// bytes compaction reads ahead of its inputs when it bypasses the page cache.
+const COMPACTION_READAHEAD_BYTES: usize = 2 << 20;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum CompactionTask {
     Leveled(LeveledCompactionTask),
@@ -38,6 +52,45 @@ impl CompactionTask {
             CompactionTask::Leveled(task) => task.is_lower_level_bottom_level,
         }
     }
+
+    /// the level the upper level inputs are read from, `None` for L0.
+    fn upper_level(&self) -> Option<usize> {
+        match self {
+            CompactionTask::ForceFullCompaction { .. } => None,
+            CompactionTask::Leveled(task) => task.upper_level,
+        }
+    }
+
+    /// the level the compaction output is written to.
+    fn output_level(&self) -> usize {
+        match self {
+            CompactionTask::ForceFullCompaction { .. } => 1,
+            CompactionTask::Leveled(task) => task.lower_level,
+        }
+    }
+
+    pub(crate) fn is_trivial_move(&self) -> bool {
+        match self {
+            CompactionTask::ForceFullCompaction { .. } => false,
+            CompactionTask::Leveled(task) => task.is_trivial_move,
+        }
+    }
+
+    /// every SST read (and replaced) by this task.
+    pub(crate) fn input_sst_ids(&self) -> Vec<usize> {
+        match self {
+            CompactionTask::ForceFullCompaction {
+                l0_sstables,
+                l1_sstables,
+            } => l0_sstables.iter().chain(l1_sstables).copied().collect(),
+            CompactionTask::Leveled(task) => task
+                .upper_level_sst_ids
+                .iter()
+                .chain(&task.lower_level_sst_ids)
+                .copied()
+                .collect(),
+        }
+    }
 }
 
 /// Controller for different Compaction strategy
@@ -61,10 +114,29 @@ impl CompactionController {
         snapshot: &LsmStorageState,
         task: &CompactionTask,
         output: &[usize],
+        in_recovery: bool,
     ) -> (LsmStorageState, Vec<usize>) {
         match (self, task) {
             (CompactionController::Leveled(ctrl), CompactionTask::Leveled(task)) => {
-                ctrl.apply_compaction_result(snapshot, task, output)
+                ctrl.apply_compaction_result(snapshot, task, output, in_recovery)
+            }
+            (
+                _,
+                CompactionTask::ForceFullCompaction {
+                    l0_sstables,
+                    l1_sstables,
+                },
+            ) => {
+                let mut snapshot = snapshot.clone();
+                let l0_sstables = l0_sstables.iter().collect::<HashSet<_>>();
+                snapshot.l0_sstables.retain(|x| !l0_sstables.contains(x));
+                snapshot.levels[0].1 = output.to_vec();
+                let files_to_remove = l0_sstables
+                    .into_iter()
+                    .chain(l1_sstables)
+                    .copied()
+                    .collect();
+                (snapshot, files_to_remove)
             }
             _ => unreachable!(),
         }
@@ -93,29 +165,60 @@ impl LsmStorageInner {
         let CompactionOptions::NoCompaction = self.options.compaction_options else {
             panic!("full compaction can only be called with compaction is not enabled")
         };
-        let snapshot = {
-            let state = self.state.read();
-            state.clone()
+        // mark the SSTs as being compacted, so ingestion adds no SST to L1 meanwhile.
+        let (l0_sstables, l1_sstables) = {
+            let mut state_lock = self.state_lock.lock();
+            loop {
+                let mut snapshot = self.state.read().as_ref().clone();
+                let l0_sstables = snapshot.l0_sstables.clone();
+                let l1_sstables = snapshot.levels[0].1.clone();
+                if l0_sstables
+                    .iter()
+                    .chain(&l1_sstables)
+                    .any(|id| snapshot.compacting_sstables.contains(id))
+                {
+                    self.compaction_inputs_released.wait(&mut state_lock);
+                    continue;
+                }
+                snapshot
+                    .compacting_sstables
+                    .extend(l0_sstables.iter().chain(&l1_sstables));
+                *self.state.write() = Arc::new(snapshot);
+                break (l0_sstables, l1_sstables);
+            }
         };
+        // with nothing to compact, L1 is not marked and may get ingested SSTs meanwhile.
+        if l0_sstables.is_empty() && l1_sstables.is_empty() {
+            return Ok(());
+        }
 
         // step2. genereate taks and execute it.
-        let l0_sstables = snapshot.l0_sstables.clone();
-        let l1_sstables = snapshot.levels[0].1.clone();
         let compaction_task = CompactionTask::ForceFullCompaction {
             l0_sstables: l0_sstables.clone(),
             l1_sstables: l1_sstables.clone(),
         };
+        // however the compaction ends, its inputs go back to the compaction picker.
+        let _inputs = CompactionInputs {
+            inner: self,
+            input_sst_ids: compaction_task.input_sst_ids(),
+        };
         println!("force full compaction: {:?}", compaction_task);
         let sstables = self.compact(&compaction_task)?;
 
         // step3. finish touches (update state, make records, persistence etc)
+        let sst_metas = sstables
+            .iter()
+            .map(|sst| SstMeta::of(sst))
+            .collect::<Vec<_>>();
         let mut ids = Vec::with_capacity(sstables.len());
+        let mut ssts_to_remove = Vec::with_capacity(l0_sstables.len() + l1_sstables.len());
         {
             let state_lock = self.state_lock.lock();
             let mut state = self.state.read().as_ref().clone();
             for sst in l0_sstables.iter().chain(l1_sstables.iter()) {
                 let result = state.sstables.remove(sst);
                 assert!(result.is_some());
+                ssts_to_remove.extend(result);
             }
             for new_sst in sstables {
                 ids.push(new_sst.sst_id());
@@ -132,25 +235,193 @@ impl LsmStorageInner {
                 .copied()
                 .collect::<Vec<_>>();
             assert!(l0_sstables_map.is_empty());
+            for id in l0_sstables.iter().chain(&l1_sstables) {
+                state.compacting_sstables.remove(id);
+            }
             *self.state.write() = Arc::new(state);
+            self.compaction_inputs_released.notify_all();
             self.sync_dir()?;
+            if !sst_metas.is_empty() {
+                self.manifest()
+                    .add_record(&state_lock, ManifestRecord::NewSsts(sst_metas))?;
+            }
             self.manifest.as_ref().unwrap().add_record(
                 &state_lock,
                 ManifestRecord::Compaction(compaction_task, ids.clone()),
             )?;
         }
-        for sst in l0_sstables.iter().chain(l1_sstables.iter()) {
-            std::fs::remove_file(self.path_of_sst(*sst))?;
+        for sst in ssts_to_remove {
+            self.remove_sst_file(&sst)?;
         }
         println!("force full compaction done, new SSTs: {:?}", ids);
 
         Ok(())
     }
 
+    /// compacts every SST overlapping the user key range `[lower, upper]`
+    /// level by level, from L0 down to `target_level`, while leveled compaction keeps running.
+    /// data still in the memtables is not touched, flush it first if it should be included.
+    pub fn compact_range(
+        &self,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        target_level: usize,
+    ) -> Result<()> {
+        let CompactionController::Leveled(controller) = &self.compaction_controller else {
+            bail!("range compaction requires leveled compaction to be enabled");
+        };
+        if target_level == 0 || target_level > controller.max_levels() {
+            bail!(
+                "target level must be within 1..={}, got {}",
+                controller.max_levels(),
+                target_level
+            );
+        }
+        for level in 0..target_level {
+            // the background compactions may own some of the inputs, wait for them to finish.
+            let task = loop {
+                let mut state_lock = self.state_lock.lock();
+                let mut snapshot = self.state.read().as_ref().clone();
+                let Some(task) =
+                    controller.generate_range_compaction_task(&snapshot, level, lower, upper)
+                else {
+                    break None;
+                };
+                let task = CompactionTask::Leveled(task);
+                let input_sst_ids = task.input_sst_ids();
+                // like the picker, never run two L0 compactions at the same time.
+                let l0_sstables = if level == 0 {
+                    &snapshot.l0_sstables[..]
+                } else {
+                    &[]
+                };
+                if input_sst_ids
+                    .iter()
+                    .chain(l0_sstables)
+                    .any(|id| snapshot.compacting_sstables.contains(id))
+                {
+                    self.compaction_inputs_released.wait(&mut state_lock);
+                    continue;
+                }
+                snapshot.compacting_sstables.extend(input_sst_ids);
+                *self.state.write() = Arc::new(snapshot);
+                break Some(task);
+            };
+            if let Some(task) = task {
+                self.run_compaction_task(task)?;
+            }
+        }
+        Ok(())
+    }
+
     fn compact(&self, task: &CompactionTask) -> Result<Vec<Arc<SsTable>>> {
-        let snapshot = {
-            let state = self.state.read();
-            state.clone()
+        let snapshot = self.state.read();
+        let boundaries = self.plan_sub_compactions(&snapshot, task)?;
+        if boundaries.is_empty() {
+            return self.compact_sub_range(&snapshot, task, None, None);
+        }
+        // sub-compaction i covers user keys in [boundaries[i - 1], boundaries[i]),
+        // the first one is unbounded below and the last one is unbounded above.
+        let ranges = (0..=boundaries.len())
+            .map(|i| {
+                let lower = i.checked_sub(1).map(|x| &boundaries[x][..]);
+                let upper = boundaries.get(i).map(|x| &x[..]);
+                (lower, upper)
+            })
+            .collect::<Vec<_>>();
+        let outputs = thread::scope(|scope| {
+            let handles = ranges
+                .into_iter()
+                .map(|(lower, upper)| {
+                    let snapshot = &snapshot;
+                    scope.spawn(move || self.compact_sub_range(snapshot, task, lower, upper))
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .map_err(|e| anyhow::anyhow!("sub-compaction panicked: {:?}", e))?
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+        // sub-compactions are disjoint and ordered, so concatenating keeps the key order.
+        Ok(outputs.into_iter().flatten().collect())
+    }
+
+    /// split the key space of a compaction task into at most `max_subcompactions`
+    /// disjoint ranges, using the block boundaries of the input SSTs.
+    /// returns the user keys at which the ranges are cut (empty if no split is needed).
+    fn plan_sub_compactions(
+        &self,
+        snapshot: &LsmStorageState,
+        task: &CompactionTask,
+    ) -> Result<Vec<Vec<u8>>> {
+        let max_subcompactions = self.options.max_subcompactions;
+        if max_subcompactions <= 1 {
+            return Ok(Vec::new());
+        }
+        let mut block_keys = Vec::new();
+        for id in task.input_sst_ids() {
+            block_keys.extend(snapshot.sstables[&id].block_first_keys()?);
+        }
+        block_keys.sort();
+        block_keys.dedup();
+        // the smallest key can never be a cut point, otherwise the first range is empty.
+        if block_keys.len() <= 1 {
+            return Ok(Vec::new());
+        }
+        let candidates = &block_keys[1..];
+        let num_ranges = max_subcompactions.min(candidates.len() + 1);
+        Ok((1..num_ranges)
+            .map(|i| candidates[i * candidates.len() / num_ranges].to_vec())
+            .collect())
+    }
+
+    /// how compaction reads its input SSTs.
+    fn compaction_read_options(&self) -> ReadOptions {
+        if !self.options.use_direct_io_for_compaction {
+            return ReadOptions::default();
+        }
+        ReadOptions {
+            fill_cache: false,
+            readahead_bytes: COMPACTION_READAHEAD_BYTES,
+            direct_io: true,
+            ..Default::default()
+        }
+    }
+
+    /// a builder for an SST compaction writes to `level`.
+    fn new_compaction_builder(&self, level: usize, bottommost: bool) -> SsTableBuilder {
+        self.new_sst_builder(level, bottommost)
+            .with_direct_io(self.options.use_direct_io_for_compaction)
+    }
+
+    /// compact the part of `task` whose user keys lie in `[lower, upper)`.
+    fn compact_sub_range(
+        &self,
+        snapshot: &LsmStorageState,
+        task: &CompactionTask,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+    ) -> Result<Vec<Arc<SsTable>>> {
+        let options = self.compaction_read_options();
+        let seek_table = |table: Arc<SsTable>| match lower {
+            Some(key) => SsTableIterator::create_and_seek_to_key_with_options(
+                table,
+                KeySlice::from_slice(key, key::TS_RANGE_BEGIN),
+                options,
+            ),
+            None => SsTableIterator::create_and_seek_to_first_with_options(table, options),
+        };
+        let seek_level = |tables: Vec<Arc<SsTable>>| match lower {
+            Some(key) => SstConcatIterator::create_and_seek_to_key_with_options(
+                tables,
+                KeySlice::from_slice(key, key::TS_RANGE_BEGIN),
+                options,
+            ),
+            None => SstConcatIterator::create_and_seek_to_first_with_options(tables, options),
         };
         match task {
             CompactionTask::ForceFullCompaction {
@@ -159,7 +430,7 @@ impl LsmStorageInner {
             } => {
                 let mut l0_iters = Vec::with_capacity(l0_sstables.len());
                 for id in l0_sstables.iter() {
-                    l0_iters.push(Box::new(SsTableIterator::create_and_seek_to_first(
+                    l0_iters.push(Box::new(seek_table(
                         snapshot.sstables.get(id).unwrap().clone(),
                     )?));
                 }
@@ -169,9 +440,9 @@ impl LsmStorageInner {
                 }
                 let iter = TwoMergeIterator::create(
                     MergeIterator::create(l0_iters),
-                    SstConcatIterator::create_and_seek_to_first(l1_iters)?,
+                    seek_level(l1_iters)?,
                 )?;
-                self.compact_generate_sst(iter, task.compact_to_bottom_level())
+                self.compact_generate_sst(iter, task, upper)
             }
             CompactionTask::Leveled(LeveledCompactionTask {
                 upper_level,
@@ -185,21 +456,22 @@ impl LsmStorageInner {
                     for id in upper_level_sst_ids.iter() {
                         upper_ssts.push(snapshot.sstables.get(id).unwrap().clone());
                     }
-                    let upper_iter = SstConcatIterator::create_and_seek_to_first(upper_ssts)?;
+                    let upper_iter = seek_level(upper_ssts)?;
                     let mut lower_ssts = Vec::with_capacity(upper_level_sst_ids.len());
                     for id in lower_level_sst_ids.iter() {
                         lower_ssts.push(snapshot.sstables.get(id).unwrap().clone());
                     }
-                    let lower_iter = SstConcatIterator::create_and_seek_to_first(lower_ssts)?;
+                    let lower_iter = seek_level(lower_ssts)?;
                     self.compact_generate_sst(
                         TwoMergeIterator::create(upper_iter, lower_iter)?,
-                        task.compact_to_bottom_level(),
+                        task,
+                        upper,
                     )
                 }
                 None => {
                     let mut upper_iters = Vec::with_capacity(upper_level_sst_ids.len());
                     for id in upper_level_sst_ids.iter() {
-                        upper_iters.push(Box::new(SsTableIterator::create_and_seek_to_first(
+                        upper_iters.push(Box::new(seek_table(
                             snapshot.sstables.get(id).unwrap().clone(),
                         )?));
                     }
@@ -208,32 +480,57 @@ impl LsmStorageInner {
                     for id in lower_level_sst_ids.iter() {
                         lower_ssts.push(snapshot.sstables.get(id).unwrap().clone());
                     }
-                    let lower_iter = SstConcatIterator::create_and_seek_to_first(lower_ssts)?;
+                    let lower_iter = seek_level(lower_ssts)?;
                     self.compact_generate_sst(
                         TwoMergeIterator::create(upper_iter, lower_iter)?,
-                        task.compact_to_bottom_level(),
+                        task,
+                        upper,
                     )
                 }
             },
         }
     }
 
+    /// one filter from every registered factory, for a single (sub-)compaction.
+    fn create_compaction_filters(
+        &self,
+        context: CompactionFilterContext,
+    ) -> Vec<Box<dyn CompactionFilter>> {
+        self.compaction_filters
+            .lock()
+            .iter()
+            .map(|factory| factory.create_compaction_filter(context))
+            .collect()
+    }
+
     /// compact and organize data stored in the LSM storage engine into SSTables.
     /// responsible for generating new SSTables during compaction.
+    /// stops before the first user key `>= upper` when an upper bound is given.
     fn compact_generate_sst(
         &self,
         mut iter: impl for<'a> StorageIterator<KeyType<'a> = KeySlice<'a>>,
-        compact_to_bottom_level: bool,
+        task: &CompactionTask,
+        upper: Option<&[u8]>,
     ) -> Result<Vec<Arc<SsTable>>> {
         let mut builder = None;
         let mut new_sst = Vec::new();
         let watermark = self.mvcc().watermark();
         let mut last_key = Vec::<u8>::new();
         let mut first_key_below_watermark = false;
-        let compaction_filters = self.compaction_filters.lock().clone();
-        'outer: while iter.is_valid() {
+        let compact_to_bottom_level = task.compact_to_bottom_level();
+        let output_level = task.output_level();
+        let context = CompactionFilterContext {
+            compact_to_bottom_level,
+        };
+        let mut compaction_filters = self.create_compaction_filters(context);
+        while iter.is_valid() {
+            if let Some(upper) = upper {
+                if iter.key().key_ref() >= upper {
+                    break;
+                }
+            }
             if builder.is_none() {
-                builder = Some(SsTableBuilder::new(self.options.block_size));
+                builder = Some(self.new_compaction_builder(output_level, compact_to_bottom_level));
             }
 
             let same_as_last_key = iter.key().key_ref() == last_key;
@@ -253,6 +550,8 @@ impl LsmStorageInner {
                 continue;
             }
 
+            let mut new_value: Option<Bytes> = None;
+            let mut removed = false;
             if iter.key().ts() <= watermark {
                 if same_as_last_key && !first_key_below_watermark {
                     iter.next()?;
@@ -261,35 +560,50 @@ impl LsmStorageInner {
 
                 first_key_below_watermark = false;
 
-                if !compaction_filters.is_empty() {
-                    for filter in &compaction_filters {
-                        match filter {
-                            CompactionFilter::Prefix(x) => {
-                                if iter.key().key_ref().starts_with(x) {
-                                    iter.next()?;
-                                    continue 'outer;
-                                }
+                if !iter.value().is_empty() {
+                    for filter in &mut compaction_filters {
+                        let value = new_value.as_deref().unwrap_or(iter.value());
+                        match filter.filter(iter.key().key_ref(), iter.key().ts(), value) {
+                            CompactionFilterDecision::Keep => {}
+                            CompactionFilterDecision::Remove => {
+                                removed = true;
+                                break;
                             }
+                            CompactionFilterDecision::ChangeValue(value) => new_value = Some(value),
                         }
                     }
                 }
             }
 
+            if removed {
+                if compact_to_bottom_level {
+                    if !same_as_last_key {
+                        last_key.clear();
+                        last_key.extend(iter.key().key_ref());
+                    }
+                    iter.next()?;
+                    continue;
+                }
+                // an older version may still live in a deeper level, hide it with a tombstone.
+                new_value = Some(Bytes::new());
+            }
+
             let builder_inner = builder.as_mut().unwrap();
             if builder_inner.estimate_size() >= self.options.target_sst_size && !same_as_last_key {
                 let sst_id = self.next_sst_id();
                 let old_builder = builder.take().unwrap();
-                let sst = Arc::new(old_builder.build(
+                let sst = old_builder.build(
                     sst_id,
                     Some(self.block_cache.clone()),
                     self.path_of_sst(sst_id),
-                )?);
+                )?;
+                let sst = Arc::new(self.configure_sst(sst, output_level)?);
                 new_sst.push(sst);
-                builder = Some(SsTableBuilder::new(self.options.block_size));
+                builder = Some(self.new_compaction_builder(output_level, compact_to_bottom_level));
             }
 
             let builder_inner = builder.as_mut().unwrap();
-            builder_inner.add(iter.key(), iter.value());
+            builder_inner.add(iter.key(), new_value.as_deref().unwrap_or(iter.value()));
 
             if !same_as_last_key {
                 last_key.clear();
@@ -300,12 +614,12 @@ impl LsmStorageInner {
         }
         if let Some(builder) = builder {
             let sst_id = self.next_sst_id(); // lock dropped here
-            let sst = Arc::new(builder.build(
+            let sst = builder.build(
                 sst_id,
                 Some(self.block_cache.clone()),
                 self.path_of_sst(sst_id),
-            )?);
-            new_sst.push(sst);
+            )?;
+            new_sst.push(Arc::new(self.configure_sst(sst, output_level)?));
         }
         Ok(new_sst)
     }
@@ -324,7 +638,14 @@ impl LsmStorageInner {
                         recv(ticker) -> _ => if let Err(e) = this.trigger_compaction() {
                             eprintln!("compaction failed: {}", e);
                         },
-                        recv(rx) -> _ => return
+                        recv(rx) -> _ => {
+                            // let the running compactions finish before shutting down.
+                            let workers = std::mem::take(&mut *this.compaction_workers.lock());
+                            for worker in workers {
+                                worker.join().ok();
+                            }
+                            return;
+                        }
                     }
                 }
             });
@@ -334,38 +655,95 @@ impl LsmStorageInner {
     }
 
     /// Initiates the compaction process within the storage system.
-    fn trigger_compaction(&self) -> Result<()> {
-        // Retrieves a snapshot of the current storage system state.
-        let snapshot = {
-            let state = self.state.read();
-            state.clone()
-        };
-        // Generates a compaction task based on the snapshot
-        // using the compaction controller.
-        // If no compaction task is generated (indicating no compaction is needed),
-        // returns early with Ok(()).
+    /// keeps scheduling tasks on disjoint SSTs until `max_background_compactions`
+    /// of them are running or there is nothing left to compact.
+    fn trigger_compaction(self: &Arc<Self>) -> Result<()> {
+        while self.running_compactions.load(Ordering::SeqCst)
+            < self.options.max_background_compactions.max(1)
+        {
+            let Some(task) = self.pick_compaction_task() else {
+                return Ok(());
+            };
+            self.running_compactions.fetch_add(1, Ordering::SeqCst);
+            let this = self.clone();
+            let worker = thread::spawn(move || {
+                if let Err(e) = this.run_compaction_task(task) {
+                    eprintln!("compaction failed: {}", e);
+                }
+                this.running_compactions.fetch_sub(1, Ordering::SeqCst);
+            });
+            let mut workers = self.compaction_workers.lock();
+            workers.retain(|worker| !worker.is_finished());
+            workers.push(worker);
+        }
+        Ok(())
+    }
+
+    /// Generates a compaction task based on the current state
+    /// and marks its SSTs as being compacted, so that other tasks skip them.
+    fn pick_compaction_task(&self) -> Option<CompactionTask> {
+        let _state_lock = self.state_lock.lock();
+        let mut snapshot = self.state.read().as_ref().clone();
         let task = self
             .compaction_controller
-            .generate_compaction_task(&snapshot);
-        let Some(task) = task else {
-            return Ok(());
-        };
+            .generate_compaction_task(&snapshot)?;
+        snapshot.compacting_sstables.extend(task.input_sst_ids());
+        *self.state.write() = Arc::new(snapshot);
+        Some(task)
+    }
+
+    /// Give the SSTs of a task back to the compaction picker, unless its result already did.
+    fn release_compaction_inputs(&self, input_sst_ids: &[usize]) {
+        let _state_lock = self.state_lock.lock();
+        let snapshot = self.state.read();
+        if !input_sst_ids
+            .iter()
+            .any(|id| snapshot.compacting_sstables.contains(id))
+        {
+            return;
+        }
+        let mut snapshot = snapshot.as_ref().clone();
+        for id in input_sst_ids {
+            snapshot.compacting_sstables.remove(id);
+        }
+        *self.state.write() = Arc::new(snapshot);
+        self.compaction_inputs_released.notify_all();
+    }
+
+    /// Executes one compaction task and installs its result.
+    fn run_compaction_task(&self, task: CompactionTask) -> Result<()> {
         self.dump_structure();
         println!("running compaction task: {:?}", task);
         // Executes the compaction task by calling the compact function,
         // which compacts the data according to the task.
-        let sstables = self.compact(&task)?;
+        // A trivial move has nothing to merge, its SSTs simply change level.
+        // however the task ends, its inputs go back to the compaction picker.
+        let _inputs = CompactionInputs {
+            inner: self,
+            input_sst_ids: task.input_sst_ids(),
+        };
+        let sstables = if task.is_trivial_move() {
+            Vec::new()
+        } else {
+            self.compact(&task)?
+        };
         // Updates the state by applying the compaction result and synchronizing the directory.
-        let output = sstables.iter().map(|x| x.sst_id()).collect::<Vec<_>>();
+        let output = if task.is_trivial_move() {
+            task.input_sst_ids()
+        } else {
+            sstables.iter().map(|x| x.sst_id()).collect::<Vec<_>>()
+        };
         // Removes old SSTables that were replaced during compaction and synchronizes the directory again for cleanup.
         let ssts_to_remove = {
             // Preparation and Setup:
             let state_lock = self.state_lock.lock();
             let mut snapshot = self.state.read().as_ref().clone();
-            let mut new_sst_ids = Vec::new();
+            let sst_metas = sstables
+                .iter()
+                .map(|sst| SstMeta::of(sst))
+                .collect::<Vec<_>>();
             // Compaction Operations: file_to_add, ssts_to_remove
             for file_to_add in sstables {
-                new_sst_ids.push(file_to_add.sst_id());
                 let result = snapshot.sstables.insert(file_to_add.sst_id(), file_to_add);
                 assert!(result.is_none());
             }
@@ -373,20 +751,39 @@ impl LsmStorageInner {
             // which may involve removing old SSTables.
             let (mut snapshot, files_to_remove) = self
                 .compaction_controller
-                .apply_compaction_result(&snapshot, &task, &output);
+                .apply_compaction_result(&snapshot, &task, &output, false);
             let mut ssts_to_remove = Vec::with_capacity(files_to_remove.len());
             for file_to_remove in &files_to_remove {
                 let result = snapshot.sstables.remove(file_to_remove);
                 assert!(result.is_some(), "cannot remove {}.sst", file_to_remove);
                 ssts_to_remove.push(result.unwrap());
             }
+            for id in task.input_sst_ids() {
+                snapshot.compacting_sstables.remove(&id);
+            }
+            // L0 SSTs moved down as they are give their pinned partitions back to the cache.
+            if task.is_trivial_move()
+                && task.upper_level().is_none()
+                && self.options.cache_index_and_filter_blocks
+            {
+                for id in &output {
+                    snapshot.sstables[id].unpin_meta_blocks();
+                }
+            }
             let mut state = self.state.write();
             *state = Arc::new(snapshot);
             drop(state);
+            self.compaction_inputs_released.notify_all();
             // finish touch: Sync and Updates
             self.sync_dir()?;
-            self.manifest()
-                .add_record(&state_lock, ManifestRecord::Compaction(task, new_sst_ids))?;
+            if !sst_metas.is_empty() {
+                self.manifest()
+                    .add_record(&state_lock, ManifestRecord::NewSsts(sst_metas))?;
+            }
+            self.manifest().add_record(
+                &state_lock,
+                ManifestRecord::Compaction(task, output.clone()),
+            )?;
             ssts_to_remove
         };
         println!(
@@ -396,7 +793,7 @@ impl LsmStorageInner {
             output
         );
         for sst in ssts_to_remove {
-            std::fs::remove_file(self.path_of_sst(sst.sst_id()))?;
+            self.remove_sst_file(&sst)?;
         }
         self.sync_dir()?;
 
@@ -410,17 +807,29 @@ impl LsmStorageInner {
         let this = self.clone();
         let handle = thread::spawn(move || {
             let ticker = channel::tick(Duration::from_millis(50));
-            channel::select! {
-                recv(ticker) -> _ => if let Err(e) = this.trigger_flush() {
-                    eprintln!("error occured: {}!",e);
-                },
-                recv(rx) -> _ => return
+            loop {
+                channel::select! {
+                    recv(ticker) -> _ => if let Err(e) = this.trigger_flush() {
+                        eprintln!("error occured: {}!",e);
+                    },
+                    recv(rx) -> _ => return
+                }
             }
         });
         Ok(Some(handle))
     }
 
     fn trigger_flush(&self) -> Result<()> {
+        // asked by the write buffer manager, flush every memtable.
+        if self.take_flush_request() {
+            if !self.state.read().memtable.is_empty() {
+                self.freeze_memtable_outside_writes()?;
+            }
+            while !self.state.read().imm_memtables.is_empty() {
+                self.force_flush_next_imm_memtable()?;
+            }
+            return Ok(());
+        }
         let cond = {
             let state = self.state.read();
             state.imm_memtables.len() >= self.options.num_memtable_limit
@@ -431,3 +840,15 @@ impl LsmStorageInner {
         Ok(())
     }
 }
+
+/// The input SSTs of a running compaction task, released when it ends.
+struct CompactionInputs<'a> {
+    inner: &'a LsmStorageInner,
+    input_sst_ids: Vec<usize>,
+}
+
+impl Drop for CompactionInputs<'_> {
+    fn drop(&mut self) {
+        self.inner.release_compaction_inputs(&self.input_sst_ids);
+    }
+}
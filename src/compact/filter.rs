@@ -0,0 +1,68 @@
+use bytes::Bytes;
+
+/// What a compaction filter decides to do with one version of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompactionFilterDecision {
+    /// write the version out unchanged.
+    Keep,
+    /// drop the version from the compaction output.
+    Remove,
+    /// write the version out with a new value.
+    ChangeValue(Bytes),
+}
+
+/// Describes the compaction a filter is created for.
+#[derive(Debug, Clone, Copy)]
+pub struct CompactionFilterContext {
+    /// the output goes to the bottom level, where tombstones are purged.
+    pub compact_to_bottom_level: bool,
+}
+
+/// A user-defined filter run over the versions written by a compaction.
+/// Only versions that no snapshot can read any more (at or below the watermark)
+/// are passed in, tombstones never are.
+pub trait CompactionFilter: Send {
+    fn filter(&mut self, key: &[u8], ts: u64, value: &[u8]) -> CompactionFilterDecision;
+}
+
+/// Creates a fresh filter for every compaction, so the filter may keep state across keys.
+pub trait CompactionFilterFactory: Send + Sync {
+    fn create_compaction_filter(
+        &self,
+        context: CompactionFilterContext,
+    ) -> Box<dyn CompactionFilter>;
+}
+
+/// A cloneable filter is its own factory: every compaction gets a copy of it.
+impl<F: CompactionFilter + Clone + Sync + 'static> CompactionFilterFactory for F {
+    fn create_compaction_filter(
+        &self,
+        _context: CompactionFilterContext,
+    ) -> Box<dyn CompactionFilter> {
+        Box::new(self.clone())
+    }
+}
+
+/// Removes every key starting with `prefix`.
+#[derive(Debug, Clone)]
+pub struct PrefixCompactionFilter {
+    prefix: Bytes,
+}
+
+impl PrefixCompactionFilter {
+    pub fn new(prefix: impl Into<Bytes>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+}
+
+impl CompactionFilter for PrefixCompactionFilter {
+    fn filter(&mut self, key: &[u8], _ts: u64, _value: &[u8]) -> CompactionFilterDecision {
+        if key.starts_with(&self.prefix) {
+            CompactionFilterDecision::Remove
+        } else {
+            CompactionFilterDecision::Keep
+        }
+    }
+}
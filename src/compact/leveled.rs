@@ -1,10 +1,13 @@
 #![allow(unused)]
 
-use std::{collections::HashSet, process::Output};
+use std::{collections::HashSet, ops::Bound, process::Output};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{compact::leveled, lsm_storage::LsmStorageState};
+use crate::{
+    compact::leveled,
+    lsm_storage::{range_overlap, LsmStorageState},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LeveledCompactionTask {
@@ -14,6 +17,10 @@ pub struct LeveledCompactionTask {
     pub lower_level: usize,
     pub lower_level_sst_ids: Vec<usize>,
     pub is_lower_level_bottom_level: bool,
+    // the upper SSTs overlap nothing in the lower level,
+    // so they are relinked into it as they are, without rewriting a byte.
+    #[serde(default)]
+    pub is_trivial_move: bool,
 }
 
 pub struct LeveledCompactionController {
@@ -68,6 +75,18 @@ impl LeveledCompactionController {
         overlap_ssts
     }
 
+    /// check that no two of the given SSTs overlap, only then can they be moved
+    /// into one level together (L0 SSTs usually do overlap each other).
+    fn is_disjoint(snapshot: &LsmStorageState, sst_ids: &[usize]) -> bool {
+        let mut ssts = sst_ids
+            .iter()
+            .map(|id| &snapshot.sstables[id])
+            .collect::<Vec<_>>();
+        ssts.sort_by(|x, y| x.first_key().cmp(y.first_key()));
+        ssts.windows(2)
+            .all(|pair| pair[0].last_key() < pair[1].first_key())
+    }
+
     pub fn generate_compaction_task(
         &self,
         snapshot: &LsmStorageState,
@@ -102,51 +121,166 @@ impl LeveledCompactionController {
                 base_level = level + 1;
             }
         }
+        // SSTs already taken by a running compaction cannot be picked again,
+        // a task is only generated when none of its inputs is busy.
+        let is_busy = |ids: &[usize]| {
+            ids.iter()
+                .any(|id| snapshot.compacting_sstables.contains(id))
+        };
         // generate compaction task for Both L0 and other levels.
         // In my implmentation, L0 always has the highest priority.
-        if snapshot.l0_sstables.len() >= self.options.level0_file_num_compaction_trigger {
-            return Some(LeveledCompactionTask {
-                upper_level: None,
-                upper_level_sst_ids: snapshot.l0_sstables.clone(),
-                lower_level: base_level,
-                lower_level_sst_ids: self.find_overlaping_ssts(
-                    snapshot,
-                    &snapshot.l0_sstables,
-                    base_level,
-                ),
-                is_lower_level_bottom_level: base_level == self.options.max_levels,
-            });
+        // L0 SSTs overlap each other, so only one L0 compaction may run at a time.
+        if snapshot.l0_sstables.len() >= self.options.level0_file_num_compaction_trigger
+            && !is_busy(&snapshot.l0_sstables)
+        {
+            let lower_level_sst_ids =
+                self.find_overlaping_ssts(snapshot, &snapshot.l0_sstables, base_level);
+            if !is_busy(&lower_level_sst_ids) {
+                let is_lower_level_bottom_level = base_level == self.options.max_levels;
+                return Some(LeveledCompactionTask {
+                    upper_level: None,
+                    upper_level_sst_ids: snapshot.l0_sstables.clone(),
+                    lower_level: base_level,
+                    // SSTs entering the bottom level are always rewritten, so that tombstones get purged.
+                    is_trivial_move: lower_level_sst_ids.is_empty()
+                        && !is_lower_level_bottom_level
+                        && Self::is_disjoint(snapshot, &snapshot.l0_sstables),
+                    lower_level_sst_ids,
+                    is_lower_level_bottom_level,
+                });
+            }
         }
         // calculate the priority among L1, L2 and other Levels.
+        // the bottom level has nowhere to go, and only levels over their target size need compaction.
         let mut priority = Vec::with_capacity(self.options.max_levels);
-        for i in (0..self.options.max_levels) {
+        for i in 0..self.options.max_levels - 1 {
             let prio = real_level_sizes[i] as f64 / target_level_sizes[i] as f64;
-            priority.push((prio, i + 1));
+            if prio > 1.0 {
+                priority.push((prio, i + 1));
+            }
         }
         // then sorting the prio score from large to small
         priority.sort_by(|a, b| a.partial_cmp(b).unwrap().reverse());
-        // get the first priority to compact
-        let priority = priority.first();
-        if let Some((_, level)) = priority {
-            let level = *level;
-            // get the old SSTable in this level (upper level)
-            let select_sst = snapshot.levels[level - 1].1.iter().min().copied().unwrap();
-            return Some(LeveledCompactionTask {
-                upper_level: Some(level),
-                upper_level_sst_ids: vec![select_sst],
-                lower_level: level + 1,
-                lower_level_sst_ids: self.find_overlaping_ssts(snapshot, &[select_sst], level + 1),
-                is_lower_level_bottom_level: level + 1 == self.options.max_levels,
-            });
+        for (_, level) in priority {
+            // pick the oldest SST of this level whose inputs are all free.
+            let mut candidates = snapshot.levels[level - 1].1.clone();
+            candidates.sort();
+            for select_sst in candidates {
+                if is_busy(&[select_sst]) {
+                    continue;
+                }
+                let lower_level_sst_ids =
+                    self.find_overlaping_ssts(snapshot, &[select_sst], level + 1);
+                if is_busy(&lower_level_sst_ids) {
+                    continue;
+                }
+                let is_lower_level_bottom_level = level + 1 == self.options.max_levels;
+                return Some(LeveledCompactionTask {
+                    upper_level: Some(level),
+                    upper_level_sst_ids: vec![select_sst],
+                    lower_level: level + 1,
+                    is_trivial_move: lower_level_sst_ids.is_empty() && !is_lower_level_bottom_level,
+                    lower_level_sst_ids,
+                    is_lower_level_bottom_level,
+                });
+            }
         }
         None
     }
 
+    /// generate a task moving every SST of `level` (0 for L0) that overlaps
+    /// the user key range `[lower, upper]` into the next level.
+    /// returns None if nothing in that level overlaps the range.
+    pub fn generate_range_compaction_task(
+        &self,
+        snapshot: &LsmStorageState,
+        level: usize,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+    ) -> Option<LeveledCompactionTask> {
+        let overlaps = |id: &usize| {
+            let sst = &snapshot.sstables[id];
+            range_overlap(
+                lower,
+                upper,
+                sst.first_key().as_key_slice(),
+                sst.last_key().as_key_slice(),
+            )
+        };
+        let upper_level_sst_ids = if level == 0 {
+            // L0 SSTs overlap each other: also take every L0 SST overlapping the chosen ones,
+            // otherwise an older version left behind in L0 could outlive a newer tombstone.
+            let mut selected = snapshot
+                .l0_sstables
+                .iter()
+                .copied()
+                .filter(overlaps)
+                .collect::<Vec<_>>();
+            while !selected.is_empty() {
+                let begin_key = selected
+                    .iter()
+                    .map(|id| snapshot.sstables[id].first_key())
+                    .min()
+                    .unwrap();
+                let end_key = selected
+                    .iter()
+                    .map(|id| snapshot.sstables[id].last_key())
+                    .max()
+                    .unwrap();
+                let expanded = snapshot
+                    .l0_sstables
+                    .iter()
+                    .copied()
+                    .filter(|id| {
+                        let sst = &snapshot.sstables[id];
+                        !(sst.last_key() < begin_key || sst.first_key() > end_key)
+                    })
+                    .collect::<Vec<_>>();
+                if expanded.len() == selected.len() {
+                    break;
+                }
+                selected = expanded;
+            }
+            selected
+        } else {
+            snapshot.levels[level - 1]
+                .1
+                .iter()
+                .copied()
+                .filter(overlaps)
+                .collect::<Vec<_>>()
+        };
+        if upper_level_sst_ids.is_empty() {
+            return None;
+        }
+        let lower_level_sst_ids =
+            self.find_overlaping_ssts(snapshot, &upper_level_sst_ids, level + 1);
+        let is_lower_level_bottom_level = level + 1 == self.options.max_levels;
+        Some(LeveledCompactionTask {
+            upper_level: if level == 0 { None } else { Some(level) },
+            // SSTs entering the bottom level are always rewritten, so that tombstones get purged.
+            is_trivial_move: lower_level_sst_ids.is_empty()
+                && !is_lower_level_bottom_level
+                && Self::is_disjoint(snapshot, &upper_level_sst_ids),
+            upper_level_sst_ids,
+            lower_level: level + 1,
+            lower_level_sst_ids,
+            is_lower_level_bottom_level,
+        })
+    }
+
+    pub fn max_levels(&self) -> usize {
+        self.options.max_levels
+    }
+
+    /// `in_recovery` is set while replaying the manifest: the SST objects are not
+    /// loaded yet, so the lower level is left unsorted and sorted by the caller later.
     pub fn apply_compaction_result(
         &self,
         snapshot: &LsmStorageState,
         task: &LeveledCompactionTask,
         output: &[usize],
+        in_recovery: bool,
     ) -> (LsmStorageState, Vec<usize>) {
         let mut snapshot = snapshot.clone();
         // Okay, Let's carry out the real execution, first get the objects ready.
@@ -174,6 +308,7 @@ impl LeveledCompactionController {
                     Some(*x)
                 })
                 .collect::<Vec<_>>();
+            snapshot.levels[upper_level - 1].1 = new_upper_level_ssts;
         } else {
             // this is L0-compaction
             let new_l0_ssts = snapshot
@@ -190,9 +325,12 @@ impl LeveledCompactionController {
         }
 
         // Aha, Let's move out these out-of-date and useless stuff.
+        // a trivial move keeps its files, they only change level.
         let mut files_to_remove = Vec::new();
-        files_to_remove.extend(&task.upper_level_sst_ids);
-        files_to_remove.extend(&task.lower_level_sst_ids);
+        if !task.is_trivial_move {
+            files_to_remove.extend(&task.upper_level_sst_ids);
+            files_to_remove.extend(&task.lower_level_sst_ids);
+        }
 
         // Okay, final hit: update the lower level SSTables.
         let mut new_lower_level_ssts = snapshot.levels[task.lower_level - 1]
@@ -206,14 +344,16 @@ impl LeveledCompactionController {
             })
             .collect::<Vec<_>>();
         new_lower_level_ssts.extend(output);
-        new_lower_level_ssts.sort_by(|x, y| {
-            snapshot
-                .sstables
-                .get(x)
-                .unwrap()
-                .first_key()
-                .cmp(snapshot.sstables.get(y).unwrap().first_key())
-        });
+        if !in_recovery {
+            new_lower_level_ssts.sort_by(|x, y| {
+                snapshot
+                    .sstables
+                    .get(x)
+                    .unwrap()
+                    .first_key()
+                    .cmp(snapshot.sstables.get(y).unwrap().first_key())
+            });
+        }
         snapshot.levels[task.lower_level - 1].1 = new_lower_level_ssts;
 
         // return the updated State and the files to move(the objects to be garbage-colletced).
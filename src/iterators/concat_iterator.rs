@@ -1,6 +1,7 @@
 use anyhow::{Ok, Result};
 
 use crate::key::KeySlice;
+use crate::lsm_storage::ReadOptions;
 use crate::table::SsTable;
 use crate::table::SsTableIterator;
 
@@ -17,12 +18,22 @@ pub struct SstConcatIterator {
     next_sst_id: usize,
     // the SSTables holding
     sstables: Vec<Arc<SsTable>>,
+    // how the blocks of every SST are read
+    options: ReadOptions,
 }
 
 impl SstConcatIterator {
     /// create a new ConcatIterator Instance,
     /// and position it at the begining of the concatenated sequence of SSTs.
     pub fn create_and_seek_to_first(sstables: Vec<Arc<SsTable>>) -> Result<Self> {
+        Self::create_and_seek_to_first_with_options(sstables, ReadOptions::default())
+    }
+
+    /// `create_and_seek_to_first` reading the blocks as `options` ask.
+    pub fn create_and_seek_to_first_with_options(
+        sstables: Vec<Arc<SsTable>>,
+        options: ReadOptions,
+    ) -> Result<Self> {
         // input validation to ensure proper ordering.
         Self::check_sst_valid(&sstables);
         // handling Empty SSTables
@@ -31,15 +42,18 @@ impl SstConcatIterator {
                 current: None,
                 next_sst_id: 0,
                 sstables,
+                options,
             });
         }
         // Init with first SST
         let mut iter = Self {
-            current: Some(SsTableIterator::create_and_seek_to_first(
+            current: Some(SsTableIterator::create_and_seek_to_first_with_options(
                 sstables[0].clone(),
+                options,
             )?),
             next_sst_id: 1,
             sstables,
+            options,
         };
         // move to the next valid iter
         iter.move_until_valid()?;
@@ -49,6 +63,15 @@ impl SstConcatIterator {
 
     /// create a new ConcatIterator Instance and move to the specified key-value pairs.
     pub fn create_and_seek_to_key(sstables: Vec<Arc<SsTable>>, key: KeySlice) -> Result<Self> {
+        Self::create_and_seek_to_key_with_options(sstables, key, ReadOptions::default())
+    }
+
+    /// `create_and_seek_to_key` reading the blocks as `options` ask.
+    pub fn create_and_seek_to_key_with_options(
+        sstables: Vec<Arc<SsTable>>,
+        key: KeySlice,
+        options: ReadOptions,
+    ) -> Result<Self> {
         // input validation
         Self::check_sst_valid(&sstables);
         // determine starting index.
@@ -61,16 +84,19 @@ impl SstConcatIterator {
                 current: None,
                 next_sst_id: sstables.len(),
                 sstables,
+                options,
             });
         }
         // Init with Specified key.
         let mut iter = Self {
-            current: Some(SsTableIterator::create_and_seek_to_key(
+            current: Some(SsTableIterator::create_and_seek_to_key_with_options(
                 sstables[idx].clone(),
                 key,
+                options,
             )?),
             next_sst_id: idx + 1,
             sstables,
+            options,
         };
         // move to the next valid iterator.
         iter.move_until_valid()?;
@@ -102,8 +128,9 @@ impl SstConcatIterator {
             if self.next_sst_id >= self.sstables.len() {
                 self.current = None;
             } else {
-                self.current = Some(SsTableIterator::create_and_seek_to_first(
+                self.current = Some(SsTableIterator::create_and_seek_to_first_with_options(
                     self.sstables[self.next_sst_id].clone(),
+                    self.options,
                 )?);
                 self.next_sst_id += 1;
             }
@@ -134,6 +161,7 @@ impl StorageIterator for SstConcatIterator {
 
     fn next(&mut self) -> anyhow::Result<()> {
         self.current.as_mut().unwrap().next()?;
+        self.move_until_valid()?;
         Ok(())
     }
 
@@ -18,8 +18,8 @@ use std::{
 struct HeapWrapper<I: StorageIterator>(pub usize, pub Box<I>);
 
 /// PartialOrd: allows comparing Instances of `HeapWrapper` for partial ordering.
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl<I: StorageIterator> PartialOrd for HeapWrapper<I> {
-    #[allow(clippy::non_canonical_partial_ord_impl)]
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
         match self.1.key().cmp(&other.1.key()) {
             // smaller keys are of higher priority (min-heap).
@@ -112,7 +112,7 @@ impl<I: 'static + for<'a> StorageIterator<KeyType<'a> = KeySlice<'a>>> StorageIt
     // appoint the keyType.
     type KeyType<'a> = KeySlice<'a>;
 
-    fn key(&self) -> KeySlice {
+    fn key(&self) -> KeySlice<'_> {
         self.current.as_ref().unwrap().1.key()
     }
 
@@ -34,7 +34,7 @@ impl<
             return false;
         }
         if !b.is_valid() {
-            return false;
+            return true;
         }
         // If a.key() is smaller then return true
         // otherwise return false, means that a.key() is the larger one.
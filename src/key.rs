@@ -6,10 +6,10 @@ pub const TS_ENABLED: bool = true;
 pub const TS_DEFAULT: u64 = 0;
 
 // Some Constants used for TimeStamp Management.
-pub const TS_MAX: u64 = std::u64::MAX;
-pub const TS_MIN: u64 = std::u64::MIN;
-pub const TS_RANGE_BEGIN: u64 = std::u64::MAX;
-pub const TS_RANGE_END: u64 = std::u64::MIN;
+pub const TS_MAX: u64 = u64::MAX;
+pub const TS_MIN: u64 = u64::MIN;
+pub const TS_RANGE_BEGIN: u64 = u64::MAX;
+pub const TS_RANGE_END: u64 = u64::MIN;
 
 // define the Key.
 pub struct Key<T: AsRef<[u8]>>(T, u64);
@@ -62,7 +62,7 @@ impl<T: AsRef<[u8]> + Default> Default for Key<T> {
 
 impl<T: AsRef<[u8]> + PartialEq> PartialEq for Key<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.0.eq(&other.0)
+        (self.0.as_ref(), self.1).eq(&(other.0.as_ref(), other.1))
     }
 }
 
@@ -70,13 +70,17 @@ impl<T: AsRef<[u8]> + Eq> Eq for Key<T> {}
 
 impl<T: AsRef<[u8]> + PartialOrd> PartialOrd for Key<T> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.0.partial_cmp(&other.0)
+        (self.0.as_ref(), std::cmp::Reverse(self.1))
+            .partial_cmp(&(other.0.as_ref(), std::cmp::Reverse(other.1)))
     }
 }
 
+/// keys are ordered by user key ascending, then by timestamp descending,
+/// so the newest version of a key is always visited first.
 impl<T: AsRef<[u8]> + Ord> Ord for Key<T> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.cmp(&other.0)
+        (self.0.as_ref(), std::cmp::Reverse(self.1))
+            .cmp(&(other.0.as_ref(), std::cmp::Reverse(other.1)))
     }
 }
 
@@ -146,7 +150,7 @@ impl Key<Vec<u8>> {
 
     // Converters
     /// converts from KeyVec to KeySlice
-    pub fn as_key_slice(&self) -> KeySlice {
+    pub fn as_key_slice(&self) -> KeySlice<'_> {
         Key(self.0.as_slice(), self.1)
     }
     /// converts from KeyVec to KeyBytes
@@ -163,9 +167,14 @@ impl Key<Vec<u8>> {
         self.0.clear()
     }
 
+    pub fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
     pub fn set_from_slice(&mut self, key_slice: KeySlice) {
         self.0.clear();
         self.0.extend(key_slice.0);
+        self.1 = key_slice.1;
     }
 
     pub fn set_ts(&mut self, ts: u64) {
@@ -197,7 +206,7 @@ impl Key<Bytes> {
     pub fn for_testing_key_ref(&self) -> &[u8] {
         self.0.as_ref()
     }
-    
+
     pub fn for_testing_from_bytes_no_ts(bytes: Bytes) -> KeyBytes {
         Key(bytes, TS_DEFAULT)
     }
@@ -207,7 +216,7 @@ impl Key<Bytes> {
     }
 
     // Convertor
-    pub fn as_key_slice(&self) -> KeySlice {
+    pub fn as_key_slice(&self) -> KeySlice<'_> {
         Key(&self.0, self.1)
     }
 }
@@ -1,4 +1,5 @@
 pub mod block;
+pub mod block_cache;
 pub mod compact;
 pub mod debug;
 pub mod iterators;
@@ -9,7 +10,9 @@ pub mod manifest;
 pub mod mem_table;
 pub mod mvcc;
 pub mod table;
+pub mod table_cache;
 pub mod wal;
+pub mod write_buffer_manager;
 
 #[cfg(test)]
 mod tests;
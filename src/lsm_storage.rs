@@ -1,38 +1,48 @@
 #![allow(unused)]
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use arc_swap::ArcSwap;
 use bytes::Bytes;
-use parking_lot::{Mutex, MutexGuard, RwLock};
+use parking_lot::{Condvar, Mutex, MutexGuard};
 use rustyline::validate;
 
+pub use crate::block_cache::{BlockCache, BlockCacheStats, CachedBlock};
 use crate::{
-    block::Block,
-    compact::{CompactionController, CompactionOptions, LeveledCompactionController},
+    block::{iterator::BlockIterator, Block},
+    compact::{
+        CompactionController, CompactionFilterFactory, CompactionOptions,
+        LeveledCompactionController, LeveledCompactionOptions,
+    },
     iterators::{
         concat_iterator::SstConcatIterator, merge_iterator::MergeIterator,
         two_merge_iterator::TwoMergeIterator, StorageIterator,
     },
     key::{self, KeyBytes, KeySlice},
     lsm_iterator::{FusedIterator, LsmIterator},
-    manifest::{Manifest, ManifestRecord},
-    mem_table::{map_bound, map_key_bound_plus_ts, MemTable},
+    manifest::{Manifest, ManifestRecord, SstMeta},
+    mem_table::{map_bound, map_key_bound_plus_ts, MemTable, MemTableRepKind},
     mvcc::{
         txn::{Transaction, TxnIterator},
         LsmMvccInner,
     },
-    table::{FileObject, SsTable, SsTableBuilder, SsTableIterator},
+    table::{
+        BlockMeta, FileObject, FilterPolicy, PrefixExtractor, SsTable, SsTableBuilder,
+        SsTableIterator, TableReader,
+    },
+    table_cache::TableCache,
+    write_buffer_manager::WriteBufferManager,
 };
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap, HashSet},
     fs::File,
-    ops::Bound,
+    ops::{Bound, Deref, DerefMut},
     path::{Path, PathBuf},
-    sync::{atomic::AtomicUsize, Arc},
-    thread, usize,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
 };
 
-/// BlockCache for `read block from disk`, this is used when SSTable is built.
-pub type BlockCache = moka::sync::Cache<(usize, usize), Arc<Block>>;
-
 /// stores the state of the storage Engine.
 /// This is the core structure for Concurrenty Control and MetaData Manangement.
 #[derive(Clone)]
@@ -48,19 +58,88 @@ pub struct LsmStorageState {
     // SST objects : map index(usize) to SST Object(Arc<SsTable>)
     // I made every SSTable a ID, then use a vector of IDs to represents SSTables in one Level.
     // The smaller ID it is, then earlier it creates.
-    pub sstables: HashMap<usize, Arc<SsTable>>,
+    // A persistent map: cloning the state to install a new version shares its handles.
+    pub sstables: im::HashMap<usize, Arc<SsTable>>,
+    // SSTs taken by a running compaction, the compaction picker must skip them.
+    pub compacting_sstables: HashSet<usize>,
 }
 
 impl LsmStorageState {
     fn create(options: &LsmStorageOptions) -> Self {
+        let levels = match &options.compaction_options {
+            CompactionOptions::Leveled(LeveledCompactionOptions { max_levels, .. }) => (1
+                ..=*max_levels)
+                .map(|level| (level, Vec::new()))
+                .collect::<Vec<_>>(),
+            CompactionOptions::NoCompaction => vec![(1, Vec::new())],
+        };
         Self {
-            memtable: Arc::new(MemTable::create(0)),
+            memtable: Arc::new(MemTable::create_with_rep(0, options.memtable_rep)),
             imm_memtables: Vec::new(),
             l0_sstables: Vec::new(),
-            levels: Vec::new(),
-            sstables: HashMap::new(),
+            levels,
+            sstables: im::HashMap::new(),
+            compacting_sstables: HashSet::new(),
+        }
+    }
+}
+
+/// StateCell holds the current version of the state. Readers load it without locking,
+/// even while a writer prepares the next version. Writers are serialized among
+/// themselves, and install the version they left in their guard when it drops.
+pub(crate) struct StateCell {
+    current: ArcSwap<LsmStorageState>,
+    writer: Mutex<()>,
+}
+
+impl StateCell {
+    fn new(state: LsmStorageState) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(state),
+            writer: Mutex::new(()),
         }
     }
+
+    /// the current version of the state.
+    pub(crate) fn read(&self) -> Arc<LsmStorageState> {
+        self.current.load_full()
+    }
+
+    /// start a new version of the state from the current one.
+    pub(crate) fn write(&self) -> StateWriteGuard<'_> {
+        let writer = self.writer.lock();
+        StateWriteGuard {
+            next: self.current.load_full(),
+            cell: self,
+            _writer: writer,
+        }
+    }
+}
+
+pub(crate) struct StateWriteGuard<'a> {
+    next: Arc<LsmStorageState>,
+    cell: &'a StateCell,
+    _writer: MutexGuard<'a, ()>,
+}
+
+impl Deref for StateWriteGuard<'_> {
+    type Target = Arc<LsmStorageState>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.next
+    }
+}
+
+impl DerefMut for StateWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.next
+    }
+}
+
+impl Drop for StateWriteGuard<'_> {
+    fn drop(&mut self) {
+        self.cell.current.store(self.next.clone());
+    }
 }
 
 /// Provide Configurable options when Initializing the StorageState.
@@ -74,6 +153,44 @@ pub struct LsmStorageOptions {
     pub num_memtable_limit: usize,
     // Compaction option
     pub compaction_options: CompactionOptions,
+    // split one compaction into at most this many key ranges, merged in parallel.
+    pub max_subcompactions: usize,
+    // number of compactions on disjoint SSTs allowed to run at the same time.
+    pub max_background_compactions: usize,
+    // hash key prefixes into the SST bloom filters to let `prefix_scan` skip SSTs.
+    pub prefix_extractor: Option<PrefixExtractor>,
+    // append a hash index to every data block to speed up point lookups.
+    pub block_hash_index: bool,
+    // how the SST filters are built, unless overridden for a level below.
+    pub filter_policy: FilterPolicy,
+    // per-level filter policy, level 0 is the flushed SSTs.
+    pub level_filter_policies: HashMap<usize, FilterPolicy>,
+    // build filters for the bottom level; most lookups that reach it find the key anyway.
+    pub filter_bottommost_level: bool,
+    // capacity of the block cache in bytes.
+    pub block_cache_capacity: u64,
+    // use this block cache instead of creating one, to share it between instances.
+    pub block_cache: Option<Arc<BlockCache>>,
+    // put index and filter partitions into the block cache (high priority pool),
+    // otherwise every SST holds its partitions once loaded.
+    pub cache_index_and_filter_blocks: bool,
+    // L0 SSTs hold their partitions once loaded, even when they are cached.
+    pub pin_l0_index_and_filter_blocks: bool,
+    // read SSTs through memory maps, data blocks then borrow the mapped file.
+    pub mmap_reads: bool,
+    // compaction reads its inputs and writes its outputs with O_DIRECT,
+    // keeping them out of the page cache, and does not fill the block cache.
+    pub use_direct_io_for_compaction: bool,
+    // keep at most this many SST files open, opening them lazily through an LRU table cache.
+    // Without a limit, every SST is opened on startup and stays open.
+    pub max_open_files: Option<usize>,
+    // writers only serialize to append to the WAL, then insert into the memtable
+    // concurrently, their commits still becoming visible in order.
+    pub enable_pipelined_write: bool,
+    // share a memtable memory budget with the other instances using this manager.
+    pub write_buffer_manager: Option<Arc<WriteBufferManager>>,
+    // the in-memory representation of the memtables.
+    pub memtable_rep: MemTableRepKind,
     // serilization or not
     // open WAL or not
     pub enable_wal: bool,
@@ -86,6 +203,23 @@ impl Default for LsmStorageOptions {
             block_size: 4 * 1024,
             target_sst_size: 1 << 20,
             compaction_options: CompactionOptions::NoCompaction,
+            max_subcompactions: 1,
+            max_background_compactions: 1,
+            prefix_extractor: None,
+            block_hash_index: false,
+            filter_policy: FilterPolicy::default(),
+            level_filter_policies: HashMap::new(),
+            filter_bottommost_level: true,
+            block_cache_capacity: 64 << 20,
+            block_cache: None,
+            cache_index_and_filter_blocks: true,
+            pin_l0_index_and_filter_blocks: false,
+            mmap_reads: false,
+            use_direct_io_for_compaction: false,
+            max_open_files: None,
+            enable_pipelined_write: false,
+            write_buffer_manager: None,
+            memtable_rep: MemTableRepKind::default(),
             enable_wal: false,
             num_memtable_limit: 3,
             serializable: false,
@@ -93,12 +227,85 @@ impl Default for LsmStorageOptions {
     }
 }
 
+/// Per-read options of `get` and `scan`.
+#[derive(Clone, Copy, Debug)]
+pub struct ReadOptions {
+    // insert the data blocks read into the block cache. Turn it off for bulk scans,
+    // so they do not evict the working set.
+    pub fill_cache: bool,
+    // read this many bytes of the following blocks in one I/O when a block is not cached.
+    pub readahead_bytes: usize,
+    // verify the checksum of every data block read from disk. Blocks read without it
+    // are not inserted into the block cache.
+    pub verify_checksums: bool,
+    // read data blocks with O_DIRECT, bypassing the page cache, where the file system allows it.
+    pub direct_io: bool,
+    // read blocks missing from the cache in batches through io_uring, where the kernel supports it.
+    // Iterators then prefetch the following blocks concurrently.
+    pub async_io: bool,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            fill_cache: true,
+            readahead_bytes: 0,
+            verify_checksums: true,
+            direct_io: false,
+            async_io: false,
+        }
+    }
+}
+
 impl LsmStorageOptions {
+    /// apply the read settings to an SST written to or opened at `level`:
+    /// how its file is read and where it keeps its index and filter partitions.
+    pub(crate) fn configure_sst(&self, mut sst: SsTable, level: usize) -> Result<SsTable> {
+        if self.mmap_reads {
+            sst = sst.into_mmap()?;
+        }
+        Ok(if !self.cache_index_and_filter_blocks {
+            sst.pin_meta_blocks(false)
+        } else if level == 0 && self.pin_l0_index_and_filter_blocks {
+            sst.pin_meta_blocks(true)
+        } else {
+            sst
+        })
+    }
+
+    /// the filter policy of SSTs written to `level`.
+    pub fn filter_policy_for(&self, level: usize, bottommost: bool) -> FilterPolicy {
+        if bottommost && !self.filter_bottommost_level {
+            return FilterPolicy::Disabled;
+        }
+        self.level_filter_policies
+            .get(&level)
+            .unwrap_or(&self.filter_policy)
+            .clone()
+    }
+
     pub fn default_for_week1_test() -> Self {
         Self {
             block_size: 4096,
             target_sst_size: 2 << 20,
             compaction_options: CompactionOptions::NoCompaction,
+            max_subcompactions: 1,
+            max_background_compactions: 1,
+            prefix_extractor: None,
+            block_hash_index: false,
+            filter_policy: FilterPolicy::default(),
+            level_filter_policies: HashMap::new(),
+            filter_bottommost_level: true,
+            block_cache_capacity: 64 << 20,
+            block_cache: None,
+            cache_index_and_filter_blocks: true,
+            pin_l0_index_and_filter_blocks: false,
+            mmap_reads: false,
+            use_direct_io_for_compaction: false,
+            max_open_files: None,
+            enable_pipelined_write: false,
+            write_buffer_manager: None,
+            memtable_rep: MemTableRepKind::default(),
             enable_wal: false,
             num_memtable_limit: 50,
             serializable: false,
@@ -110,6 +317,23 @@ impl LsmStorageOptions {
             block_size: 4096,
             target_sst_size: 2 << 20,
             compaction_options: CompactionOptions::NoCompaction,
+            max_subcompactions: 1,
+            max_background_compactions: 1,
+            prefix_extractor: None,
+            block_hash_index: false,
+            filter_policy: FilterPolicy::default(),
+            level_filter_policies: HashMap::new(),
+            filter_bottommost_level: true,
+            block_cache_capacity: 64 << 20,
+            block_cache: None,
+            cache_index_and_filter_blocks: true,
+            pin_l0_index_and_filter_blocks: false,
+            mmap_reads: false,
+            use_direct_io_for_compaction: false,
+            max_open_files: None,
+            enable_pipelined_write: false,
+            write_buffer_manager: None,
+            memtable_rep: MemTableRepKind::default(),
             enable_wal: false,
             num_memtable_limit: 2,
             serializable: false,
@@ -121,6 +345,23 @@ impl LsmStorageOptions {
             block_size: 4096,
             target_sst_size: 1 << 20, // 1MB
             compaction_options,
+            max_subcompactions: 1,
+            max_background_compactions: 1,
+            prefix_extractor: None,
+            block_hash_index: false,
+            filter_policy: FilterPolicy::default(),
+            level_filter_policies: HashMap::new(),
+            filter_bottommost_level: true,
+            block_cache_capacity: 64 << 20,
+            block_cache: None,
+            cache_index_and_filter_blocks: true,
+            pin_l0_index_and_filter_blocks: false,
+            mmap_reads: false,
+            use_direct_io_for_compaction: false,
+            max_open_files: None,
+            enable_pipelined_write: false,
+            write_buffer_manager: None,
+            memtable_rep: MemTableRepKind::default(),
             enable_wal: false,
             num_memtable_limit: 2,
             serializable: false,
@@ -128,18 +369,13 @@ impl LsmStorageOptions {
     }
 }
 
-#[derive(Clone, Debug)]
-pub enum CompactionFilter {
-    Prefix(Bytes),
-}
-
 fn key_within(user_key: &[u8], table_begin: KeySlice, table_end: KeySlice) -> bool {
     table_begin.key_ref() <= user_key && user_key <= table_end.key_ref()
 }
 
 /// this function is used to efficiently determine if there is any overlap
 /// between two ranges defined by the user and a table, based on their respective bounds.
-fn range_overlap(
+pub(crate) fn range_overlap(
     user_begin: Bound<&[u8]>,
     user_end: Bound<&[u8]>,
     table_begin: KeySlice,
@@ -169,31 +405,61 @@ fn range_overlap(
 /// the core data-structure of LsmStorage Engine.
 /// only visible inside the crate.
 pub(crate) struct LsmStorageInner {
-    // lock the state for concurrent R/w.
-    pub(crate) state: Arc<RwLock<Arc<LsmStorageState>>>,
+    // the current version of the state, read without locking.
+    pub(crate) state: StateCell,
     // lock for sync.
     pub(crate) state_lock: Mutex<()>,
     // the path to the storage location on the file system.
     path: PathBuf,
     // cache data blocks read from the storage(disk)
     pub(crate) block_cache: Arc<BlockCache>,
+    // open SST files, when their number is limited.
+    pub(crate) table_cache: Option<Arc<TableCache>>,
     // generate unique ids for SSTables.
     next_sst_id: AtomicUsize,
     // configuration settings control the behavior of LSM Tree
     pub(crate) options: Arc<LsmStorageOptions>,
     pub(crate) compaction_controller: CompactionController,
+    // number of compaction tasks currently running in the background.
+    pub(crate) running_compactions: AtomicUsize,
+    // the threads running them, joined on shutdown.
+    pub(crate) compaction_workers: Mutex<Vec<thread::JoinHandle<()>>>,
+    // signalled under `state_lock` whenever SSTs leave `compacting_sstables`.
+    pub(crate) compaction_inputs_released: Condvar,
     pub(crate) manifest: Option<Manifest>,
     pub(crate) mvcc: Option<LsmMvccInner>,
-    pub(crate) compaction_filters: Arc<Mutex<Vec<CompactionFilter>>>,
+    pub(crate) compaction_filters: Arc<Mutex<Vec<Arc<dyn CompactionFilterFactory>>>>,
+    // bytes written to the memtables and charged to the write buffer manager.
+    write_buffer_usage: AtomicUsize,
+    // the write buffer manager asked the flush thread to flush the memtables.
+    flush_requested: AtomicBool,
+}
+
+impl Drop for LsmStorageInner {
+    fn drop(&mut self) {
+        // the memtables left are no longer charged to the write buffer manager.
+        self.free_write_buffer(self.write_buffer_usage());
+    }
 }
 
 impl LsmStorageInner {
     /*---------------------------Boost and Init---------------------------------*/
     pub(crate) fn open(path: impl AsRef<Path>, options: LsmStorageOptions) -> Result<Self> {
+        if let CompactionOptions::Leveled(LeveledCompactionOptions { max_levels: 0, .. }) =
+            options.compaction_options
+        {
+            bail!("leveled compaction needs max_levels of at least 1");
+        }
         let mut state = LsmStorageState::create(&options);
         let path = path.as_ref();
         let mut next_sst_id = 1;
-        let block_cache = Arc::new(BlockCache::new(1 << 20)); // 4GB block cache,
+        let block_cache = options
+            .block_cache
+            .clone()
+            .unwrap_or_else(|| Arc::new(BlockCache::new(options.block_cache_capacity)));
+        let table_cache = options.max_open_files.map(|max_open_files| {
+            Arc::new(TableCache::new(path, max_open_files, block_cache.clone()))
+        });
         let manifest;
         let compaction_controller = match &options.compaction_options {
             CompactionOptions::Leveled(options) => {
@@ -210,6 +476,7 @@ impl LsmStorageInner {
             if options.enable_wal {
                 state.memtable = Arc::new(MemTable::create_with_wal(
                     state.memtable.id(),
+                    options.memtable_rep,
                     Self::path_of_wal_static(path, state.memtable.id()),
                 )?);
             }
@@ -218,6 +485,7 @@ impl LsmStorageInner {
         } else {
             let (m, records) = Manifest::recover(&manifest_path)?;
             let mut memtables = BTreeSet::new();
+            let mut sst_metas = HashMap::new();
             for record in records {
                 match record {
                     ManifestRecord::Flush(sst_id) => {
@@ -235,12 +503,29 @@ impl LsmStorageInner {
                         memtables.insert(x);
                     }
                     ManifestRecord::Compaction(task, output) => {
-                        let (new_state, _) =
-                            compaction_controller.apply_compaction_result(&state, &task, &output);
+                        let (new_state, _) = compaction_controller
+                            .apply_compaction_result(&state, &task, &output, true);
                         state = new_state;
                         next_sst_id =
                             next_sst_id.max(output.iter().max().copied().unwrap_or_default());
                     }
+                    ManifestRecord::NewSsts(metas) => {
+                        sst_metas.extend(metas.into_iter().map(|meta| (meta.id, meta)));
+                    }
+                    ManifestRecord::IngestExternalFiles(ingested) => {
+                        for (level, meta) in ingested {
+                            let sst_id = meta.id;
+                            if level > 0 {
+                                state.levels[level - 1].1.push(sst_id);
+                            } else if compaction_controller.flush_to_l0() {
+                                state.l0_sstables.insert(0, sst_id);
+                            } else {
+                                state.levels.insert(0, (sst_id, vec![sst_id]));
+                            }
+                            sst_metas.insert(sst_id, meta);
+                            next_sst_id = next_sst_id.max(sst_id);
+                        }
+                    }
                 }
             }
             let mut sst_cnt = 0;
@@ -251,32 +536,56 @@ impl LsmStorageInner {
                 .chain(state.levels.iter().flat_map(|(_, files)| files))
             {
                 let table_id = *table_id;
-                let sst = SsTable::open(
-                    table_id,
-                    Some(block_cache.clone()),
-                    FileObject::open(&Self::path_of_sst_static(path, table_id))
-                        .context("failed to open SST")?,
-                )?;
+                let level = if state.l0_sstables.contains(&table_id) {
+                    0
+                } else {
+                    1
+                };
+                // with a table cache, SSTs the manifest knows the key range of stay closed.
+                let sst = match (&table_cache, sst_metas.get(&table_id)) {
+                    (Some(table_cache), Some(meta)) => {
+                        options.configure_sst(meta.open_lazy(table_cache.clone()), level)?
+                    }
+                    _ => {
+                        let sst = SsTable::open(
+                            table_id,
+                            Some(block_cache.clone()),
+                            FileObject::open(&Self::path_of_sst_static(path, table_id))
+                                .context("failed to open SST")?,
+                        )?;
+                        let sst = options.configure_sst(sst, level)?;
+                        match &table_cache {
+                            Some(table_cache) => sst.into_table_cache(table_cache.clone()),
+                            None => sst,
+                        }
+                    }
+                };
                 last_commit_ts = last_commit_ts.max(sst.max_ts());
                 state.sstables.insert(table_id, Arc::new(sst));
                 sst_cnt += 1;
             }
+            // levels were left unsorted while replaying compactions, sort them now.
+            for (_, ssts) in &mut state.levels {
+                ssts.sort_by(|x, y| {
+                    state.sstables[x]
+                        .first_key()
+                        .cmp(state.sstables[y].first_key())
+                });
+            }
             println!("{} SSTs opened", sst_cnt);
             next_sst_id += 1;
             // recover memtables
             if options.enable_wal {
                 let mut wal_cnt = 0;
                 for id in memtables.iter() {
-                    let memtable =
-                        MemTable::recover_from_wal(*id, Self::path_of_wal_static(path, *id))?;
-                    let max_ts = memtable
-                        .map
-                        .iter()
-                        .map(|x| x.key().ts())
-                        .max()
-                        .unwrap_or_default();
-                    last_commit_ts = last_commit_ts.max(max_ts);
+                    let memtable = MemTable::recover_from_wal(
+                        *id,
+                        options.memtable_rep,
+                        Self::path_of_wal_static(path, *id),
+                    )?;
+                    last_commit_ts = last_commit_ts.max(memtable.max_ts());
                     if !memtable.is_empty() {
+                        memtable.mark_immutable();
                         state.imm_memtables.insert(0, Arc::new(memtable));
                         wal_cnt += 1;
                     }
@@ -284,27 +593,39 @@ impl LsmStorageInner {
                 println!("{} WALs recovered", wal_cnt);
                 state.memtable = Arc::new(MemTable::create_with_wal(
                     next_sst_id,
+                    options.memtable_rep,
                     Self::path_of_wal_static(path, next_sst_id),
                 )?);
             } else {
-                state.memtable = Arc::new(MemTable::create(next_sst_id));
+                state.memtable =
+                    Arc::new(MemTable::create_with_rep(next_sst_id, options.memtable_rep));
             }
             m.add_record_when_init(ManifestRecord::NewMemTable(state.memtable.id()))?;
             next_sst_id += 1;
             manifest = m;
         };
         let storage = Self {
-            state: Arc::new(RwLock::new(Arc::new(state))),
+            state: StateCell::new(state),
             state_lock: Mutex::new(()),
             path: path.to_path_buf(),
             block_cache,
+            table_cache,
             next_sst_id: AtomicUsize::new(next_sst_id),
             compaction_controller,
+            running_compactions: AtomicUsize::new(0),
+            compaction_workers: Mutex::new(Vec::new()),
+            compaction_inputs_released: Condvar::new(),
             manifest: Some(manifest),
             options: options.into(),
             mvcc: Some(LsmMvccInner::new(last_commit_ts)),
             compaction_filters: Arc::new(Mutex::new(Vec::new())),
+            write_buffer_usage: AtomicUsize::new(0),
+            flush_requested: AtomicBool::new(false),
         };
+        // the recovered memtables count against the write buffer too.
+        for memtable in &storage.state.read().imm_memtables {
+            storage.charge_write_buffer(memtable.approximate_size());
+        }
         storage.sync_dir()?;
         Ok(storage)
     }
@@ -315,6 +636,34 @@ impl LsmStorageInner {
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
     }
 
+    /// the bytes this instance charged to the write buffer manager.
+    pub(crate) fn write_buffer_usage(&self) -> usize {
+        self.write_buffer_usage.load(Ordering::Relaxed)
+    }
+
+    /// have the flush thread flush every memtable.
+    pub(crate) fn request_flush(&self) {
+        self.flush_requested.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn take_flush_request(&self) -> bool {
+        self.flush_requested.swap(false, Ordering::Relaxed)
+    }
+
+    fn charge_write_buffer(&self, bytes: usize) {
+        if let Some(ref manager) = self.options.write_buffer_manager {
+            self.write_buffer_usage.fetch_add(bytes, Ordering::Relaxed);
+            manager.reserve(bytes);
+        }
+    }
+
+    fn free_write_buffer(&self, bytes: usize) {
+        if let Some(ref manager) = self.options.write_buffer_manager {
+            self.write_buffer_usage.fetch_sub(bytes, Ordering::Relaxed);
+            manager.free(bytes);
+        }
+    }
+
     pub(crate) fn mvcc(&self) -> &LsmMvccInner {
         self.mvcc.as_ref().unwrap()
     }
@@ -323,6 +672,21 @@ impl LsmStorageInner {
         self.manifest.as_ref().unwrap()
     }
 
+    /// apply the read settings to an SST written to `level`, handing its reader
+    /// over to the table cache when the number of open files is limited.
+    pub(crate) fn configure_sst(&self, sst: SsTable, level: usize) -> Result<SsTable> {
+        let sst = self.options.configure_sst(sst, level)?;
+        Ok(match &self.table_cache {
+            Some(table_cache) => sst.into_table_cache(table_cache.clone()),
+            None => sst,
+        })
+    }
+
+    /// delete the file of an SST no longer in the state, see `SsTable::delete_file`.
+    pub(crate) fn remove_sst_file(&self, sst: &SsTable) -> Result<()> {
+        sst.delete_file(&self.path_of_sst(sst.sst_id()))
+    }
+
     /*----------------------------Util functions---------------------------------*/
 
     /// 根据SST的id, 返回它的实际路径
@@ -334,13 +698,21 @@ impl LsmStorageInner {
         path.as_ref().join(format!("{:05}.sst", id))
     }
 
+    /// 按当前配置创建SST builder, 输出到`level`层
+    pub(crate) fn new_sst_builder(&self, level: usize, bottommost: bool) -> SsTableBuilder {
+        SsTableBuilder::new(self.options.block_size)
+            .with_prefix_extractor(self.options.prefix_extractor.clone())
+            .with_block_hash_index(self.options.block_hash_index)
+            .with_filter_policy(self.options.filter_policy_for(level, bottommost))
+    }
+
     /// 根据Wal的id, 返回它的实际路径
     pub(crate) fn path_of_wal(&self, id: usize) -> PathBuf {
         Self::path_of_wal_static(&self.path, id)
     }
 
     pub(crate) fn path_of_wal_static(path: impl AsRef<Path>, id: usize) -> PathBuf {
-        path.as_ref().join(format!("{:05}.sst", id))
+        path.as_ref().join(format!("{:05}.wal", id))
     }
 
     pub(super) fn sync_dir(&self) -> Result<()> {
@@ -354,93 +726,255 @@ impl LsmStorageInner {
     }
 
     pub fn get(self: &Arc<Self>, key: &[u8]) -> Result<Option<Bytes>> {
+        self.get_with_options(&ReadOptions::default(), key)
+    }
+
+    pub fn get_with_options(
+        self: &Arc<Self>,
+        options: &ReadOptions,
+        key: &[u8],
+    ) -> Result<Option<Bytes>> {
         let txn = self.mvcc().new_txn(self.clone(), self.options.serializable);
-        txn.get(key)
+        txn.get_with_options(options, key)
     }
 
     pub fn get_with_ts(&self, key: &[u8], ts: u64) -> Result<Option<Bytes>> {
-        // 1.snapshot generation
-        let snapshot = {
-            let guard = self.state.read();
-            Arc::clone(&guard)
-        };
-        // 2.Iterators creation
-        // MemTable iters
-        let mut memtable_iters = Vec::with_capacity(snapshot.imm_memtables.len() + 1);
-        memtable_iters.push(Box::new(snapshot.memtable.scan(
-            Bound::Included(KeySlice::from_slice(key, key::TS_RANGE_BEGIN)),
-            Bound::Included(KeySlice::from_slice(key, key::TS_RANGE_END)),
-        )));
-        for memtable in snapshot.imm_memtables.iter() {
-            memtable_iters.push(Box::new(memtable.scan(
-                Bound::Included(KeySlice::from_slice(key, key::TS_RANGE_BEGIN)),
-                Bound::Included(KeySlice::from_slice(key, key::TS_RANGE_END)),
-            )));
+        self.get_with_ts_and_options(key, ts, &ReadOptions::default())
+    }
+
+    /// `get_with_ts` reading the SST blocks as `options` ask.
+    pub(crate) fn get_with_ts_and_options(
+        &self,
+        key: &[u8],
+        ts: u64,
+        options: &ReadOptions,
+    ) -> Result<Option<Bytes>> {
+        let snapshot = self.state.read();
+        // every source holds only older versions than the one before it, so the first
+        // version found is the visible one. An empty value is a tombstone.
+        let found = |value: Bytes| Ok((!value.is_empty()).then_some(value));
+        for memtable in std::iter::once(&snapshot.memtable).chain(snapshot.imm_memtables.iter()) {
+            if let Some(value) = memtable.get_visible(key, ts) {
+                return found(value);
+            }
         }
-        let memtable_iter = MergeIterator::create(memtable_iters);
-        // L0 SSTable iters
-        let mut l0_iters = Vec::with_capacity(snapshot.l0_sstables.len());
-        let keep_table = |key: &[u8], table: &SsTable| {
-            if key_within(
-                key,
-                table.first_key().as_key_slice(),
-                table.last_key().as_key_slice(),
-            ) {
-                if let Some(bloom) = &table.bloom {
-                    if bloom.may_contain(farmhash::fingerprint32(key)) {
-                        return true;
-                    }
-                } else {
-                    return true;
-                }
+        for table_id in snapshot.l0_sstables.iter() {
+            let table = &snapshot.sstables[table_id];
+            if let Some(value) = Self::get_from_sst(table, key, ts, options)? {
+                return found(value);
             }
-            false
+        }
+        for (_, level_sst_ids) in &snapshot.levels {
+            // SSTs of a level are sorted and do not overlap.
+            let sst_idx = level_sst_ids
+                .partition_point(|id| snapshot.sstables[id].last_key().key_ref() < key);
+            let Some(table_id) = level_sst_ids.get(sst_idx) else {
+                continue;
+            };
+            if let Some(value) = Self::get_from_sst(&snapshot.sstables[table_id], key, ts, options)?
+            {
+                return found(value);
+            }
+        }
+        Ok(None)
+    }
+
+    /// the version of `key` visible at `read_ts` in one SST, if the table holds one.
+    fn get_from_sst(
+        table: &SsTable,
+        key: &[u8],
+        read_ts: u64,
+        options: &ReadOptions,
+    ) -> Result<Option<Bytes>> {
+        if !key_within(
+            key,
+            table.first_key().as_key_slice(),
+            table.last_key().as_key_slice(),
+        ) || !table.may_contain_key(key)?
+        {
+            return Ok(None);
+        }
+        let reader = table.reader()?;
+        let block_idx = reader.find_block_idx(KeySlice::from_slice(key, read_ts))?;
+        let block = reader.read_blocks(&[block_idx], options)?.remove(0);
+        Self::seek_visible(&reader, block_idx, block, key, read_ts, options)
+    }
+
+    /// seek the version of `key` visible at `read_ts` from block `block_idx`. When every
+    /// version of the key in the block is too new, the search goes on in the blocks after it.
+    fn seek_visible(
+        reader: &TableReader,
+        mut block_idx: usize,
+        mut block: Arc<Block>,
+        key: &[u8],
+        read_ts: u64,
+        options: &ReadOptions,
+    ) -> Result<Option<Bytes>> {
+        loop {
+            let iter =
+                BlockIterator::create_and_seek_to_key(block, KeySlice::from_slice(key, read_ts));
+            if iter.is_valid() {
+                return Ok(
+                    (iter.key().key_ref() == key).then(|| Bytes::copy_from_slice(iter.value()))
+                );
+            }
+            block_idx += 1;
+            if block_idx >= reader.num_of_blocks() {
+                return Ok(None);
+            }
+            block = reader.read_blocks(&[block_idx], options)?.remove(0);
+        }
+    }
+
+    /// get the values of `keys` in one pass, in the order of `keys`.
+    pub fn multi_get(self: &Arc<Self>, keys: &[&[u8]]) -> Result<Vec<Option<Bytes>>> {
+        self.multi_get_with_options(&ReadOptions::default(), keys)
+    }
+
+    pub fn multi_get_with_options(
+        self: &Arc<Self>,
+        options: &ReadOptions,
+        keys: &[&[u8]],
+    ) -> Result<Vec<Option<Bytes>>> {
+        let txn = self.mvcc().new_txn(self.clone(), self.options.serializable);
+        txn.multi_get_with_options(options, keys)
+    }
+
+    /// look all `keys` up at `read_ts` against one snapshot. The keys are sorted, then every
+    /// memtable and SST is probed once for the keys still missing within its range, and keys
+    /// living in the same block share its read. With `async_io` the blocks of one SST are
+    /// read concurrently.
+    pub(crate) fn multi_get_with_ts(
+        &self,
+        keys: &[&[u8]],
+        read_ts: u64,
+        options: &ReadOptions,
+    ) -> Result<Vec<Option<Bytes>>> {
+        let snapshot = self.state.read();
+        let mut sorted_keys = keys.to_vec();
+        sorted_keys.sort();
+        sorted_keys.dedup();
+        // the visible version of every sorted key, once found, an empty value for a tombstone.
+        let mut found: Vec<Option<Bytes>> = vec![None; sorted_keys.len()];
+        let missing = |found: &[Option<Bytes>]| {
+            (0..sorted_keys.len())
+                .filter(|&idx| found[idx].is_none())
+                .map(|idx| (idx, sorted_keys[idx]))
+                .collect::<Vec<_>>()
         };
-        for table in snapshot.l0_sstables.iter() {
-            let table = snapshot.sstables[table].clone();
-            if keep_table(key, &table) {
-                l0_iters.push(Box::new(SsTableIterator::create_and_seek_to_key(
-                    table,
-                    KeySlice::from_slice(key, key::TS_RANGE_BEGIN),
-                )?));
+
+        for memtable in std::iter::once(&snapshot.memtable).chain(snapshot.imm_memtables.iter()) {
+            for (idx, key) in missing(&found) {
+                found[idx] = memtable.get_visible(key, read_ts);
+            }
+        }
+        for table_id in snapshot.l0_sstables.iter() {
+            let table = &snapshot.sstables[table_id];
+            let keys = missing(&found)
+                .into_iter()
+                .filter(|(_, key)| {
+                    key_within(
+                        key,
+                        table.first_key().as_key_slice(),
+                        table.last_key().as_key_slice(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            for (idx, value) in Self::multi_get_from_sst(table, &keys, read_ts, options)? {
+                found[idx] = Some(value);
             }
         }
-        let l0_iter = MergeIterator::create(l0_iters);
-        // Level SSTable iters
-        let mut level_iters = Vec::with_capacity(snapshot.levels.len());
         for (_, level_sst_ids) in &snapshot.levels {
-            let mut level_ssts = Vec::with_capacity(snapshot.levels[0].1.len());
-            for table in level_sst_ids {
-                let table = snapshot.sstables[table].clone();
-                if keep_table(key, &table) {
-                    level_ssts.push(table);
+            let level_ssts = level_sst_ids
+                .iter()
+                .map(|id| &snapshot.sstables[id])
+                .collect::<Vec<_>>();
+            // the keys are sorted, so the keys of one SST follow each other.
+            let mut keys_by_sst = Vec::<(usize, Vec<_>)>::new();
+            for (idx, key) in missing(&found) {
+                let sst_idx = level_ssts.partition_point(|table| table.last_key().key_ref() < key);
+                if sst_idx == level_ssts.len() || level_ssts[sst_idx].first_key().key_ref() > key {
+                    continue;
+                }
+                match keys_by_sst.last_mut() {
+                    Some((last, keys)) if *last == sst_idx => keys.push((idx, key)),
+                    _ => keys_by_sst.push((sst_idx, vec![(idx, key)])),
+                }
+            }
+            for (sst_idx, keys) in keys_by_sst {
+                let table = level_ssts[sst_idx];
+                for (idx, value) in Self::multi_get_from_sst(table, &keys, read_ts, options)? {
+                    found[idx] = Some(value);
                 }
             }
-            let level_iter = SstConcatIterator::create_and_seek_to_key(
-                level_ssts,
-                KeySlice::from_slice(key, key::TS_RANGE_BEGIN),
-            )?;
-            level_iters.push(Box::new(level_iter));
         }
-        // 3. Merging Iterators (merge these 3 iters to A single Iterator).
-        let iter = LsmIterator::new(
-            TwoMergeIterator::create(
-                TwoMergeIterator::create(memtable_iter, l0_iter)?,
-                MergeIterator::create(level_iters),
-            )?,
-            Bound::Unbounded,
-            ts,
-        )?;
-        // 4. Key Filtering
-        if iter.is_valid() && iter.key() == key && !iter.value().is_empty() {
-            return Ok(Some(Bytes::copy_from_slice(iter.value())));
+
+        Ok(keys
+            .iter()
+            .map(|key| {
+                let idx = sorted_keys.binary_search(key).unwrap();
+                found[idx].clone().filter(|value| !value.is_empty())
+            })
+            .collect())
+    }
+
+    /// look the sorted `keys` up in one SST, returning the versions visible at `read_ts`
+    /// it holds. The blocks the keys fall in are read in one batch.
+    fn multi_get_from_sst(
+        table: &SsTable,
+        keys: &[(usize, &[u8])],
+        read_ts: u64,
+        options: &ReadOptions,
+    ) -> Result<Vec<(usize, Bytes)>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
         }
-        Ok(None)
+        let reader = table.reader()?;
+        let mut candidates = Vec::with_capacity(keys.len());
+        for &(idx, key) in keys {
+            if reader.may_contain_key(key)? {
+                let block_idx = reader.find_block_idx(KeySlice::from_slice(key, read_ts))?;
+                candidates.push((idx, key, block_idx));
+            }
+        }
+        // sorted keys fall in ascending blocks.
+        let mut block_idxs = candidates
+            .iter()
+            .map(|(_, _, block_idx)| *block_idx)
+            .collect::<Vec<_>>();
+        block_idxs.dedup();
+        let blocks = reader.read_blocks(&block_idxs, options)?;
+        let mut found = Vec::new();
+        for (idx, key, block_idx) in candidates {
+            let block = blocks[block_idxs.binary_search(&block_idx).unwrap()].clone();
+            if let Some(value) =
+                Self::seek_visible(&reader, block_idx, block, key, read_ts, options)?
+            {
+                found.push((idx, value));
+            }
+        }
+        Ok(found)
     }
 
     pub fn scan(self: &Arc<Self>, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> Result<TxnIterator> {
+        self.scan_with_options(&ReadOptions::default(), lower, upper)
+    }
+
+    pub fn scan_with_options(
+        self: &Arc<Self>,
+        options: &ReadOptions,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+    ) -> Result<TxnIterator> {
         let txn = self.mvcc().new_txn(self.clone(), self.options.serializable);
-        txn.scan(lower, upper)
+        txn.scan_with_options(options, lower, upper)
+    }
+
+    /// scan every key starting with `prefix`. With a prefix extractor configured,
+    /// SSTs whose bloom filter rules the prefix out are skipped.
+    pub fn prefix_scan(self: &Arc<Self>, prefix: &[u8]) -> Result<TxnIterator> {
+        let txn = self.mvcc().new_txn(self.clone(), self.options.serializable);
+        txn.prefix_scan(prefix)
     }
 
     pub fn scan_with_ts(
@@ -449,11 +983,43 @@ impl LsmStorageInner {
         upper: Bound<&[u8]>,
         read_ts: u64,
     ) -> Result<FusedIterator<LsmIterator>> {
-        // 1. snapshot generation
-        let snapshot = {
-            let guard = self.state.read();
-            Arc::clone(&guard)
+        self.scan_with_ts_and_prefix(lower, upper, read_ts, None, &ReadOptions::default())
+    }
+
+    /// get the bloom hash that every SST holding a key starting with `prefix` contains,
+    /// `None` when the prefix is shorter than what the extractor produces.
+    pub(crate) fn prefix_bloom_hash(&self, prefix: &[u8]) -> Option<u32> {
+        self.options
+            .prefix_extractor
+            .as_ref()
+            .and_then(|extractor| extractor.extract(prefix))
+            .map(farmhash::fingerprint32)
+    }
+
+    /// `scan_with_ts` over keys starting with `prefix`, skipping the SSTs whose
+    /// filter partitions rule the prefix out, and reading the SST blocks as `options` ask.
+    pub(crate) fn scan_with_ts_and_prefix(
+        &self,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        read_ts: u64,
+        prefix: Option<&[u8]>,
+        options: &ReadOptions,
+    ) -> Result<FusedIterator<LsmIterator>> {
+        let prefix_hash = prefix.and_then(|prefix| self.prefix_bloom_hash(prefix));
+        // only SSTs built with the current extractor have the prefix hashed in.
+        let may_contain_prefix = |table: &SsTable| match (prefix, prefix_hash) {
+            (Some(prefix), Some(hash)) => {
+                let reader = table.reader()?;
+                if reader.properties().prefix_extractor != self.options.prefix_extractor {
+                    return Ok(true);
+                }
+                reader.may_contain_prefix(prefix, hash)
+            }
+            _ => Ok(true),
         };
+        // 1. snapshot generation
+        let snapshot = self.state.read();
 
         // 2. Iterators generation
         // MemTable iter
@@ -478,23 +1044,28 @@ impl LsmStorageInner {
                 upper,
                 table.first_key().as_key_slice(),
                 table.last_key().as_key_slice(),
-            ) {
+            ) && may_contain_prefix(&table)?
+            {
                 let iter = match lower {
-                    Bound::Included(key) => SsTableIterator::create_and_seek_to_key(
+                    Bound::Included(key) => SsTableIterator::create_and_seek_to_key_with_options(
                         table,
                         KeySlice::from_slice(key, key::TS_RANGE_BEGIN),
+                        *options,
                     )?,
                     Bound::Excluded(key) => {
-                        let mut iter = SsTableIterator::create_and_seek_to_key(
+                        let mut iter = SsTableIterator::create_and_seek_to_key_with_options(
                             table,
                             KeySlice::from_slice(key, key::TS_RANGE_BEGIN),
+                            *options,
                         )?;
                         while iter.is_valid() && iter.key().key_ref() == key {
                             iter.next()?;
                         }
                         iter
                     }
-                    Bound::Unbounded => SsTableIterator::create_and_seek_to_first(table)?,
+                    Bound::Unbounded => {
+                        SsTableIterator::create_and_seek_to_first_with_options(table, *options)?
+                    }
                 };
 
                 table_iters.push(Box::new(iter));
@@ -512,27 +1083,32 @@ impl LsmStorageInner {
                     upper,
                     table.first_key().as_key_slice(),
                     table.last_key().as_key_slice(),
-                ) {
+                ) && may_contain_prefix(&table)?
+                {
                     level_ssts.push(table);
                 }
             }
 
             let level_iter = match lower {
-                Bound::Included(key) => SstConcatIterator::create_and_seek_to_key(
+                Bound::Included(key) => SstConcatIterator::create_and_seek_to_key_with_options(
                     level_ssts,
                     KeySlice::from_slice(key, key::TS_RANGE_BEGIN),
+                    *options,
                 )?,
                 Bound::Excluded(key) => {
-                    let mut iter = SstConcatIterator::create_and_seek_to_key(
+                    let mut iter = SstConcatIterator::create_and_seek_to_key_with_options(
                         level_ssts,
                         KeySlice::from_slice(key, key::TS_RANGE_BEGIN),
+                        *options,
                     )?;
                     while iter.is_valid() && iter.key().key_ref() == key {
                         iter.next()?;
                     }
                     iter
                 }
-                Bound::Unbounded => SstConcatIterator::create_and_seek_to_first(level_ssts)?,
+                Bound::Unbounded => {
+                    SstConcatIterator::create_and_seek_to_first_with_options(level_ssts, *options)?
+                }
             };
             level_iters.push(Box::new(level_iter));
         }
@@ -594,6 +1170,9 @@ impl LsmStorageInner {
     /// return a u64 commit timestamp so that Transaction::Commit can correctly
     /// store the committed transaction data into the MVCC structure.
     pub fn write_batch_inner<T: AsRef<[u8]>>(&self, batch: &[WriteBatchRecord<T>]) -> Result<u64> {
+        if self.options.enable_pipelined_write {
+            return self.write_batch_pipelined(batch);
+        }
         let _lck = self.mvcc().write_lock.lock();
         let commit_ts = self.mvcc().latest_commit_ts() + 1;
         for record in batch {
@@ -606,10 +1185,10 @@ impl LsmStorageInner {
                     let size;
                     {
                         let guard = self.state.read();
-                        guard
-                            .memtable
-                            .put(KeySlice::from_slice(key, commit_ts), value)?;
+                        let key = KeySlice::from_slice(key, commit_ts);
+                        let charged = guard.memtable.put(key, value)?;
                         size = guard.memtable.approximate_size();
+                        self.charge_write_buffer(charged);
                     }
                     self.try_freeze(size)?;
                 }
@@ -619,16 +1198,70 @@ impl LsmStorageInner {
                     let size;
                     {
                         let guard = self.state.read();
-                        guard
-                            .memtable
-                            .put(KeySlice::from_slice(key, commit_ts), b"")?;
+                        let key = KeySlice::from_slice(key, commit_ts);
+                        let charged = guard.memtable.put(key, b"")?;
                         size = guard.memtable.approximate_size();
+                        self.charge_write_buffer(charged);
                     }
                     self.try_freeze(size)?;
                 }
             }
         }
         self.mvcc().update_commit_ts(commit_ts);
+        if let Some(ref manager) = self.options.write_buffer_manager {
+            manager.maybe_flush();
+        }
+        Ok(commit_ts)
+    }
+
+    /// the pipelined write path. The write lock is only held to take a commit timestamp
+    /// and append the batch to the WAL, so the next batch appends while this one inserts
+    /// into the memtable, concurrently with the other batches in flight. The commit
+    /// timestamp is published once every older one is.
+    fn write_batch_pipelined<T: AsRef<[u8]>>(&self, batch: &[WriteBatchRecord<T>]) -> Result<u64> {
+        let data = batch
+            .iter()
+            .map(|record| match record {
+                WriteBatchRecord::Put(key, value) => {
+                    let (key, value) = (key.as_ref(), value.as_ref());
+                    assert!(!key.is_empty(), "key cannot be empty!");
+                    assert!(!value.is_empty(), "value cannot be empty!");
+                    (key, value)
+                }
+                WriteBatchRecord::Del(key) => {
+                    let key = key.as_ref();
+                    assert!(!key.is_empty(), "key cannot be empty!");
+                    (key, &b""[..])
+                }
+            })
+            .collect::<Vec<_>>();
+        let (memtable, commit_ts, data) = {
+            let _lck = self.mvcc().write_lock.lock();
+            let size = self.state.read().memtable.approximate_size();
+            if size > self.options.target_sst_size {
+                // the batches in flight insert into the memtable about to be frozen.
+                self.mvcc().pipeline.wait_idle();
+                self.try_freeze(size)?;
+            }
+            let memtable = self.state.read().memtable.clone();
+            let commit_ts = self.mvcc().pipeline.allocate();
+            let data = data
+                .into_iter()
+                .map(|(key, value)| (KeySlice::from_slice(key, commit_ts), value))
+                .collect::<Vec<_>>();
+            if let Err(e) = memtable.append_wal(&data) {
+                // an empty commit, the commits after it must not wait for it.
+                self.mvcc().finish_commit(commit_ts);
+                return Err(e);
+            }
+            (memtable, commit_ts, data)
+        };
+        let charged = memtable.insert_batch(&data);
+        self.charge_write_buffer(charged);
+        self.mvcc().finish_commit(commit_ts);
+        if let Some(ref manager) = self.options.write_buffer_manager {
+            manager.maybe_flush();
+        }
         Ok(commit_ts)
     }
 
@@ -640,9 +1273,7 @@ impl LsmStorageInner {
     fn try_freeze(&self, estimated_size: usize) -> Result<()> {
         if estimated_size > self.options.target_sst_size {
             let lock = self.state_lock.lock();
-            let guard = self.state.read();
             if estimated_size > self.options.target_sst_size {
-                drop(guard);
                 self.force_freeze_memtable(&lock)?;
             }
         }
@@ -655,10 +1286,14 @@ impl LsmStorageInner {
         let memtable = if self.options.enable_wal {
             Arc::new(MemTable::create_with_wal(
                 memtable_id,
+                self.options.memtable_rep,
                 self.path_of_wal(memtable_id),
             )?)
         } else {
-            Arc::new(MemTable::create(memtable_id))
+            Arc::new(MemTable::create_with_rep(
+                memtable_id,
+                self.options.memtable_rep,
+            ))
         };
 
         // step2. the actual freeze logic.
@@ -672,6 +1307,15 @@ impl LsmStorageInner {
         Ok(())
     }
 
+    /// freeze the memtable from outside the write path. Writers load the state without
+    /// locking, so the write lock is taken and the pipeline drained to wait for the writes
+    /// in flight, which would otherwise land in the memtable after it is frozen.
+    pub(crate) fn freeze_memtable_outside_writes(&self) -> Result<()> {
+        let _writes = self.mvcc().write_lock.lock();
+        self.mvcc().pipeline.wait_idle();
+        self.force_freeze_memtable(&self.state_lock.lock())
+    }
+
     fn freeze_memtable_with_memtable(&self, memtable: Arc<MemTable>) -> Result<()> {
         // step1. get snapshot
         let mut guard = self.state.write();
@@ -684,6 +1328,7 @@ impl LsmStorageInner {
         // step3. update the state and sync
         *guard = Arc::new(snapshot);
         drop(guard);
+        old_memtable.mark_immutable();
         old_memtable.sync_wal()?;
 
         Ok(())
@@ -703,14 +1348,16 @@ impl LsmStorageInner {
         }
 
         // step2. doing on purpose
-        let mut builder = SsTableBuilder::new(self.options.block_size);
+        let mut builder = self.new_sst_builder(0, false);
         flush_memtable.flush(&mut builder)?;
         let sst_id = flush_memtable.id();
-        let sst = Arc::new(builder.build(
+        let sst = builder.build(
             sst_id,
             Some(self.block_cache.clone()),
             self.path_of_sst(sst_id),
-        )?);
+        )?;
+        let sst = Arc::new(self.configure_sst(sst, 0)?);
+        let sst_meta = SstMeta::of(&sst);
         {
             let mut guard = self.state.write();
             let mut snapshot = guard.as_ref().clone();
@@ -731,11 +1378,14 @@ impl LsmStorageInner {
             snapshot.sstables.insert(sst_id, sst);
             *guard = Arc::new(snapshot);
         }
+        self.free_write_buffer(flush_memtable.approximate_size());
 
         // update manifest and sync : wal, manifest and flush to Disk
         if self.options.enable_wal {
             std::fs::remove_file(self.path_of_wal(sst_id))?;
         }
+        self.manifest()
+            .add_record(&state_lock, ManifestRecord::NewSsts(vec![sst_meta]))?;
         self.manifest()
             .add_record(&state_lock, ManifestRecord::Flush(sst_id))?;
         self.sync_dir()?;
@@ -743,9 +1393,194 @@ impl LsmStorageInner {
         Ok(())
     }
 
-    pub fn add_compaction_filter(&self, compaction_filter: CompactionFilter) {
+    /// attach SSTs written by `SstFileWriter`, returning the commit timestamp all their
+    /// entries become visible at. Each file is copied into the storage, to the lowest
+    /// level holding nothing it overlaps, the originals are left as they are.
+    pub fn ingest_external_files(&self, paths: &[impl AsRef<Path>]) -> Result<u64> {
+        let mut files = paths
+            .iter()
+            .map(|path| {
+                let file = FileObject::open(path.as_ref())
+                    .with_context(|| format!("failed to open {}", path.as_ref().display()))?;
+                Ok(Arc::new(SsTable::open(0, None, file)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        files.sort_by(|x, y| x.first_key().key_ref().cmp(y.first_key().key_ref()));
+        if files
+            .windows(2)
+            .any(|pair| pair[0].last_key().key_ref() >= pair[1].first_key().key_ref())
+        {
+            bail!("the files to ingest overlap each other");
+        }
+
+        // no write may take a timestamp until the files are in.
+        let _writes = self.mvcc().write_lock.lock();
+        self.mvcc().pipeline.wait_idle();
+        // lookups probe the memtables first, so those overlapping the files are flushed
+        // ahead of the newer entries ingested.
+        let snapshot = self.state.read();
+        let overlaps_memtable = std::iter::once(&snapshot.memtable)
+            .chain(&snapshot.imm_memtables)
+            .any(|memtable| {
+                files.iter().any(|file| {
+                    memtable
+                        .scan(
+                            Bound::Included(KeySlice::from_slice(
+                                file.first_key().key_ref(),
+                                key::TS_RANGE_BEGIN,
+                            )),
+                            Bound::Included(KeySlice::from_slice(
+                                file.last_key().key_ref(),
+                                key::TS_RANGE_END,
+                            )),
+                        )
+                        .is_valid()
+                })
+            });
+        let memtable_empty = snapshot.memtable.is_empty();
+        drop(snapshot);
+        if overlaps_memtable {
+            if !memtable_empty {
+                self.force_freeze_memtable(&self.state_lock.lock())?;
+            }
+            while !self.state.read().imm_memtables.is_empty() {
+                self.force_flush_next_imm_memtable()?;
+            }
+        }
+
+        let commit_ts = if self.options.enable_pipelined_write {
+            self.mvcc().pipeline.allocate()
+        } else {
+            self.mvcc().latest_commit_ts() + 1
+        };
+        let result = self.ingest_at(&files, commit_ts);
+        if self.options.enable_pipelined_write {
+            // published even on failure, as an empty commit.
+            self.mvcc().finish_commit(commit_ts);
+        } else if result.is_ok() {
+            self.mvcc().update_commit_ts(commit_ts);
+        }
+        result.map(|_| commit_ts)
+    }
+
+    /// copy the files into the storage with every entry at `commit_ts`, then add them to
+    /// the state and the manifest in one record.
+    fn ingest_at(&self, files: &[Arc<SsTable>], commit_ts: u64) -> Result<()> {
+        let state_lock = self.state_lock.lock();
+        let snapshot = self.state.read();
+        let mut ingested = Vec::with_capacity(files.len());
+        for file in files {
+            let sst_id = self.next_sst_id();
+            match self.build_ingested_sst(&snapshot, file, sst_id, commit_ts) {
+                Ok(sst) => ingested.push(sst),
+                Err(e) => {
+                    // none of the files is ingested, delete what was built for them.
+                    let built = ingested.iter().map(|(_, sst)| sst.sst_id());
+                    for sst_id in built.chain([sst_id]) {
+                        let _ = std::fs::remove_file(self.path_of_sst(sst_id));
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        drop(snapshot);
+        self.sync_dir()?;
+        self.manifest().add_record(
+            &state_lock,
+            ManifestRecord::IngestExternalFiles(
+                ingested
+                    .iter()
+                    .map(|(level, sst)| (*level, SstMeta::of(sst)))
+                    .collect(),
+            ),
+        )?;
+
+        let mut guard = self.state.write();
+        let mut snapshot = guard.as_ref().clone();
+        for (level, sst) in ingested {
+            let sst_id = sst.sst_id();
+            snapshot.sstables.insert(sst_id, sst);
+            if level > 0 {
+                let ssts = &mut snapshot.levels[level - 1].1;
+                ssts.push(sst_id);
+                ssts.sort_by(|x, y| {
+                    snapshot.sstables[x]
+                        .first_key()
+                        .cmp(snapshot.sstables[y].first_key())
+                });
+            } else if self.compaction_controller.flush_to_l0() {
+                snapshot.l0_sstables.insert(0, sst_id);
+            } else {
+                snapshot.levels.insert(0, (sst_id, vec![sst_id]));
+            }
+        }
+        *guard = Arc::new(snapshot);
+        Ok(())
+    }
+
+    /// rewrite `file` into SST `sst_id` at `commit_ts`, in the level it is ingested into.
+    fn build_ingested_sst(
+        &self,
+        snapshot: &LsmStorageState,
+        file: &Arc<SsTable>,
+        sst_id: usize,
+        commit_ts: u64,
+    ) -> Result<(usize, Arc<SsTable>)> {
+        let level = self.pick_ingest_level(snapshot, file);
+        let bottommost = level > 0 && level == snapshot.levels.len();
+        let mut builder = self.new_sst_builder(level, bottommost);
+        let mut iter = SsTableIterator::create_and_seek_to_first(file.clone())?;
+        let mut last_key = Vec::new();
+        while iter.is_valid() {
+            if iter.key().key_ref() <= &last_key[..] {
+                bail!("the keys of an ingested file must be strictly increasing");
+            }
+            last_key = iter.key().key_ref().to_vec();
+            builder.add(KeySlice::from_slice(&last_key, commit_ts), iter.value());
+            iter.next()?;
+        }
+        let sst = builder.build(
+            sst_id,
+            Some(self.block_cache.clone()),
+            self.path_of_sst(sst_id),
+        )?;
+        Ok((level, Arc::new(self.configure_sst(sst, level)?)))
+    }
+
+    /// the lowest level `file` can be added to: below every SST it overlaps, and above
+    /// any level a running compaction writes to, which could write over its key range.
+    fn pick_ingest_level(&self, snapshot: &LsmStorageState, file: &SsTable) -> usize {
+        if !self.compaction_controller.flush_to_l0() {
+            return 0;
+        }
+        let (first, last) = (file.first_key().key_ref(), file.last_key().key_ref());
+        let overlaps = |id: &usize| {
+            let sst = &snapshot.sstables[id];
+            sst.first_key().key_ref() <= last && first <= sst.last_key().key_ref()
+        };
+        let compacting = |ids: &[usize]| {
+            ids.iter()
+                .any(|id| snapshot.compacting_sstables.contains(id))
+        };
+        if snapshot.l0_sstables.iter().any(overlaps) {
+            return 0;
+        }
+        let mut level = 0;
+        let mut upper = &snapshot.l0_sstables;
+        for (idx, ssts) in &snapshot.levels {
+            if ssts.iter().any(overlaps) || compacting(upper) || compacting(ssts) {
+                break;
+            }
+            (level, upper) = (*idx, ssts);
+        }
+        level
+    }
+
+    /// Registers a filter run by every later compaction. Pass a [`CompactionFilterFactory`]
+    /// to get a fresh filter per compaction, any cloneable filter works as its own factory.
+    pub fn add_compaction_filter(&self, factory: impl CompactionFilterFactory + 'static) {
         let mut compaction_filters = self.compaction_filters.lock();
-        compaction_filters.push(compaction_filter);
+        compaction_filters.push(Arc::new(factory));
     }
 }
 
@@ -772,6 +1607,9 @@ impl MiniLsm {
         let compaction_thread = Mutex::new(inner.spawn_compaction_thread(rx)?);
         let (tx2, rx) = crossbeam::channel::unbounded();
         let flush_thread = Mutex::new(inner.spawn_flush_thread(rx)?);
+        if let Some(ref manager) = inner.options.write_buffer_manager {
+            manager.register(&inner);
+        }
         Ok(Arc::new(Self {
             inner,
             comapction_notifier: tx1,
@@ -822,9 +1660,12 @@ impl MiniLsm {
         // If No Wal, then check MemTable ( freeze & flush )
         // Chain of Thoughts: Freeze current MemTable and force all flush to the disk.
         if !self.inner.state.read().memtable.is_empty() {
+            let _writes = self.inner.mvcc().write_lock.lock();
+            self.inner.mvcc().pipeline.wait_idle();
             self.inner
-                .freeze_memtable_with_memtable(Arc::new(MemTable::create(
+                .freeze_memtable_with_memtable(Arc::new(MemTable::create_with_rep(
                     self.inner.next_sst_id(),
+                    self.inner.options.memtable_rep,
                 )))?;
         }
         while {
@@ -848,10 +1689,42 @@ impl MiniLsm {
         self.inner.get(key)
     }
 
+    pub fn get_with_options(&self, options: &ReadOptions, key: &[u8]) -> Result<Option<Bytes>> {
+        self.inner.get_with_options(options, key)
+    }
+
+    /// get the values of many keys at once, cheaper than calling `get` for each of them.
+    pub fn multi_get(&self, keys: &[&[u8]]) -> Result<Vec<Option<Bytes>>> {
+        self.inner.multi_get(keys)
+    }
+
+    pub fn multi_get_with_options(
+        &self,
+        options: &ReadOptions,
+        keys: &[&[u8]],
+    ) -> Result<Vec<Option<Bytes>>> {
+        self.inner.multi_get_with_options(options, keys)
+    }
+
     pub fn scan(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> Result<TxnIterator> {
         self.inner.scan(lower, upper)
     }
 
+    /// `scan` reading the SST blocks as `options` ask, e.g. without filling the
+    /// block cache for a bulk export.
+    pub fn scan_with_options(
+        &self,
+        options: &ReadOptions,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+    ) -> Result<TxnIterator> {
+        self.inner.scan_with_options(options, lower, upper)
+    }
+
+    pub fn prefix_scan(&self, prefix: &[u8]) -> Result<TxnIterator> {
+        self.inner.prefix_scan(prefix)
+    }
+
     pub fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
         self.inner.put(key, value)
     }
@@ -867,8 +1740,7 @@ impl MiniLsm {
     /*----------------Sync and Compaction------------------*/
     pub fn flush(&self) -> Result<()> {
         if !self.inner.state.read().memtable.is_empty() {
-            self.inner
-                .force_freeze_memtable(&self.inner.state_lock.lock())?;
+            self.inner.freeze_memtable_outside_writes()?;
         }
         if !self.inner.state.read().imm_memtables.is_empty() {
             self.inner.force_flush_next_imm_memtable()?;
@@ -880,8 +1752,28 @@ impl MiniLsm {
         self.inner.force_full_compaction()
     }
 
-    pub fn add_compaction_filter(&self, compaction_filter: CompactionFilter) {
-        self.inner.add_compaction_filter(compaction_filter)
+    /// compacts every SST overlapping `[lower, upper]` down to `target_level`.
+    pub fn compact_range(
+        &self,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        target_level: usize,
+    ) -> Result<()> {
+        self.inner.compact_range(lower, upper, target_level)
+    }
+
+    pub fn add_compaction_filter(&self, factory: impl CompactionFilterFactory + 'static) {
+        self.inner.add_compaction_filter(factory)
+    }
+
+    /// attach SSTs written by `SstFileWriter`, see `LsmStorageInner::ingest_external_files`.
+    pub fn ingest_external_files(&self, paths: &[impl AsRef<Path>]) -> Result<u64> {
+        self.inner.ingest_external_files(paths)
+    }
+
+    /// the block cache of this instance, possibly shared with others.
+    pub fn block_cache(&self) -> Arc<BlockCache> {
+        self.inner.block_cache.clone()
     }
 
     pub fn sync(&self) -> Result<()> {
@@ -892,8 +1784,7 @@ impl MiniLsm {
     /// Only call this in test cases due to race conditions
     pub fn force_flush(&self) -> Result<()> {
         if !self.inner.state.read().memtable.is_empty() {
-            self.inner
-                .force_freeze_memtable(&self.inner.state_lock.lock())?;
+            self.inner.freeze_memtable_outside_writes()?;
         }
         if !self.inner.state.read().imm_memtables.is_empty() {
             self.inner.force_flush_next_imm_memtable()?;
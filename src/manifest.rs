@@ -8,7 +8,11 @@ use std::{
 };
 
 use crate::compact::CompactionTask;
+use crate::key::KeyBytes;
+use crate::table::{SsTable, TableProperties};
+use crate::table_cache::TableCache;
 use anyhow::{bail, Context, Ok, Result};
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
 /// Manifest stores the metadata of SSTs in the disk
@@ -21,6 +25,51 @@ pub enum ManifestRecord {
     Flush(usize),
     NewMemTable(usize),
     Compaction(CompactionTask, Vec<usize>),
+    /// written ahead of the flush or compaction adding the SSTs.
+    NewSsts(Vec<SstMeta>),
+    /// SSTs ingested together, with the level each was added to.
+    IngestExternalFiles(Vec<(usize, SstMeta)>),
+}
+
+/// What the manifest keeps of an SST, enough to open it lazily on recovery.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SstMeta {
+    pub id: usize,
+    pub first_key: Vec<u8>,
+    pub first_ts: u64,
+    pub last_key: Vec<u8>,
+    pub last_ts: u64,
+    pub size: u64,
+    pub num_blocks: usize,
+    pub properties: TableProperties,
+}
+
+impl SstMeta {
+    pub fn of(sst: &SsTable) -> Self {
+        Self {
+            id: sst.sst_id(),
+            first_key: sst.first_key().key_ref().to_vec(),
+            first_ts: sst.first_key().ts(),
+            last_key: sst.last_key().key_ref().to_vec(),
+            last_ts: sst.last_key().ts(),
+            size: sst.table_size(),
+            num_blocks: sst.num_of_blocks(),
+            properties: sst.properties().clone(),
+        }
+    }
+
+    /// an SST opened through `table_cache` once it is read.
+    pub fn open_lazy(&self, table_cache: Arc<TableCache>) -> SsTable {
+        SsTable::open_lazy(
+            self.id,
+            KeyBytes::from_bytes_with_ts(Bytes::from(self.first_key.clone()), self.first_ts),
+            KeyBytes::from_bytes_with_ts(Bytes::from(self.last_key.clone()), self.last_ts),
+            self.size,
+            self.num_blocks,
+            self.properties.clone(),
+            table_cache,
+        )
+    }
 }
 
 impl Manifest {
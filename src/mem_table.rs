@@ -1,20 +1,25 @@
-// a basic memtable, based on crossbeam-skiplist.
+// a basic memtable, over a sorted in-memory representation of its entries.
 #![allow(unused)]
 #![allow(dead_code)]
+mod arena;
+mod hash_linklist;
+mod skiplist;
+mod vector;
+
 use anyhow::Result;
 use bytes::Bytes;
-use core::borrow;
-use crossbeam_skiplist::map::Entry;
-use crossbeam_skiplist::SkipMap;
-use ouroboros::self_referencing;
-use std::iter::Skip;
 use std::ops::Bound;
 use std::path::Path;
 use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 
+pub use arena::ArenaSkipList;
+pub use hash_linklist::HashLinkListRep;
+pub use skiplist::SkipListRep;
+pub use vector::VectorRep;
+
 use crate::iterators::StorageIterator;
-use crate::key::{KeyBytes, KeySlice};
+use crate::key::{self, KeyBytes, KeySlice};
 use crate::table::SsTableBuilder;
 use crate::wal::Wal;
 
@@ -43,7 +48,7 @@ pub(crate) fn map_key_bound(bound: Bound<KeySlice>) -> Bound<KeyBytes> {
 }
 
 /// Create a bound of `KeySlice` from a bound of `&[u8]`(Native) add ts.
-pub(crate) fn map_key_bound_plus_ts(bound: Bound<&[u8]>, ts: u64) -> Bound<KeySlice> {
+pub(crate) fn map_key_bound_plus_ts(bound: Bound<&[u8]>, ts: u64) -> Bound<KeySlice<'_>> {
     match bound {
         Bound::Included(x) => Bound::Included(KeySlice::from_slice(x, ts)),
         Bound::Excluded(x) => Bound::Excluded(KeySlice::from_slice(x, ts)),
@@ -51,10 +56,78 @@ pub(crate) fn map_key_bound_plus_ts(bound: Bound<&[u8]>, ts: u64) -> Bound<KeySl
     }
 }
 
+/// Get the smallest key greater than every key starting with `prefix`,
+/// `None` when there is none (the prefix is empty or all `0xff`).
+pub(crate) fn prefix_upper_bound(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut upper = prefix.to_vec();
+    while let Some(last) = upper.pop() {
+        if last < u8::MAX {
+            upper.push(last + 1);
+            return Some(upper);
+        }
+    }
+    None
+}
+
+/// The sorted in-memory representation of a memtable's entries, ordered like `KeyBytes`:
+/// by key, then by descending timestamp.
+pub trait MemTableRep: Send + Sync {
+    /// insert an entry, replacing the value of the same key and timestamp, and return the
+    /// bytes of memory the representation grew by. Inserts may run concurrently.
+    fn insert(&self, key: KeySlice, value: &[u8]) -> usize;
+
+    /// get the newest version of the user key of `key` no newer than its timestamp,
+    /// with the timestamp of that version.
+    fn get(&self, key: KeySlice) -> Option<(u64, Bytes)>;
+
+    /// iterate over the entries within the bounds.
+    fn scan(self: Arc<Self>, lower: Bound<KeyBytes>, upper: Bound<KeyBytes>) -> RepIterator;
+
+    fn is_empty(&self) -> bool;
+
+    /// called once the memtable is frozen, no insert follows.
+    fn mark_immutable(&self) {}
+}
+
+pub type RepIterator = Box<dyn Iterator<Item = (KeyBytes, Bytes)> + Send>;
+
+/// The representations a memtable can keep its entries in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemTableRepKind {
+    /// a lock-free skiplist allocated in an arena, see `ArenaSkipList`.
+    ArenaSkipList,
+    /// a `crossbeam_skiplist::SkipMap`, see `SkipListRep`.
+    #[default]
+    SkipList,
+    /// an append-only vector sorted on freeze, for bulk loads, see `VectorRep`.
+    Vector,
+    /// linked lists in buckets hashed by key prefix, for point workloads, see
+    /// `HashLinkListRep`.
+    HashLinkList {
+        prefix_len: usize,
+        bucket_count: usize,
+    },
+}
+
+impl MemTableRepKind {
+    fn build(self) -> Arc<dyn MemTableRep> {
+        match self {
+            Self::ArenaSkipList => Arc::new(ArenaSkipList::new()),
+            Self::SkipList => Arc::new(SkipListRep::new()),
+            Self::Vector => Arc::new(VectorRep::new()),
+            Self::HashLinkList {
+                prefix_len,
+                bucket_count,
+            } => Arc::new(HashLinkListRep::new(prefix_len, bucket_count)),
+        }
+    }
+}
+
 /// Data Structure 1: MemTable in the Memory.
 pub struct MemTable {
-    pub(crate) map: Arc<SkipMap<KeyBytes, Bytes>>,
+    pub(crate) rep: Arc<dyn MemTableRep>,
     id: usize,
+    // the bytes of memory taken by the entries.
     approximate_size: Arc<AtomicUsize>,
     wal: Option<Wal>,
 }
@@ -62,75 +135,108 @@ pub struct MemTable {
 impl MemTable {
     /*----------------MemTable creation and Initialization------------*/
     pub fn create(id: usize) -> Self {
+        Self::create_with_rep(id, MemTableRepKind::default())
+    }
+
+    pub fn create_with_rep(id: usize, rep: MemTableRepKind) -> Self {
         Self {
             id,
-            map: Arc::new(SkipMap::new()),
+            rep: rep.build(),
             approximate_size: Arc::new(AtomicUsize::new(0)),
             wal: None,
         }
     }
 
-    pub fn create_with_wal(id: usize, path: impl AsRef<Path>) -> Result<Self> {
+    pub fn create_with_wal(
+        id: usize,
+        rep: MemTableRepKind,
+        path: impl AsRef<Path>,
+    ) -> Result<Self> {
         Ok(Self {
-            id,
             wal: Some(Wal::create(path)?),
-            map: Arc::new(SkipMap::new()),
-            approximate_size: Arc::new(AtomicUsize::new(0)),
+            ..Self::create_with_rep(id, rep)
         })
     }
 
-    pub fn recover_from_wal(id: usize, path: impl AsRef<Path>) -> Result<Self> {
-        let map = Arc::new(SkipMap::new());
+    pub fn recover_from_wal(
+        id: usize,
+        rep: MemTableRepKind,
+        path: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let memtable = Self::create_with_rep(id, rep);
+        let wal = Wal::recover(path, |key, value| {
+            memtable.insert(key, value);
+        })?;
         Ok(Self {
-            id,
-            wal: Some(Wal::recover(path, &map)?),
-            map,
-            approximate_size: Arc::new(AtomicUsize::new(0)),
+            wal: Some(wal),
+            ..memtable
         })
     }
 
     /*----------------CRUD API and Data Manipulation------------------*/
     pub fn get(&self, key: KeySlice) -> Option<Bytes> {
-        let key_bytes = KeyBytes::from_bytes_with_ts(
-            Bytes::from_static(unsafe { std::mem::transmute(key.key_ref()) }),
-            key.ts(),
-        );
-        self.map.get(&key_bytes).map(|e| e.value().clone())
+        self.rep
+            .get(key)
+            .filter(|(ts, _)| *ts == key.ts())
+            .map(|(_, value)| value)
+    }
+
+    /// get the newest version of `key` visible at `read_ts`, an empty value is a tombstone.
+    pub fn get_visible(&self, key: &[u8], read_ts: u64) -> Option<Bytes> {
+        self.rep
+            .get(KeySlice::from_slice(key, read_ts))
+            .map(|(_, value)| value)
     }
 
     pub fn scan(&self, lower: Bound<KeySlice>, upper: Bound<KeySlice>) -> MemTableIterator {
         let (lower, upper) = (map_key_bound(lower), map_key_bound(upper));
-        let mut iter = MemTableIteratorBuilder {
-            map: self.map.clone(),
-            iter_builder: |map| map.range((lower, upper)),
+        let mut iter = MemTableIterator {
+            iter: self.rep.clone().scan(lower, upper),
             item: (KeyBytes::new(), Bytes::new()),
-        }
-        .build();
+        };
         iter.next().unwrap();
         iter
     }
 
-    pub fn put(&self, key: KeySlice, value: &[u8]) -> Result<()> {
+    /// put an entry, returning the bytes of memory it took.
+    pub fn put(&self, key: KeySlice, value: &[u8]) -> Result<usize> {
         // 先写WAL, 再写内存.
         if let Some(ref wal) = self.wal {
             wal.put(key, value)?;
         }
         // 写内存.
-        let estimated_size = key.raw_len() + value.len();
-        self.map.insert(
-            key.to_key_vec().into_key_bytes(),
-            Bytes::copy_from_slice(value),
-        );
+        Ok(self.insert(key, value))
+    }
+
+    fn insert(&self, key: KeySlice, value: &[u8]) -> usize {
+        let size = self.rep.insert(key, value);
         self.approximate_size
-            .fetch_add(estimated_size, std::sync::atomic::Ordering::Relaxed);
+            .fetch_add(size, std::sync::atomic::Ordering::Relaxed);
+        size
+    }
+
+    /// append a batch to the WAL only, the write path inserts it into the memtable
+    /// afterwards with `insert_batch`.
+    pub(crate) fn append_wal(&self, data: &[(KeySlice, &[u8])]) -> Result<()> {
+        if let Some(ref wal) = self.wal {
+            wal.put_batch(data)?;
+        }
         Ok(())
     }
 
+    /// insert a batch already in the WAL, returning the bytes of memory it took. Several
+    /// batches can be inserted concurrently.
+    pub(crate) fn insert_batch(&self, data: &[(KeySlice, &[u8])]) -> usize {
+        data.iter()
+            .map(|(key, value)| self.insert(*key, value))
+            .sum()
+    }
+
     /*----------------WAL Management: Flush and Sync------------------*/
     /// Flush the mem-table to SSTable. Implement in week 1 day 6.
     pub fn flush(&self, builder: &mut SsTableBuilder) -> Result<()> {
-        for entry in self.map.iter() {
-            builder.add(entry.key().as_key_slice(), &entry.value()[..]);
+        for (key, value) in self.rep.clone().scan(Bound::Unbounded, Bound::Unbounded) {
+            builder.add(key.as_key_slice(), &value);
         }
         Ok(())
     }
@@ -148,72 +254,64 @@ impl MemTable {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.map.is_empty()
+        self.rep.is_empty()
     }
 
+    /// the memtable is frozen, no write follows.
+    pub(crate) fn mark_immutable(&self) {
+        self.rep.mark_immutable();
+    }
+
+    /// the bytes of memory taken by the entries.
     pub fn approximate_size(&self) -> usize {
         self.approximate_size
             .load(std::sync::atomic::Ordering::Relaxed)
     }
+
+    /// the largest timestamp of the entries.
+    pub(crate) fn max_ts(&self) -> u64 {
+        self.rep
+            .clone()
+            .scan(Bound::Unbounded, Bound::Unbounded)
+            .map(|(key, _)| key.ts())
+            .max()
+            .unwrap_or_default()
+    }
 }
 
-/// Define a SkipMap Range-Iterator for `Range Query`, like scan() function.
-type SkipMapRangeIter<'a> = crossbeam_skiplist::map::Range<
-    'a,
-    KeyBytes,
-    (Bound<KeyBytes>, Bound<KeyBytes>),
-    KeyBytes,
-    Bytes,
->;
-
-// define a self-referential struct, to hold refs to its own fields.
-#[self_referencing]
 pub struct MemTableIterator {
-    // store the map, which contain all the key-value pairs.
-    map: Arc<SkipMap<KeyBytes, Bytes>>,
-
-    #[borrows(map)] //the iterator `iter` borrows the `map` field
-    #[not_covariant] //the iterator is not Covariant along with the struct.
-    // iter is the actual Iterator when Range-Query is executed.
-    iter: SkipMapRangeIter<'this>,
+    // iter walks the entries of the range query.
+    iter: RepIterator,
     // item stores the current key-value pair pointed to by the iter.
     item: (KeyBytes, Bytes),
 }
 
-impl MemTableIterator {
-    /// Convert an entry to an item.
-    fn entry_to_item(entry: Option<Entry<'_, KeyBytes, Bytes>>) -> (KeyBytes, Bytes) {
-        entry
-            .map(|x| (x.key().clone(), x.value().clone()))
-            .unwrap_or_else(|| (KeyBytes::new(), Bytes::new()))
-    }
-}
-
 // We need to impl the `StorageIterator`  for MemTableIterator for general purpose.
 impl StorageIterator for MemTableIterator {
     // appoint the KeyType as KeySlice.
     type KeyType<'a> = KeySlice<'a>;
 
     // get the current entry's key.
-    fn key(&self) -> KeySlice {
-        self.borrow_item().0.as_key_slice()
+    fn key(&self) -> KeySlice<'_> {
+        self.item.0.as_key_slice()
     }
 
     // get the current entry's value.
     fn value(&self) -> &[u8] {
-        // borrow_item() provides an `immutable_reference` to item, just like &item.
-        &self.borrow_item().1[..]
+        &self.item.1[..]
     }
 
     // check the validitity of the current entry.
     fn is_valid(&self) -> bool {
-        !self.borrow_item().0.is_empty()
+        !self.item.0.is_empty()
     }
 
     /// moves the iterator to the next position.
     fn next(&mut self) -> anyhow::Result<()> {
-        let entry = self.with_iter_mut(|iter| MemTableIterator::entry_to_item(iter.next()));
-        self.with_mut(|x| *x.item = entry);
+        self.item = self
+            .iter
+            .next()
+            .unwrap_or_else(|| (KeyBytes::new(), Bytes::new()));
         Ok(())
     }
 }
@@ -0,0 +1,394 @@
+use std::{
+    alloc::{self, Layout},
+    cmp::Ordering as CmpOrdering,
+    mem::size_of,
+    ops::Bound,
+    ptr, slice,
+    sync::{
+        atomic::{AtomicPtr, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+
+use crate::key::{KeyBytes, KeySlice};
+
+use super::{MemTableRep, RepIterator};
+
+const MAX_HEIGHT: usize = 12;
+/// the arena allocates memory in blocks of this size, larger entries get a block of their own.
+const ARENA_BLOCK_SIZE: usize = 64 << 10;
+const ALIGN: usize = 8;
+
+/// Arena hands out memory from large blocks, freed all at once with the arena.
+struct Arena {
+    blocks: Mutex<ArenaBlocks>,
+}
+
+struct ArenaBlocks {
+    // every block allocated, with its size.
+    blocks: Vec<(*mut u8, usize)>,
+    // the unused part of the current block.
+    ptr: *mut u8,
+    remaining: usize,
+    // the bytes of every block allocated.
+    allocated: usize,
+}
+
+// SAFETY: the blocks are only handed out under the mutex, and never move until dropped.
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
+
+impl Arena {
+    fn new() -> Self {
+        Self {
+            blocks: Mutex::new(ArenaBlocks {
+                blocks: Vec::new(),
+                ptr: ptr::null_mut(),
+                remaining: 0,
+                allocated: 0,
+            }),
+        }
+    }
+
+    /// allocate `size` bytes aligned to 8.
+    fn alloc(&self, size: usize) -> *mut u8 {
+        let size = size.next_multiple_of(ALIGN);
+        let mut blocks = self.blocks.lock();
+        if size > blocks.remaining {
+            if size > ARENA_BLOCK_SIZE / 4 {
+                return blocks.new_block(size);
+            }
+            blocks.ptr = blocks.new_block(ARENA_BLOCK_SIZE);
+            blocks.remaining = ARENA_BLOCK_SIZE;
+        }
+        let ptr = blocks.ptr;
+        // SAFETY: `size` bytes remain in the current block.
+        blocks.ptr = unsafe { ptr.add(size) };
+        blocks.remaining -= size;
+        ptr
+    }
+
+    /// the bytes of the blocks allocated, the unused part of the ones given up included.
+    fn allocated(&self) -> usize {
+        self.blocks.lock().allocated
+    }
+}
+
+impl ArenaBlocks {
+    fn new_block(&mut self, size: usize) -> *mut u8 {
+        let layout = Layout::from_size_align(size, ALIGN).unwrap();
+        // SAFETY: the layout is never zero sized.
+        let ptr = unsafe { alloc::alloc(layout) };
+        if ptr.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        self.blocks.push((ptr, size));
+        self.allocated += size;
+        ptr
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        for &(ptr, size) in &self.blocks.get_mut().blocks {
+            // SAFETY: allocated by `new_block` with this layout.
+            unsafe { alloc::dealloc(ptr, Layout::from_size_align(size, ALIGN).unwrap()) };
+        }
+    }
+}
+
+/// A skiplist node in the arena, followed by its `height` links and then its key.
+#[repr(C)]
+struct Node {
+    // the value: its length as a u32 followed by its bytes, replaced as a whole.
+    value: AtomicPtr<u8>,
+    ts: u64,
+    key_len: u32,
+    height: u32,
+}
+
+const LINK_SIZE: usize = size_of::<AtomicPtr<Node>>();
+
+impl Node {
+    /// the link of `node` at `level`.
+    ///
+    /// SAFETY: `node` is a node of the list and `level` below its height.
+    unsafe fn link<'a>(node: *const Node, level: usize) -> &'a AtomicPtr<Node> {
+        &*((node as *const u8).add(size_of::<Node>() + level * LINK_SIZE) as *const AtomicPtr<Node>)
+    }
+
+    /// SAFETY: `node` is a node of the list.
+    unsafe fn key<'a>(node: *const Node) -> &'a [u8] {
+        let offset = size_of::<Node>() + (*node).height as usize * LINK_SIZE;
+        slice::from_raw_parts((node as *const u8).add(offset), (*node).key_len as usize)
+    }
+
+    /// SAFETY: `node` is a node of the list other than the head.
+    unsafe fn value<'a>(node: *const Node) -> &'a [u8] {
+        let value = (*node).value.load(Ordering::Acquire);
+        slice::from_raw_parts(
+            value.add(size_of::<u32>()),
+            (value as *const u32).read() as usize,
+        )
+    }
+
+    /// order `node` against `(key, ts)` like `KeyBytes`, by key then by descending ts.
+    ///
+    /// SAFETY: `node` is a node of the list other than the head.
+    unsafe fn compare(node: *const Node, key: &[u8], ts: u64) -> CmpOrdering {
+        Node::key(node).cmp(key).then(ts.cmp(&(*node).ts))
+    }
+
+    /// SAFETY: `node` is a node of the list other than the head.
+    unsafe fn entry(node: *const Node) -> (KeyBytes, Bytes) {
+        (
+            KeyBytes::from_bytes_with_ts(Bytes::copy_from_slice(Node::key(node)), (*node).ts),
+            Bytes::copy_from_slice(Node::value(node)),
+        )
+    }
+}
+
+/// ArenaSkipList is a lock-free skiplist whose nodes, keys and values all live in an
+/// arena. An entry takes one allocation of the arena instead of separate heap buffers,
+/// and the memtable is charged the blocks of the arena as they are allocated. Inserts
+/// may run concurrently, with each other and with readers.
+pub struct ArenaSkipList {
+    arena: Arena,
+    head: *mut Node,
+    // the bytes of the arena already returned by the inserts.
+    charged: AtomicUsize,
+}
+
+// SAFETY: the nodes are published through atomic links once fully written, and live as
+// long as the arena.
+unsafe impl Send for ArenaSkipList {}
+unsafe impl Sync for ArenaSkipList {}
+
+impl Default for ArenaSkipList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArenaSkipList {
+    pub fn new() -> Self {
+        let arena = Arena::new();
+        let head = Self::alloc_node(&arena, MAX_HEIGHT, &[], 0, ptr::null_mut());
+        Self {
+            arena,
+            head,
+            charged: AtomicUsize::new(0),
+        }
+    }
+
+    fn alloc_node(arena: &Arena, height: usize, key: &[u8], ts: u64, value: *mut u8) -> *mut Node {
+        let ptr = arena.alloc(size_of::<Node>() + height * LINK_SIZE + key.len());
+        let node = ptr as *mut Node;
+        // SAFETY: the allocation has room for the node, its links and its key.
+        unsafe {
+            node.write(Node {
+                value: AtomicPtr::new(value),
+                ts,
+                key_len: key.len() as u32,
+                height: height as u32,
+            });
+            for level in 0..height {
+                (Node::link(node, level) as *const AtomicPtr<Node> as *mut AtomicPtr<Node>)
+                    .write(AtomicPtr::new(ptr::null_mut()));
+            }
+            let key_ptr = ptr.add(size_of::<Node>() + height * LINK_SIZE);
+            ptr::copy_nonoverlapping(key.as_ptr(), key_ptr, key.len());
+        }
+        node
+    }
+
+    fn alloc_value(&self, value: &[u8]) -> *mut u8 {
+        let ptr = self.arena.alloc(size_of::<u32>() + value.len());
+        // SAFETY: the allocation has room for the length and the bytes.
+        unsafe {
+            (ptr as *mut u32).write(value.len() as u32);
+            ptr::copy_nonoverlapping(value.as_ptr(), ptr.add(size_of::<u32>()), value.len());
+        }
+        ptr
+    }
+
+    fn random_height() -> usize {
+        let mut height = 1;
+        while height < MAX_HEIGHT && rand::random::<u32>().is_multiple_of(4) {
+            height += 1;
+        }
+        height
+    }
+
+    /// from `prev` at `level`, find the nodes around `(key, ts)`: the last one before it
+    /// and the first one at or after it.
+    ///
+    /// SAFETY: `prev` is before `(key, ts)` and at least `level + 1` high.
+    unsafe fn find_splice_for_level(
+        &self,
+        key: &[u8],
+        ts: u64,
+        mut prev: *mut Node,
+        level: usize,
+    ) -> (*mut Node, *mut Node) {
+        loop {
+            let next = Node::link(prev, level).load(Ordering::Acquire);
+            if next.is_null() || Node::compare(next, key, ts) != CmpOrdering::Less {
+                return (prev, next);
+            }
+            prev = next;
+        }
+    }
+
+    /// the first node at or after `(key, ts)`, null if none.
+    fn seek_node(&self, key: &[u8], ts: u64) -> *mut Node {
+        let mut prev = self.head;
+        let mut next = ptr::null_mut();
+        for level in (0..MAX_HEIGHT).rev() {
+            // SAFETY: the head is before every key and as high as the list.
+            (prev, next) = unsafe { self.find_splice_for_level(key, ts, prev, level) };
+        }
+        next
+    }
+
+    fn insert_entry(&self, key: &[u8], ts: u64, value: &[u8]) {
+        let value = self.alloc_value(value);
+        let mut prevs = [self.head; MAX_HEIGHT];
+        let mut nexts = [ptr::null_mut(); MAX_HEIGHT];
+        let mut prev = self.head;
+        for level in (0..MAX_HEIGHT).rev() {
+            // SAFETY: `prev` comes from the level above, before `(key, ts)`.
+            (prevs[level], nexts[level]) =
+                unsafe { self.find_splice_for_level(key, ts, prev, level) };
+            prev = prevs[level];
+        }
+        // SAFETY: the nodes found are nodes of the list other than the head.
+        unsafe {
+            if !nexts[0].is_null() && Node::compare(nexts[0], key, ts) == CmpOrdering::Equal {
+                (*nexts[0]).value.store(value, Ordering::Release);
+                return;
+            }
+        }
+
+        let height = Self::random_height();
+        let node = Self::alloc_node(&self.arena, height, key, ts, value);
+        // link the node bottom up, a level at a time, searching again when a concurrent
+        // insert changed the splice.
+        for level in 0..height {
+            loop {
+                // SAFETY: `node` is `height` high and `prevs[level]` at least `level + 1`.
+                unsafe {
+                    Node::link(node, level).store(nexts[level], Ordering::Relaxed);
+                    if Node::link(prevs[level], level)
+                        .compare_exchange(nexts[level], node, Ordering::AcqRel, Ordering::Acquire)
+                        .is_ok()
+                    {
+                        break;
+                    }
+                    let (prev, next) = self.find_splice_for_level(key, ts, prevs[level], level);
+                    if level == 0
+                        && !next.is_null()
+                        && Node::compare(next, key, ts) == CmpOrdering::Equal
+                    {
+                        // a concurrent insert of the same key won, replace its value.
+                        (*next).value.store(value, Ordering::Release);
+                        return;
+                    }
+                    (prevs[level], nexts[level]) = (prev, next);
+                }
+            }
+        }
+    }
+}
+
+impl MemTableRep for ArenaSkipList {
+    /// the bytes the arena grew by since the previous insert returned, so the inserts
+    /// add up to the blocks of the arena.
+    fn insert(&self, key: KeySlice, value: &[u8]) -> usize {
+        self.insert_entry(key.key_ref(), key.ts(), value);
+        let allocated = self.arena.allocated();
+        allocated.saturating_sub(self.charged.fetch_max(allocated, Ordering::Relaxed))
+    }
+
+    fn get(&self, key: KeySlice) -> Option<(u64, Bytes)> {
+        let node = self.seek_node(key.key_ref(), key.ts());
+        // SAFETY: a node found is a node of the list other than the head.
+        unsafe {
+            (!node.is_null() && Node::key(node) == key.key_ref())
+                .then(|| ((*node).ts, Bytes::copy_from_slice(Node::value(node))))
+        }
+    }
+
+    fn scan(self: Arc<Self>, lower: Bound<KeyBytes>, upper: Bound<KeyBytes>) -> RepIterator {
+        let node = match lower {
+            Bound::Included(key) => self.seek_node(key.key_ref(), key.ts()),
+            Bound::Excluded(key) => {
+                let node = self.seek_node(key.key_ref(), key.ts());
+                // SAFETY: a node found is a node of the list other than the head.
+                unsafe {
+                    if !node.is_null()
+                        && Node::compare(node, key.key_ref(), key.ts()) == CmpOrdering::Equal
+                    {
+                        Node::link(node, 0).load(Ordering::Acquire)
+                    } else {
+                        node
+                    }
+                }
+            }
+            // SAFETY: the head is as high as the list.
+            Bound::Unbounded => unsafe { Node::link(self.head, 0).load(Ordering::Acquire) },
+        };
+        Box::new(ArenaSkipListIter {
+            list: self,
+            node,
+            upper,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        // SAFETY: the head is as high as the list.
+        unsafe { Node::link(self.head, 0).load(Ordering::Acquire).is_null() }
+    }
+}
+
+struct ArenaSkipListIter {
+    // keeps the arena holding `node` alive.
+    list: Arc<ArenaSkipList>,
+    node: *mut Node,
+    upper: Bound<KeyBytes>,
+}
+
+// SAFETY: `node` lives in the arena of `list`, which the iterator keeps alive.
+unsafe impl Send for ArenaSkipListIter {}
+
+impl Iterator for ArenaSkipListIter {
+    type Item = (KeyBytes, Bytes);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.node.is_null() {
+            return None;
+        }
+        // SAFETY: `node` is a node of the list other than the head.
+        unsafe {
+            let within = match &self.upper {
+                Bound::Included(key) => {
+                    Node::compare(self.node, key.key_ref(), key.ts()) != CmpOrdering::Greater
+                }
+                Bound::Excluded(key) => {
+                    Node::compare(self.node, key.key_ref(), key.ts()) == CmpOrdering::Less
+                }
+                Bound::Unbounded => true,
+            };
+            if !within {
+                self.node = ptr::null_mut();
+                return None;
+            }
+            let entry = Node::entry(self.node);
+            self.node = Node::link(self.node, 0).load(Ordering::Acquire);
+            Some(entry)
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    mem::size_of,
+    ops::Bound,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+
+use crate::key::{KeyBytes, KeySlice};
+
+use super::{
+    vector::{scan_sorted, SortedEntries},
+    MemTableRep, RepIterator,
+};
+
+/// the bytes an entry takes besides its key and value: its list node and the headers of
+/// the two buffers.
+const ENTRY_OVERHEAD: usize = size_of::<ListNode>() + 4 * size_of::<usize>();
+
+struct ListNode {
+    key: KeyBytes,
+    value: Bytes,
+    next: Option<Box<ListNode>>,
+}
+
+/// HashLinkListRep hashes the keys into buckets by their first `prefix_len` bytes, each
+/// bucket a sorted linked list. A point lookup only walks the list of its prefix, so it
+/// suits point workloads with few keys sharing a prefix. Scans sort every entry first.
+pub struct HashLinkListRep {
+    prefix_len: usize,
+    buckets: Vec<Mutex<Option<Box<ListNode>>>>,
+    has_entries: AtomicBool,
+    // the entries sorted once frozen, for the scans flushing the memtable.
+    frozen: OnceLock<SortedEntries>,
+}
+
+impl HashLinkListRep {
+    pub fn new(prefix_len: usize, bucket_count: usize) -> Self {
+        assert!(bucket_count > 0, "the hash table must have buckets");
+        Self {
+            prefix_len,
+            buckets: (0..bucket_count).map(|_| Mutex::new(None)).collect(),
+            has_entries: AtomicBool::new(false),
+            frozen: OnceLock::new(),
+        }
+    }
+
+    fn bucket(&self, key: &[u8]) -> &Mutex<Option<Box<ListNode>>> {
+        let prefix = &key[..self.prefix_len.min(key.len())];
+        &self.buckets[farmhash::fingerprint32(prefix) as usize % self.buckets.len()]
+    }
+
+    fn sorted(&self) -> SortedEntries {
+        if let Some(frozen) = self.frozen.get() {
+            return frozen.clone();
+        }
+        let mut entries = Vec::new();
+        for bucket in &self.buckets {
+            let bucket = bucket.lock();
+            let mut node = bucket.as_deref();
+            while let Some(current) = node {
+                entries.push((current.key.clone(), current.value.clone()));
+                node = current.next.as_deref();
+            }
+        }
+        entries.sort_by(|x, y| x.0.cmp(&y.0));
+        Arc::new(entries)
+    }
+}
+
+impl MemTableRep for HashLinkListRep {
+    fn insert(&self, key: KeySlice, value: &[u8]) -> usize {
+        let mut bucket = self.bucket(key.key_ref()).lock();
+        let mut link = &mut *bucket;
+        while link
+            .as_ref()
+            .is_some_and(|node| node.key.as_key_slice() < key)
+        {
+            link = &mut link.as_mut().unwrap().next;
+        }
+        match link {
+            Some(node) if node.key.as_key_slice() == key => {
+                node.value = Bytes::copy_from_slice(value);
+            }
+            _ => {
+                let next = link.take();
+                *link = Some(Box::new(ListNode {
+                    key: key.to_key_vec().into_key_bytes(),
+                    value: Bytes::copy_from_slice(value),
+                    next,
+                }));
+            }
+        }
+        self.has_entries.store(true, Ordering::Release);
+        key.raw_len() + value.len() + ENTRY_OVERHEAD
+    }
+
+    fn get(&self, key: KeySlice) -> Option<(u64, Bytes)> {
+        let bucket = self.bucket(key.key_ref()).lock();
+        let mut node = bucket.as_deref();
+        while let Some(current) = node {
+            if current.key.as_key_slice() >= key {
+                return (current.key.key_ref() == key.key_ref())
+                    .then(|| (current.key.ts(), current.value.clone()));
+            }
+            node = current.next.as_deref();
+        }
+        None
+    }
+
+    fn scan(self: Arc<Self>, lower: Bound<KeyBytes>, upper: Bound<KeyBytes>) -> RepIterator {
+        scan_sorted(self.sorted(), lower, upper)
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.has_entries.load(Ordering::Acquire)
+    }
+
+    fn mark_immutable(&self) {
+        let sorted = self.sorted();
+        self.frozen.get_or_init(|| sorted);
+    }
+}
+
+impl Drop for HashLinkListRep {
+    fn drop(&mut self) {
+        // unlink the nodes one by one, dropping a long list recursively could overflow
+        // the stack.
+        for bucket in &mut self.buckets {
+            let mut node = bucket.get_mut().take();
+            while let Some(mut current) = node {
+                node = current.next.take();
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::{mem::size_of, ops::Bound, sync::Arc};
+
+use bytes::Bytes;
+use crossbeam_skiplist::SkipMap;
+use ouroboros::self_referencing;
+
+use crate::key::{KeyBytes, KeySlice};
+
+use super::{MemTableRep, RepIterator};
+
+/// the bytes a SkipMap entry takes besides its key and value: the node holding the
+/// `KeyBytes` and `Bytes` handles, an average tower, and the headers of the two buffers.
+const ENTRY_OVERHEAD: usize =
+    size_of::<KeyBytes>() + size_of::<Bytes>() + 3 * size_of::<usize>() + 4 * size_of::<usize>();
+
+/// SkipListRep keeps the entries in a `crossbeam_skiplist::SkipMap`, every key and value
+/// in a buffer of its own.
+#[derive(Default)]
+pub struct SkipListRep {
+    map: SkipMap<KeyBytes, Bytes>,
+}
+
+impl SkipListRep {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MemTableRep for SkipListRep {
+    fn insert(&self, key: KeySlice, value: &[u8]) -> usize {
+        self.map.insert(
+            key.to_key_vec().into_key_bytes(),
+            Bytes::copy_from_slice(value),
+        );
+        key.raw_len() + value.len() + ENTRY_OVERHEAD
+    }
+
+    fn get(&self, key: KeySlice) -> Option<(u64, Bytes)> {
+        let lower = KeyBytes::from_bytes_with_ts(Bytes::copy_from_slice(key.key_ref()), key.ts());
+        self.map
+            .lower_bound(Bound::Included(&lower))
+            .filter(|entry| entry.key().key_ref() == key.key_ref())
+            .map(|entry| (entry.key().ts(), entry.value().clone()))
+    }
+
+    fn scan(self: Arc<Self>, lower: Bound<KeyBytes>, upper: Bound<KeyBytes>) -> RepIterator {
+        Box::new(
+            SkipListIterBuilder {
+                rep: self,
+                iter_builder: |rep| rep.map.range((lower, upper)),
+            }
+            .build(),
+        )
+    }
+
+    fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Define a SkipMap Range-Iterator for `Range Query`, like scan() function.
+type SkipMapRangeIter<'a> = crossbeam_skiplist::map::Range<
+    'a,
+    KeyBytes,
+    (Bound<KeyBytes>, Bound<KeyBytes>),
+    KeyBytes,
+    Bytes,
+>;
+
+// define a self-referential struct, to hold refs to its own fields.
+#[self_referencing]
+struct SkipListIter {
+    rep: Arc<SkipListRep>,
+    #[borrows(rep)]
+    #[not_covariant]
+    iter: SkipMapRangeIter<'this>,
+}
+
+impl Iterator for SkipListIter {
+    type Item = (KeyBytes, Bytes);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.with_iter_mut(|iter| {
+            iter.next()
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+        })
+    }
+}
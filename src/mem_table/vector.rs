@@ -0,0 +1,113 @@
+use std::{mem, mem::size_of, ops::Bound, sync::Arc};
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+
+use crate::key::{KeyBytes, KeySlice};
+
+use super::{MemTableRep, RepIterator};
+
+/// the bytes an entry takes besides its key and value: its slot in the vector and the
+/// headers of the two buffers.
+const ENTRY_OVERHEAD: usize = size_of::<(KeyBytes, Bytes)>() + 4 * size_of::<usize>();
+
+pub(super) type SortedEntries = Arc<Vec<(KeyBytes, Bytes)>>;
+
+/// VectorRep appends the entries to a vector, and sorts them only when read or frozen.
+/// Inserts are cheap, reads during the writes are not: meant for bulk loads, which read
+/// the memtable once it is frozen and flushed.
+#[derive(Default)]
+pub struct VectorRep {
+    entries: Mutex<VectorEntries>,
+}
+
+#[derive(Default)]
+struct VectorEntries {
+    sorted: SortedEntries,
+    // the entries appended since the last sort.
+    unsorted: Vec<(KeyBytes, Bytes)>,
+}
+
+impl VectorRep {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// sort the entries appended since the last read into the sorted ones.
+    fn sorted(&self) -> SortedEntries {
+        let mut entries = self.entries.lock();
+        if entries.unsorted.is_empty() {
+            return entries.sorted.clone();
+        }
+        let mut unsorted = mem::take(&mut entries.unsorted);
+        // keep the last insert of a key: reversed, the stable sort puts it first.
+        unsorted.reverse();
+        unsorted.sort_by(|x, y| x.0.cmp(&y.0));
+        unsorted.dedup_by(|later, first| later.0 == first.0);
+
+        let mut merged = Vec::with_capacity(entries.sorted.len() + unsorted.len());
+        let mut unsorted = unsorted.into_iter().peekable();
+        for entry in entries.sorted.iter() {
+            while let Some(newer) = unsorted.next_if(|newer| newer.0 <= entry.0) {
+                merged.push(newer);
+            }
+            if merged.last().is_none_or(|last| last.0 != entry.0) {
+                merged.push(entry.clone());
+            }
+        }
+        merged.extend(unsorted);
+        entries.sorted = Arc::new(merged);
+        entries.sorted.clone()
+    }
+}
+
+impl MemTableRep for VectorRep {
+    fn insert(&self, key: KeySlice, value: &[u8]) -> usize {
+        self.entries.lock().unsorted.push((
+            key.to_key_vec().into_key_bytes(),
+            Bytes::copy_from_slice(value),
+        ));
+        key.raw_len() + value.len() + ENTRY_OVERHEAD
+    }
+
+    fn get(&self, key: KeySlice) -> Option<(u64, Bytes)> {
+        let sorted = self.sorted();
+        let idx = sorted.partition_point(|(found, _)| found.as_key_slice() < key);
+        sorted
+            .get(idx)
+            .filter(|(found, _)| found.key_ref() == key.key_ref())
+            .map(|(found, value)| (found.ts(), value.clone()))
+    }
+
+    fn scan(self: Arc<Self>, lower: Bound<KeyBytes>, upper: Bound<KeyBytes>) -> RepIterator {
+        scan_sorted(self.sorted(), lower, upper)
+    }
+
+    fn is_empty(&self) -> bool {
+        let entries = self.entries.lock();
+        entries.sorted.is_empty() && entries.unsorted.is_empty()
+    }
+
+    fn mark_immutable(&self) {
+        self.sorted();
+    }
+}
+
+/// iterate over the entries of a sorted vector within the bounds.
+pub(super) fn scan_sorted(
+    entries: SortedEntries,
+    lower: Bound<KeyBytes>,
+    upper: Bound<KeyBytes>,
+) -> RepIterator {
+    let start = match lower {
+        Bound::Included(key) => entries.partition_point(|(found, _)| *found < key),
+        Bound::Excluded(key) => entries.partition_point(|(found, _)| *found <= key),
+        Bound::Unbounded => 0,
+    };
+    let end = match upper {
+        Bound::Included(key) => entries.partition_point(|(found, _)| *found <= key),
+        Bound::Excluded(key) => entries.partition_point(|(found, _)| *found < key),
+        Bound::Unbounded => entries.len(),
+    };
+    Box::new((start..end.max(start)).map(move |idx| entries[idx].clone()))
+}
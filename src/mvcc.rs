@@ -1,6 +1,7 @@
 #![allow(unused)]
 #![allow(dead_code)]
 
+pub mod pipeline;
 pub mod txn;
 pub mod watermark;
 
@@ -15,7 +16,7 @@ use parking_lot::Mutex;
 
 use crate::lsm_storage::LsmStorageInner;
 
-use self::watermark::Watermark;
+use self::{pipeline::CommitPipeline, watermark::Watermark};
 
 /// 为了管理事务的生命周期，需要为每个事务和全局层面记录两部分元信息
 /// 每个事务层面，需要记录自己读写的key列表，以及事务的开始时间戳和提交时间戳
@@ -34,6 +35,8 @@ pub(crate) struct LsmMvccInner {
     pub(crate) commit_lock: Mutex<()>,
     pub(crate) ts: Arc<Mutex<(u64, Watermark)>>,
     pub(crate) committed_txns: Arc<Mutex<BTreeMap<u64, CommittedTxnData>>>,
+    // orders the commits of the pipelined write path.
+    pub(crate) pipeline: CommitPipeline,
 }
 
 impl LsmMvccInner {
@@ -43,6 +46,7 @@ impl LsmMvccInner {
             commit_lock: Mutex::new(()),
             ts: Arc::new(Mutex::new((init_ts, Watermark::new()))),
             committed_txns: Arc::new(Mutex::new(BTreeMap::new())),
+            pipeline: CommitPipeline::new(init_ts),
         }
     }
 
@@ -72,6 +76,12 @@ impl LsmMvccInner {
         self.ts.lock().0 = ts;
     }
 
+    /// publish `commit_ts` of the pipelined write path once every older commit is,
+    /// returning when readers can see it.
+    pub(crate) fn finish_commit(&self, commit_ts: u64) {
+        self.pipeline.finish(commit_ts, &self.ts);
+    }
+
     pub fn latest_commit_ts(&self) -> u64 {
         self.ts.lock().0
     }
@@ -0,0 +1,72 @@
+use std::collections::BTreeSet;
+
+use parking_lot::{Condvar, Mutex};
+
+use super::watermark::Watermark;
+
+/// CommitPipeline orders the writes of the pipelined write path. Writers are handed
+/// commit timestamps in the order they append to the WAL, then insert into the memtable
+/// concurrently. A commit timestamp is only published once every older one is, so
+/// readers never see a batch before the ones committed ahead of it.
+pub(crate) struct CommitPipeline {
+    state: Mutex<PipelineState>,
+    published: Condvar,
+}
+
+struct PipelineState {
+    // the last commit timestamp handed out.
+    allocated: u64,
+    // the last commit timestamp published.
+    published: u64,
+    // commit timestamps whose inserts are done, waiting for older ones to be published.
+    finished: BTreeSet<u64>,
+}
+
+impl CommitPipeline {
+    pub(crate) fn new(init_ts: u64) -> Self {
+        Self {
+            state: Mutex::new(PipelineState {
+                allocated: init_ts,
+                published: init_ts,
+                finished: BTreeSet::new(),
+            }),
+            published: Condvar::new(),
+        }
+    }
+
+    /// hand out the next commit timestamp. Callers hold the write lock, so timestamps
+    /// follow the order of the WAL.
+    pub(crate) fn allocate(&self) -> u64 {
+        let mut state = self.state.lock();
+        state.allocated += 1;
+        state.allocated
+    }
+
+    /// mark the inserts of `commit_ts` done, publish every timestamp now in order into
+    /// `ts`, then wait for `commit_ts` itself to be published.
+    pub(crate) fn finish(&self, commit_ts: u64, ts: &Mutex<(u64, Watermark)>) {
+        let mut guard = self.state.lock();
+        let state = &mut *guard;
+        state.finished.insert(commit_ts);
+        let published = state.published;
+        while state.finished.remove(&(state.published + 1)) {
+            state.published += 1;
+        }
+        if state.published > published {
+            ts.lock().0 = state.published;
+            self.published.notify_all();
+        }
+        while guard.published < commit_ts {
+            self.published.wait(&mut guard);
+        }
+    }
+
+    /// wait until every commit timestamp handed out is published. With the write lock
+    /// held, no memtable insert is in flight afterwards.
+    pub(crate) fn wait_idle(&self) {
+        let mut state = self.state.lock();
+        while state.published < state.allocated {
+            self.published.wait(&mut state);
+        }
+    }
+}
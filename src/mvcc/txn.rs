@@ -6,7 +6,7 @@ use std::{
     sync::{atomic::AtomicBool, Arc},
 };
 
-use crate::mem_table::map_bound;
+use crate::mem_table::{map_bound, prefix_upper_bound};
 use crate::mvcc::CommittedTxnData;
 use anyhow::Result;
 use bytes::Bytes;
@@ -18,7 +18,7 @@ use parking_lot::Mutex;
 use crate::iterators::two_merge_iterator::TwoMergeIterator;
 use crate::iterators::StorageIterator;
 use crate::lsm_iterator::{FusedIterator, LsmIterator};
-use crate::lsm_storage::{LsmStorageInner, WriteBatchRecord};
+use crate::lsm_storage::{LsmStorageInner, ReadOptions, WriteBatchRecord};
 
 pub struct Transaction {
     pub(crate) read_ts: u64,
@@ -30,6 +30,10 @@ pub struct Transaction {
 
 impl Transaction {
     pub fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        self.get_with_options(&ReadOptions::default(), key)
+    }
+
+    pub fn get_with_options(&self, options: &ReadOptions, key: &[u8]) -> Result<Option<Bytes>> {
         // Status check
         let committed = self.committed.load(std::sync::atomic::Ordering::SeqCst);
         assert!(
@@ -51,10 +55,90 @@ impl Transaction {
             }
         }
         // call the underlying `get_with_ts()` method.
-        self.inner.get_with_ts(key, self.read_ts)
+        self.inner
+            .get_with_ts_and_options(key, self.read_ts, options)
+    }
+
+    /// get the values of `keys`, in their order, seeing this transaction's own writes.
+    pub fn multi_get(&self, keys: &[&[u8]]) -> Result<Vec<Option<Bytes>>> {
+        self.multi_get_with_options(&ReadOptions::default(), keys)
+    }
+
+    pub fn multi_get_with_options(
+        &self,
+        options: &ReadOptions,
+        keys: &[&[u8]],
+    ) -> Result<Vec<Option<Bytes>>> {
+        let committed = self.committed.load(std::sync::atomic::Ordering::SeqCst);
+        assert!(
+            !committed,
+            "Cannot operate on Transaction that's committed!"
+        );
+        if let Some(key_hashes) = &self.key_hashes {
+            let mut key_hash = key_hashes.lock();
+            let (_, read_set) = &mut *key_hash;
+            read_set.extend(keys.iter().map(|key| farmhash::hash32(key)));
+        }
+        // keys written by this transaction are answered locally, the rest in one batch.
+        let local = keys
+            .iter()
+            .map(|key| {
+                self.local_storage
+                    .get(*key)
+                    .map(|entry| Some(entry.value().clone()).filter(|value| !value.is_empty()))
+            })
+            .collect::<Vec<_>>();
+        let stored_keys = keys
+            .iter()
+            .zip(&local)
+            .filter(|(_, local)| local.is_none())
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        let mut stored = self
+            .inner
+            .multi_get_with_ts(&stored_keys, self.read_ts, options)?
+            .into_iter();
+        Ok(local
+            .into_iter()
+            .map(|local| local.unwrap_or_else(|| stored.next().unwrap()))
+            .collect())
     }
 
     pub fn scan(self: &Arc<Self>, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> Result<TxnIterator> {
+        self.scan_with_options(&ReadOptions::default(), lower, upper)
+    }
+
+    pub fn scan_with_options(
+        self: &Arc<Self>,
+        options: &ReadOptions,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+    ) -> Result<TxnIterator> {
+        self.scan_with_prefix(lower, upper, None, options)
+    }
+
+    /// scan every key starting with `prefix`, pruning SSTs by their prefix bloom filters.
+    pub fn prefix_scan(self: &Arc<Self>, prefix: &[u8]) -> Result<TxnIterator> {
+        let upper = prefix_upper_bound(prefix);
+        let upper = match &upper {
+            Some(upper) => Bound::Excluded(upper.as_slice()),
+            None => Bound::Unbounded,
+        };
+        self.scan_with_prefix(
+            Bound::Included(prefix),
+            upper,
+            Some(prefix),
+            &ReadOptions::default(),
+        )
+    }
+
+    fn scan_with_prefix(
+        self: &Arc<Self>,
+        lower: Bound<&[u8]>,
+        upper: Bound<&[u8]>,
+        prefix: Option<&[u8]>,
+        options: &ReadOptions,
+    ) -> Result<TxnIterator> {
         let committed = self.committed.load(std::sync::atomic::Ordering::SeqCst);
         assert!(
             !committed,
@@ -73,7 +157,8 @@ impl Transaction {
             self.clone(),
             TwoMergeIterator::create(
                 local_iter,
-                self.inner.scan_with_ts(lower, upper, self.read_ts)?,
+                self.inner
+                    .scan_with_ts_and_prefix(lower, upper, self.read_ts, prefix, options)?,
             )?,
         )
     }
@@ -4,19 +4,12 @@ use anyhow::Result;
 
 use super::txn::Transaction;
 
+#[derive(Default)]
 pub struct Watermark {
     // for this read_ts(u64), how many snapshots(usize) are using.
     readers: BTreeMap<u64, usize>,
 }
 
-impl Default for Watermark {
-    fn default() -> Self {
-        Watermark {
-            readers: BTreeMap::new(),
-        }
-    }
-}
-
 impl Watermark {
     pub fn new() -> Self {
         Self::default()
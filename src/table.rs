@@ -1,20 +1,45 @@
 #![allow(unused)]
-pub(crate) mod bloom;
+pub mod bloom;
 mod builder;
+mod file;
+mod filter;
 mod iterator;
+mod mmap;
+mod prefix_extractor;
+mod properties;
+mod sst_file_writer;
+mod uring;
+mod xor;
 
-use self::bloom::Bloom;
 pub use self::builder::SsTableBuilder;
+pub use self::file::FileObject;
+pub use self::filter::{Filter, FilterPolicy};
 pub use self::iterator::SsTableIterator;
+pub use self::prefix_extractor::PrefixExtractor;
+pub use self::properties::TableProperties;
+pub use self::sst_file_writer::SstFileWriter;
 use crate::block::{self, Block};
+use crate::block_cache::{BlockCache, CachedBlock};
 use crate::key::{Key, KeyBytes, KeySlice};
-use crate::lsm_storage::BlockCache;
+use crate::lsm_storage::ReadOptions;
+use crate::table_cache::TableCache;
 
 use anyhow::anyhow;
 use anyhow::Result;
 use anyhow::{bail, Ok};
-use bytes::{Buf, BufMut};
-use std::{fs::File, io::Read, path::Path, sync::Arc};
+use bytes::{Buf, BufMut, Bytes};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::Read,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use parking_lot::RwLock;
 
 /// Here you can see the Actual BlockMeta(the metadata for managing the Block)
 /// that store Every block's offset in the File and the (FristKey, LastKey) contained.
@@ -26,19 +51,23 @@ pub struct BlockMeta {
 }
 
 impl BlockMeta {
-    pub fn encode_block_meta(block_meta: &[BlockMeta], max_ts: u64, buf: &mut Vec<u8>) {
+    /// the number of bytes this meta takes in an index partition.
+    pub(crate) fn encoded_size(&self) -> usize {
+        // offset, double key_len and the actual length of key and timestamp.
+        std::mem::size_of::<u64>()
+            + std::mem::size_of::<u16>()
+            + self.first_key.raw_len()
+            + std::mem::size_of::<u16>()
+            + self.last_key.raw_len()
+    }
+
+    pub fn encode_block_meta(block_meta: &[BlockMeta], buf: &mut Vec<u8>) {
         // Init with u32, which represents the overall Number of Blocks existing.
         let mut estimated_size = std::mem::size_of::<u32>();
         for meta in block_meta {
             /*----------calculate the size of each block's metadata----------*/
-            // offset.
-            estimated_size += std::mem::size_of::<u32>();
-            // double key_len and the actual length of key and timestamp.
-            estimated_size += std::mem::size_of::<u16>() + meta.first_key.raw_len();
-            estimated_size += std::mem::size_of::<u16>() + meta.last_key.raw_len();
-        }
-        // size of the TimeStamp
-        estimated_size += std::mem::size_of::<u64>();
+            estimated_size += meta.encoded_size();
+        }
         // size of the checksum
         estimated_size += std::mem::size_of::<u32>();
         // reserve space in the buffer to improve perf.
@@ -48,7 +77,7 @@ impl BlockMeta {
         /*------------Put all these staff into buffer--------*/
         buf.put_u32(block_meta.len() as u32);
         for meta in block_meta {
-            buf.put_u32(meta.offset as u32);
+            buf.put_u64(meta.offset as u64);
             //first key
             buf.put_u16(meta.first_key.key_len() as u16);
             buf.put_slice(meta.first_key.key_ref());
@@ -58,24 +87,39 @@ impl BlockMeta {
             buf.put_slice(meta.last_key.key_ref());
             buf.put_u64(meta.last_key.ts());
         }
-        buf.put_u64(max_ts);
-        buf.put_u32(crc32fast::hash(&buf[original_len + 4..]));
+        buf.put_u32(crc32fast::hash(&buf[original_len..]));
         assert_eq!(estimated_size, buf.len() - original_len)
     }
 
-    pub fn decode_block_meta(mut buf: &[u8]) -> Result<(Vec<BlockMeta>, u64)> {
+    pub fn decode_block_meta(buf: &[u8]) -> Result<Vec<BlockMeta>> {
+        // the block count and the checksum.
+        if buf.len() < 8 {
+            bail!("index partition too short: {} bytes", buf.len());
+        }
+        let (mut buf, mut checksum) = buf.split_at(buf.len() - 4);
+        if checksum.get_u32() != crc32fast::hash(buf) {
+            bail!("meta checksum mismatched");
+        }
         let mut block_meta = Vec::new();
         let num = buf.get_u32() as usize;
-        let checksum = crc32fast::hash(&buf[..buf.remaining() - 4]);
         for _ in 0..num {
-            // offset
-            let offset = buf.get_u32() as usize;
+            // offset and the length of the first key.
+            if buf.remaining() < 10 {
+                bail!("index partition truncated");
+            }
+            let offset = buf.get_u64() as usize;
             // first key
             let first_key_len = buf.get_u16() as usize;
+            if buf.remaining() < first_key_len + 10 {
+                bail!("index partition truncated");
+            }
             let first_key =
                 KeyBytes::from_bytes_with_ts(buf.copy_to_bytes(first_key_len), buf.get_u64());
             // last key
             let last_key_len: usize = buf.get_u16() as usize;
+            if buf.remaining() < last_key_len + 8 {
+                bail!("index partition truncated");
+            }
             let last_key =
                 KeyBytes::from_bytes_with_ts(buf.copy_to_bytes(last_key_len), buf.get_u64());
             // The One Indepedent Entity
@@ -85,104 +129,364 @@ impl BlockMeta {
                 last_key,
             });
         }
-        let max_ts = buf.get_u64();
-        if buf.get_u32() != checksum {
-            bail!("meta checksum mismatched");
+        if buf.has_remaining() {
+            bail!("index partition has a wrong length");
         }
-        Ok((block_meta, max_ts))
+        Ok(block_meta)
     }
 }
 
-/// A file object
-pub struct FileObject(Option<File>, u64);
+/// An entry of the top-level index, the only index data kept in memory per SST.
+/// It locates one index partition (the metas of a run of consecutive blocks)
+/// and the filter partition holding the key hashes of the same blocks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PartitionHandle {
+    // index of the partition's first block within the whole SST.
+    first_block_idx: usize,
+    // where the partition's last data block (with its checksum) ends.
+    data_end: usize,
+    index_offset: usize,
+    index_len: usize,
+    filter_offset: usize,
+    filter_len: usize,
+    first_key: KeyBytes,
+    last_key: KeyBytes,
+}
 
-impl FileObject {
-    /// open the file lies in the Given Path and return the File object
-    pub fn open(path: &Path) -> Result<Self> {
-        let file = File::options().read(true).write(false).open(path)?;
-        let size = file.metadata()?.len();
-        Ok(FileObject(Some(file), size))
+impl PartitionHandle {
+    pub(crate) fn encode_top_index(
+        handles: &[PartitionHandle],
+        num_blocks: usize,
+        buf: &mut Vec<u8>,
+    ) {
+        let original_len = buf.len();
+        buf.put_u32(handles.len() as u32);
+        for handle in handles {
+            buf.put_u32(handle.first_block_idx as u32);
+            buf.put_u64(handle.data_end as u64);
+            buf.put_u64(handle.index_offset as u64);
+            buf.put_u32(handle.index_len as u32);
+            buf.put_u64(handle.filter_offset as u64);
+            buf.put_u32(handle.filter_len as u32);
+            buf.put_u16(handle.first_key.key_len() as u16);
+            buf.put_slice(handle.first_key.key_ref());
+            buf.put_u64(handle.first_key.ts());
+            buf.put_u16(handle.last_key.key_len() as u16);
+            buf.put_slice(handle.last_key.key_ref());
+            buf.put_u64(handle.last_key.ts());
+        }
+        buf.put_u32(num_blocks as u32);
+        buf.put_u32(crc32fast::hash(&buf[original_len..]));
     }
 
-    /// Write given data to the path
-    pub fn create(path: &Path, data: Vec<u8>) -> Result<Self> {
-        std::fs::write(path, &data)?;
-        File::open(path)?.sync_all()?;
-        Ok(FileObject(
-            Some(File::options().read(true).write(false).open(path)?),
-            data.len() as u64,
-        ))
+    /// decode the top-level index, returns the handles and the number of blocks.
+    pub(crate) fn decode_top_index(buf: &[u8]) -> Result<(Vec<PartitionHandle>, usize)> {
+        // the partition count, the block count and the checksum.
+        if buf.len() < 12 {
+            bail!("top-level index too short: {} bytes", buf.len());
+        }
+        let (mut buf, mut checksum) = buf.split_at(buf.len() - 4);
+        if checksum.get_u32() != crc32fast::hash(buf) {
+            bail!("top-level index checksum mismatched");
+        }
+        let num = buf.get_u32() as usize;
+        if num == 0 {
+            bail!("top-level index has no partitions");
+        }
+        let mut handles = Vec::new();
+        for _ in 0..num {
+            // the fixed-size fields up to the length of the first key.
+            if buf.remaining() < 38 {
+                bail!("top-level index truncated");
+            }
+            let first_block_idx = buf.get_u32() as usize;
+            let data_end = buf.get_u64() as usize;
+            let index_offset = buf.get_u64() as usize;
+            let index_len = buf.get_u32() as usize;
+            let filter_offset = buf.get_u64() as usize;
+            let filter_len = buf.get_u32() as usize;
+            let first_key_len = buf.get_u16() as usize;
+            if buf.remaining() < first_key_len + 10 {
+                bail!("top-level index truncated");
+            }
+            let first_key =
+                KeyBytes::from_bytes_with_ts(buf.copy_to_bytes(first_key_len), buf.get_u64());
+            let last_key_len = buf.get_u16() as usize;
+            if buf.remaining() < last_key_len + 8 {
+                bail!("top-level index truncated");
+            }
+            let last_key =
+                KeyBytes::from_bytes_with_ts(buf.copy_to_bytes(last_key_len), buf.get_u64());
+            handles.push(PartitionHandle {
+                first_block_idx,
+                data_end,
+                index_offset,
+                index_len,
+                filter_offset,
+                filter_len,
+                first_key,
+                last_key,
+            });
+        }
+        if buf.remaining() != 4 {
+            bail!("top-level index has a wrong length");
+        }
+        let num_blocks = buf.get_u32() as usize;
+        Ok((handles, num_blocks))
     }
+}
 
-    // Executor
-    /// read the file from: `offset`,  read `len` bytes.
-    pub fn read(&self, offset: u64, len: u64) -> Result<Vec<u8>> {
-        use std::os::unix::fs::FileExt;
-        let mut data = vec![0; len as usize];
-        self.0
-            .as_ref()
-            .unwrap()
-            .read_exact_at(&mut data[..], offset)?;
-        Ok(data)
+/// identifies a file as an SST of this engine, stored in the last 8 bytes.
+pub(crate) const SST_MAGIC: u64 = 0x4d49_4e49_4c53_4d54;
+/// the on-disk layout version written into the footer.
+pub(crate) const FORMAT_VERSION: u32 = 1;
+
+/// The fixed-size tail of every SST, read first when the table is opened.
+/// Footer = index offset/len + properties offset/len + version + checksum + magic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Footer {
+    index_offset: u64,
+    index_len: u64,
+    properties_offset: u64,
+    properties_len: u64,
+    format_version: u32,
+}
+
+impl Footer {
+    pub(crate) const SIZE: usize = 4 * std::mem::size_of::<u64>()
+        + 2 * std::mem::size_of::<u32>()
+        + std::mem::size_of::<u64>();
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        let original_len = buf.len();
+        buf.put_u64(self.index_offset);
+        buf.put_u64(self.index_len);
+        buf.put_u64(self.properties_offset);
+        buf.put_u64(self.properties_len);
+        buf.put_u32(self.format_version);
+        buf.put_u32(crc32fast::hash(&buf[original_len..]));
+        buf.put_u64(SST_MAGIC);
+        assert_eq!(Self::SIZE, buf.len() - original_len);
     }
 
-    // Accessor
-    pub fn size(&self) -> u64 {
-        self.1
+    pub(crate) fn decode(mut buf: &[u8]) -> Result<Self> {
+        if buf.len() != Self::SIZE {
+            bail!("footer must be {} bytes, got {}", Self::SIZE, buf.len());
+        }
+        if (&buf[Self::SIZE - 8..]).get_u64() != SST_MAGIC {
+            bail!("bad magic number, not an SST file");
+        }
+        let checksum = crc32fast::hash(&buf[..Self::SIZE - 12]);
+        let footer = Self {
+            index_offset: buf.get_u64(),
+            index_len: buf.get_u64(),
+            properties_offset: buf.get_u64(),
+            properties_len: buf.get_u64(),
+            format_version: buf.get_u32(),
+        };
+        if buf.get_u32() != checksum {
+            bail!("footer checksum mismatched");
+        }
+        if footer.format_version != FORMAT_VERSION {
+            bail!("unsupported SST format version {}", footer.format_version);
+        }
+        Ok(footer)
     }
 }
 
+/// blocks an iterator reads ahead at most with async I/O.
+const MAX_PREFETCH_BLOCKS: usize = 64;
+
 /// An SSTable is a file format used for storing key-value pairs sorted by keys.
+/// The table itself only keeps its key range and size, its file is read through a
+/// [`TableReader`] it either holds, or opens on demand through a [`TableCache`].
 pub struct SsTable {
-    // File handle
-    pub(crate) file: FileObject,
-    // BlockMeta
-    pub(crate) block_meta: Vec<BlockMeta>,
-    pub(crate) block_meta_offset: usize,
-    // Meta data member of SsTable
     id: usize,
     first_key: KeyBytes,
     last_key: KeyBytes,
-    max_ts: u64,
-    // Optimization: Cache and Bloom Filter
+    size: u64,
+    num_blocks: usize,
+    properties: TableProperties,
+    // identifies this table's entries in the block cache, kept when the file is reopened.
+    cache_id: usize,
+    reader: TableReaderSlot,
+    // the pinned partitions were released, readers opened later do not pin them.
+    meta_blocks_unpinned: AtomicBool,
+    // set once the table left the state, its file is deleted when the table is dropped.
+    obsolete: ObsoleteFile,
+}
+
+enum TableReaderSlot {
+    Open(Arc<TableReader>),
+    Cached {
+        table_cache: Arc<TableCache>,
+        settings: ReaderSettings,
+    },
+}
+
+/// How a table cache opens the file of a table.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ReaderSettings {
+    pub(crate) mmap: bool,
+    // pin the index and filter partitions, still caching them when `Some(true)`.
+    pub(crate) pin_meta_blocks: Option<bool>,
+}
+
+/// The open file of an SST. Only the top-level index stays in memory,
+/// index and filter partitions are read on demand through the block cache.
+pub struct TableReader {
+    // File handle
+    pub(crate) file: FileObject,
+    // Top-level index
+    pub(crate) index: Vec<PartitionHandle>,
+    num_blocks: usize,
+    properties: TableProperties,
+    // Optimization: Cache
+    block_cache: Option<Arc<BlockCache>>,
+    // identifies this table's entries in the block cache.
+    cache_id: usize,
+    // index and filter partitions go through the block cache.
+    cache_meta_blocks: bool,
+    // index and filter partitions held by the reader once loaded, never evicted.
+    pinned: RwLock<Option<Vec<PinnedPartition>>>,
+    pinned_charge: PinnedCharge,
+}
+
+/// Bytes read ahead of the block an iterator is positioned on,
+/// or with async I/O, the following blocks already read and decoded.
+#[derive(Default)]
+pub(crate) struct ReadaheadBuffer {
+    offset: usize,
+    data: Vec<u8>,
+    prefetched: VecDeque<(usize, Arc<Block>)>,
+}
+
+impl ReadaheadBuffer {
+    /// get `[offset, offset_end)` of the file, reading `[offset, read_end)` when
+    /// the buffer does not hold it yet.
+    fn read(
+        &mut self,
+        file: &FileObject,
+        offset: usize,
+        offset_end: usize,
+        read_end: usize,
+        direct: bool,
+    ) -> Result<&[u8]> {
+        if offset < self.offset || offset_end > self.offset + self.data.len() {
+            self.data = file.read_with(offset as u64, (read_end - offset) as u64, direct)?;
+            self.offset = offset;
+        }
+        Ok(&self.data[offset - self.offset..offset_end - self.offset])
+    }
+
+    /// take block `block_idx` if it was prefetched, dropping the blocks before it.
+    fn take_prefetched(&mut self, block_idx: usize) -> Option<Arc<Block>> {
+        while let Some(&(idx, _)) = self.prefetched.front() {
+            if idx > block_idx {
+                break;
+            }
+            let (idx, block) = self.prefetched.pop_front().unwrap();
+            if idx == block_idx {
+                return Some(block);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Default)]
+struct PinnedPartition {
+    index: OnceLock<CachedBlock>,
+    filter: OnceLock<CachedBlock>,
+}
+
+/// bytes of pinned partitions charged to a block cache, given back when dropped.
+#[derive(Default)]
+pub(crate) struct PinnedCharge {
     block_cache: Option<Arc<BlockCache>>,
-    pub(crate) bloom: Option<Bloom>,
+    bytes: AtomicU64,
+}
+
+impl PinnedCharge {
+    fn add(&self, bytes: u64) {
+        if let Some(ref block_cache) = self.block_cache {
+            block_cache.reserve(bytes);
+            self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    fn release(&self) {
+        let bytes = self.bytes.swap(0, Ordering::Relaxed);
+        if let Some(ref block_cache) = self.block_cache {
+            block_cache.release(bytes);
+        }
+    }
+}
+
+impl Drop for PinnedCharge {
+    fn drop(&mut self) {
+        self.release();
+    }
 }
 
 impl SsTable {
     /*-----------------------Constructor--------------------------- */
 
     /// `open()` is responsible for opening an SSTable from a file.
-    /// this function reads the necessary metadata from the file,
-    /// including the Bloom filter and constructs an `SSTable` object.
+    /// this function only reads the footer, the top-level index and the properties,
+    /// the index and filter partitions are loaded lazily, and constructs an `SSTable` object.
     /// params:
     /// id : an identifier for the SSTable
     /// block_cache: Optional, used to store blocks of data read from the SSTable file.
     /// file : the file object representing the SSTable file.
     pub fn open(id: usize, block_cache: Option<Arc<BlockCache>>, file: FileObject) -> Result<Self> {
-        // Read metadata.
-        let len = file.size();
-        let raw_bloom_offset = file.read(len - 4, 4)?;
-        let bloom_offset = (&raw_bloom_offset[..]).get_u32() as u64;
-        let raw_bloom = file.read(bloom_offset, len - 4 - bloom_offset)?;
-        let bloom_filter = Bloom::decode(&raw_bloom)?;
-        // read block metadata.
-        let raw_meta_offset = file.read(bloom_offset - 4, 4)?;
-        let block_meta_offset = (&raw_meta_offset[..]).get_u32() as u64;
-        let raw_meta = file.read(block_meta_offset, bloom_offset - 4 - block_meta_offset)?;
-        let (block_meta, max_ts) = BlockMeta::decode_block_meta(&raw_meta[..])?;
-        // construct SSTable Object.
-        Ok(Self {
-            file,
-            first_key: block_meta.first().unwrap().first_key.clone(),
-            last_key: block_meta.last().unwrap().last_key.clone(),
-            block_meta,
-            block_meta_offset: block_meta_offset as usize,
+        let cache_id = block_cache.as_ref().map_or(0, |cache| cache.new_table_id());
+        let reader = TableReader::open(file, block_cache, cache_id)?;
+        Ok(Self::from_reader(id, reader))
+    }
+
+    /// a table holding an opened reader.
+    pub(crate) fn from_reader(id: usize, reader: TableReader) -> Self {
+        Self {
             id,
-            max_ts,
-            block_cache,
-            bloom: Some(bloom_filter),
-        })
+            first_key: reader.index.first().unwrap().first_key.clone(),
+            last_key: reader.index.last().unwrap().last_key.clone(),
+            size: reader.file.size(),
+            num_blocks: reader.num_blocks,
+            properties: reader.properties.clone(),
+            cache_id: reader.cache_id,
+            reader: TableReaderSlot::Open(Arc::new(reader)),
+            meta_blocks_unpinned: AtomicBool::new(false),
+            obsolete: ObsoleteFile::default(),
+        }
+    }
+
+    /// a table whose file is only opened, through `table_cache`, once it is read.
+    /// Its key range, size, block count and properties are known without opening it.
+    pub fn open_lazy(
+        id: usize,
+        first_key: KeyBytes,
+        last_key: KeyBytes,
+        size: u64,
+        num_blocks: usize,
+        properties: TableProperties,
+        table_cache: Arc<TableCache>,
+    ) -> Self {
+        Self {
+            id,
+            first_key,
+            last_key,
+            size,
+            num_blocks,
+            properties,
+            cache_id: table_cache.block_cache().new_table_id(),
+            reader: TableReaderSlot::Cached {
+                table_cache,
+                settings: ReaderSettings::default(),
+            },
+            meta_blocks_unpinned: AtomicBool::new(false),
+            obsolete: ObsoleteFile::default(),
+        }
     }
 
     /// create a `mock SST`(means that It has not File object underlying)
@@ -194,75 +498,191 @@ impl SsTable {
         last_key: KeyBytes,
     ) -> Self {
         Self {
-            file: FileObject(None, file_size),
-            block_meta: vec![],
-            block_meta_offset: 0,
             id,
             first_key,
             last_key,
-            max_ts: 0,
-            block_cache: None,
-            bloom: None,
+            size: file_size,
+            num_blocks: 0,
+            properties: TableProperties::default(),
+            cache_id: 0,
+            reader: TableReaderSlot::Open(Arc::new(TableReader::meta_only(file_size))),
+            meta_blocks_unpinned: AtomicBool::new(false),
+            obsolete: ObsoleteFile::default(),
+        }
+    }
+
+    /// change the reader settings, before the table is shared.
+    fn configure_reader(
+        mut self,
+        configure: impl FnOnce(TableReader) -> Result<TableReader>,
+        configure_settings: impl FnOnce(&mut ReaderSettings),
+    ) -> Result<Self> {
+        self.reader = match self.reader {
+            TableReaderSlot::Open(reader) => {
+                let reader = Arc::into_inner(reader).expect("the table reader is shared");
+                TableReaderSlot::Open(Arc::new(configure(reader)?))
+            }
+            TableReaderSlot::Cached {
+                table_cache,
+                mut settings,
+            } => {
+                configure_settings(&mut settings);
+                TableReaderSlot::Cached {
+                    table_cache,
+                    settings,
+                }
+            }
+        };
+        Ok(self)
+    }
+
+    /// keep the index and filter partitions in the reader once loaded, so block cache
+    /// traffic cannot evict them. With `cache_meta_blocks` they are charged to the block
+    /// cache for as long as they are pinned, otherwise they bypass it.
+    pub fn pin_meta_blocks(self, cache_meta_blocks: bool) -> Self {
+        self.configure_reader(
+            |reader| Ok(reader.pin_meta_blocks(cache_meta_blocks)),
+            |settings| settings.pin_meta_blocks = Some(cache_meta_blocks),
+        )
+        .unwrap()
+    }
+
+    /// read the table through a memory map of its file.
+    pub fn into_mmap(self) -> Result<Self> {
+        self.configure_reader(TableReader::into_mmap, |settings| settings.mmap = true)
+    }
+
+    /// hand the opened reader over to `table_cache`, which may close it
+    /// and open the file again later on.
+    pub fn into_table_cache(self, table_cache: Arc<TableCache>) -> Self {
+        let TableReaderSlot::Open(reader) = self.reader else {
+            return self;
+        };
+        let settings = ReaderSettings {
+            mmap: reader.file.is_mmap(),
+            pin_meta_blocks: reader
+                .meta_blocks_pinned()
+                .then_some(reader.cache_meta_blocks),
+        };
+        table_cache.insert(self.id, reader);
+        Self {
+            reader: TableReaderSlot::Cached {
+                table_cache,
+                settings,
+            },
+            ..self
+        }
+    }
+
+    /// the index and filter partitions are held by the reader.
+    pub fn meta_blocks_pinned(&self) -> bool {
+        match &self.reader {
+            TableReaderSlot::Open(reader) => reader.meta_blocks_pinned(),
+            TableReaderSlot::Cached { settings, .. } => {
+                settings.pin_meta_blocks.is_some()
+                    && !self.meta_blocks_unpinned.load(Ordering::SeqCst)
+            }
+        }
+    }
+
+    /// drop the pinned index and filter partitions and give their charge back to the
+    /// block cache, they are read through the block cache from now on.
+    pub fn unpin_meta_blocks(&self) {
+        self.meta_blocks_unpinned.store(true, Ordering::SeqCst);
+        match &self.reader {
+            TableReaderSlot::Open(reader) => reader.unpin_meta_blocks(),
+            TableReaderSlot::Cached { table_cache, .. } => {
+                if let Some(reader) = table_cache.get(self.id) {
+                    reader.unpin_meta_blocks();
+                }
+            }
+        }
+    }
+
+    /// delete the file of a table no longer in the state. A file read through the table
+    /// cache is only deleted with the last reference to the table: a snapshot still
+    /// holding the table may have to open the file again.
+    pub(crate) fn delete_file(&self, path: &Path) -> Result<()> {
+        match &self.reader {
+            TableReaderSlot::Open(_) => std::fs::remove_file(path)?,
+            TableReaderSlot::Cached { table_cache, .. } => {
+                let _ = self.obsolete.0.set((table_cache.clone(), self.id));
+            }
+        }
+        Ok(())
+    }
+
+    /// the reader of the table file, opening it if the table cache does not hold it.
+    pub fn reader(&self) -> Result<Arc<TableReader>> {
+        match &self.reader {
+            TableReaderSlot::Open(reader) => Ok(reader.clone()),
+            TableReaderSlot::Cached {
+                table_cache,
+                settings,
+            } => {
+                let mut settings = *settings;
+                let unpinned = self.meta_blocks_unpinned.load(Ordering::SeqCst);
+                if unpinned {
+                    settings.pin_meta_blocks = None;
+                }
+                let reader = table_cache.get_or_open(self.id, self.cache_id, settings)?;
+                // a reader opened before the partitions were unpinned may still pin them.
+                if unpinned {
+                    reader.unpin_meta_blocks();
+                }
+                Ok(reader)
+            }
         }
     }
 
     /*-----------------------Executor--------------------------- */
 
     /// reads a block from the disk based on the given block index.
-    /// block_idx: index of the block to be read.
     pub fn read_block(&self, block_idx: usize) -> Result<Arc<Block>> {
-        // determines the offset(Start) and length of the block data in the file.
-        let offset = self.block_meta[block_idx].offset;
-        let offset_end = self
-            .block_meta
-            .get(block_idx + 1)
-            .map_or(self.block_meta_offset, |x| x.offset);
-        let block_len = offset_end - offset - 4;
-        // reads the block data along with the checksum from  the file
-        let block_data_with_checksum: Vec<u8> = self
-            .file
-            .read(offset as u64, (offset_end - offset) as u64)?;
-        let block_data = &block_data_with_checksum[..block_len];
-        let checksum = (&block_data_with_checksum[block_len..]).get_u32();
-        // verifies the checksum against the pre-calculated checksum
-        if checksum != crc32fast::hash(block_data) {
-            bail!("block checksum mismatched!");
-        }
-        // decodes the block data and return it as an Arc reference
-        Ok(Arc::new(Block::decode(block_data)))
+        self.reader()?.read_block(block_idx)
     }
 
     /// Read a block from the disk, with block cache.
-    /// block_idx: index of the block to be read.
     pub fn read_block_cached(&self, block_idx: usize) -> Result<Arc<Block>> {
-        // Checks if a block cache is available
-        if let Some(ref block_cache) = self.block_cache {
-            // if available, attempts to retrieve the block from the cache.
-            let block = block_cache
-                .try_get_with((self.id, block_idx), || self.read_block(block_idx))
-                .map_err(|e| anyhow!("{}", e))?;
-            Ok(block)
-        } else {
-            // if not, simply reads the block from disk.
-            self.read_block(block_idx)
-        }
+        self.reader()?.read_block_cached(block_idx)
+    }
+
+    /// Read several blocks at once, see [`TableReader::read_blocks`].
+    pub fn read_blocks(
+        &self,
+        block_idxs: &[usize],
+        options: &ReadOptions,
+    ) -> Result<Vec<Arc<Block>>> {
+        self.reader()?.read_blocks(block_idxs, options)
     }
 
     /// Find the index of the block that many contain `Key`
-    /// key: the Key to search for, usize: the index of the block.
-    pub fn find_block_idx(&self, key: KeySlice) -> usize {
-        self.block_meta
-            .partition_point(|meta| meta.first_key.as_key_slice() <= key)
-            .saturating_sub(1)
+    pub fn find_block_idx(&self, key: KeySlice) -> Result<usize> {
+        self.reader()?.find_block_idx(key)
+    }
+
+    /// check the filter for `key`, see [`TableReader::may_contain_key`].
+    pub fn may_contain_key(&self, key: &[u8]) -> Result<bool> {
+        self.reader()?.may_contain_key(key)
+    }
+
+    /// check the filters for `prefix`, see [`TableReader::may_contain_prefix`].
+    pub fn may_contain_prefix(&self, prefix: &[u8], prefix_hash: u32) -> Result<bool> {
+        self.reader()?.may_contain_prefix(prefix, prefix_hash)
     }
 
     /*-----------------------Accessor--------------------------- */
+    /// the user key each block starts with, used to cut compactions into sub-ranges.
+    pub(crate) fn block_first_keys(&self) -> Result<Vec<Bytes>> {
+        self.reader()?.block_first_keys()
+    }
+
     pub fn table_size(&self) -> u64 {
-        self.file.1
+        self.size
     }
 
     pub fn num_of_blocks(&self) -> usize {
-        self.block_meta.len()
+        self.num_blocks
     }
 
     pub fn sst_id(&self) -> usize {
@@ -278,6 +698,527 @@ impl SsTable {
     }
 
     pub fn max_ts(&self) -> u64 {
-        self.max_ts
+        self.properties.max_ts
+    }
+
+    pub fn properties(&self) -> &TableProperties {
+        &self.properties
+    }
+}
+
+/// the file of an SST read through a table cache, deleted on drop once set.
+#[derive(Default)]
+struct ObsoleteFile(OnceLock<(Arc<TableCache>, usize)>);
+
+impl Drop for ObsoleteFile {
+    fn drop(&mut self) {
+        if let Some((table_cache, id)) = self.0.get() {
+            if let Err(e) = table_cache.remove_file(*id) {
+                eprintln!("failed to delete SST {}: {}", id, e);
+            }
+        }
+    }
+}
+
+impl TableReader {
+    /*-----------------------Constructor--------------------------- */
+
+    /// read the footer, the top-level index and the properties of an SST file.
+    pub(crate) fn open(
+        file: FileObject,
+        block_cache: Option<Arc<BlockCache>>,
+        cache_id: usize,
+    ) -> Result<Self> {
+        // Read the footer.
+        let len = file.size();
+        let footer_size = Footer::SIZE as u64;
+        if len < footer_size {
+            bail!("file of {} bytes is too small to be an SST", len);
+        }
+        let footer = Footer::decode(&file.read(len - footer_size, footer_size)?)?;
+        // Read the top-level index and the properties.
+        let raw_index = file.read(footer.index_offset, footer.index_len)?;
+        let (index, num_blocks) = PartitionHandle::decode_top_index(&raw_index)?;
+        let raw_properties = file.read(footer.properties_offset, footer.properties_len)?;
+        let properties = TableProperties::decode(&raw_properties)?;
+        Ok(Self {
+            file,
+            index,
+            num_blocks,
+            properties,
+            block_cache,
+            cache_id,
+            cache_meta_blocks: true,
+            pinned: RwLock::new(None),
+            pinned_charge: PinnedCharge::default(),
+        })
+    }
+
+    /// a reader without a file underneath.
+    fn meta_only(file_size: u64) -> Self {
+        Self {
+            file: FileObject::meta_only(file_size),
+            index: vec![],
+            num_blocks: 0,
+            properties: TableProperties::default(),
+            block_cache: None,
+            cache_id: 0,
+            cache_meta_blocks: true,
+            pinned: RwLock::new(None),
+            pinned_charge: PinnedCharge::default(),
+        }
+    }
+
+    /// keep the index and filter partitions in the reader once loaded.
+    pub(crate) fn pin_meta_blocks(mut self, cache_meta_blocks: bool) -> Self {
+        self.cache_meta_blocks = cache_meta_blocks;
+        self.pinned_charge = PinnedCharge {
+            block_cache: self.block_cache.clone().filter(|_| cache_meta_blocks),
+            bytes: AtomicU64::new(0),
+        };
+        *self.pinned.get_mut() = Some(
+            (0..self.index.len())
+                .map(|_| PinnedPartition::default())
+                .collect(),
+        );
+        self
+    }
+
+    /// drop the pinned partitions, giving their charge back to the block cache.
+    pub(crate) fn unpin_meta_blocks(&self) {
+        if self.pinned.write().take().is_some() {
+            self.pinned_charge.release();
+        }
+    }
+
+    /// read the file through a memory map.
+    pub(crate) fn into_mmap(mut self) -> Result<Self> {
+        self.file = self.file.into_mmap()?;
+        Ok(self)
+    }
+
+    /// open the file as `settings` ask.
+    pub(crate) fn open_with_settings(
+        file: FileObject,
+        block_cache: Option<Arc<BlockCache>>,
+        cache_id: usize,
+        settings: ReaderSettings,
+    ) -> Result<Self> {
+        let mut reader = Self::open(file, block_cache, cache_id)?;
+        if settings.mmap {
+            reader = reader.into_mmap()?;
+        }
+        if let Some(cache_meta_blocks) = settings.pin_meta_blocks {
+            reader = reader.pin_meta_blocks(cache_meta_blocks);
+        }
+        Ok(reader)
+    }
+
+    /// the index and filter partitions are held by the reader.
+    pub fn meta_blocks_pinned(&self) -> bool {
+        self.pinned.read().is_some()
+    }
+
+    /*-----------------------Executor--------------------------- */
+
+    /// look `offset` up in the block cache, reading it with `read` on a miss.
+    fn read_cached(
+        &self,
+        offset: usize,
+        high_priority: bool,
+        read: impl FnOnce() -> Result<CachedBlock>,
+    ) -> Result<CachedBlock> {
+        if let Some(ref block_cache) = self.block_cache {
+            block_cache.try_get_with((self.cache_id, offset), high_priority, read)
+        } else {
+            read()
+        }
+    }
+
+    /// read an index or filter partition: from the reader when it pins them, loading and
+    /// pinning it on first use, otherwise through the block cache unless the table bypasses it.
+    fn read_meta_block(
+        &self,
+        partition_idx: usize,
+        filter: bool,
+        offset: usize,
+        read: impl FnOnce() -> Result<CachedBlock>,
+    ) -> Result<CachedBlock> {
+        let pinned = self.pinned.read();
+        let Some(pinned) = pinned.as_ref() else {
+            return if self.cache_meta_blocks {
+                self.read_cached(offset, true, read)
+            } else {
+                read()
+            };
+        };
+        let partition = &pinned[partition_idx];
+        let slot = if filter {
+            &partition.filter
+        } else {
+            &partition.index
+        };
+        if let Some(block) = slot.get() {
+            return Ok(block.clone());
+        }
+        let block = read()?;
+        // a concurrent reader may have pinned its copy first, both are the same partition.
+        let mut pinned_now = false;
+        let block = slot
+            .get_or_init(|| {
+                pinned_now = true;
+                block
+            })
+            .clone();
+        if pinned_now {
+            self.pinned_charge.add(block.charge() as u64);
+        }
+        Ok(block)
+    }
+
+    /// load the metas of the blocks in one partition.
+    fn read_index_partition(&self, partition_idx: usize) -> Result<Arc<Vec<BlockMeta>>> {
+        let handle = &self.index[partition_idx];
+        let read = || {
+            let raw = self
+                .file
+                .read(handle.index_offset as u64, handle.index_len as u64)?;
+            Ok(CachedBlock::Index(Arc::new(BlockMeta::decode_block_meta(
+                &raw,
+            )?)))
+        };
+        match self.read_meta_block(partition_idx, false, handle.index_offset, read)? {
+            CachedBlock::Index(block_meta) => Ok(block_meta),
+            _ => bail!(
+                "cached block at {} is not an index partition",
+                handle.index_offset
+            ),
+        }
+    }
+
+    /// load the filter of the keys in one partition.
+    fn read_filter_partition(&self, partition_idx: usize) -> Result<Arc<Filter>> {
+        let handle = &self.index[partition_idx];
+        let read = || {
+            let raw = self
+                .file
+                .read(handle.filter_offset as u64, handle.filter_len as u64)?;
+            Ok(CachedBlock::Filter(Arc::new(Filter::decode(&raw)?)))
+        };
+        match self.read_meta_block(partition_idx, true, handle.filter_offset, read)? {
+            CachedBlock::Filter(filter) => Ok(filter),
+            _ => bail!(
+                "cached block at {} is not a filter partition",
+                handle.filter_offset
+            ),
+        }
+    }
+
+    /// get the `[start, end)` file range of a block, checksum included.
+    fn block_range(&self, block_idx: usize) -> Result<(usize, usize)> {
+        let partition_idx = self
+            .index
+            .partition_point(|handle| handle.first_block_idx <= block_idx)
+            - 1;
+        let handle = &self.index[partition_idx];
+        let block_meta = self.read_index_partition(partition_idx)?;
+        let local_idx = block_idx - handle.first_block_idx;
+        let offset = block_meta[local_idx].offset;
+        let offset_end = block_meta
+            .get(local_idx + 1)
+            .map_or(handle.data_end, |x| x.offset);
+        Ok((offset, offset_end))
+    }
+
+    fn read_block_at(
+        &self,
+        offset: usize,
+        offset_end: usize,
+        options: &ReadOptions,
+    ) -> Result<Arc<Block>> {
+        if let Some(mapped) = self
+            .file
+            .read_mapped(offset as u64, (offset_end - offset) as u64)?
+        {
+            Self::verify_block_checksum(&mapped, options.verify_checksums)?;
+            return Ok(Arc::new(Block::decode_mapped(
+                mapped.slice(0..mapped.len() - 4),
+            )));
+        }
+        // reads the block data along with the checksum from  the file
+        let block_data_with_checksum: Vec<u8> = self.file.read_with(
+            offset as u64,
+            (offset_end - offset) as u64,
+            options.direct_io,
+        )?;
+        Self::decode_block(&block_data_with_checksum, options.verify_checksums)
+    }
+
+    /// decode a block followed by its checksum.
+    fn decode_block(block_data_with_checksum: &[u8], verify_checksum: bool) -> Result<Arc<Block>> {
+        Self::verify_block_checksum(block_data_with_checksum, verify_checksum)?;
+        let block_len = block_data_with_checksum.len() - 4;
+        // decodes the block data and return it as an Arc reference
+        Ok(Arc::new(Block::decode(
+            &block_data_with_checksum[..block_len],
+        )))
+    }
+
+    /// verifies the checksum against the pre-calculated checksum
+    fn verify_block_checksum(block_data_with_checksum: &[u8], verify_checksum: bool) -> Result<()> {
+        if block_data_with_checksum.len() < 4 {
+            bail!(
+                "block of {} bytes is too short",
+                block_data_with_checksum.len()
+            );
+        }
+        let block_len = block_data_with_checksum.len() - 4;
+        let block_data = &block_data_with_checksum[..block_len];
+        if verify_checksum {
+            let checksum = (&block_data_with_checksum[block_len..]).get_u32();
+            if checksum != crc32fast::hash(block_data) {
+                bail!("block checksum mismatched!");
+            }
+        }
+        Ok(())
+    }
+
+    /// reads a block from the disk based on the given block index.
+    /// block_idx: index of the block to be read.
+    pub fn read_block(&self, block_idx: usize) -> Result<Arc<Block>> {
+        let (offset, offset_end) = self.block_range(block_idx)?;
+        self.read_block_at(offset, offset_end, &ReadOptions::default())
+    }
+
+    /// Read a block from the disk, with block cache.
+    /// block_idx: index of the block to be read.
+    pub fn read_block_cached(&self, block_idx: usize) -> Result<Arc<Block>> {
+        self.read_block_with_options(
+            block_idx,
+            &ReadOptions::default(),
+            &mut ReadaheadBuffer::default(),
+        )
+    }
+
+    /// Read a block as `options` ask: the block cache is always looked up, but only
+    /// filled with `fill_cache`, and never with blocks whose checksum was not verified,
+    /// as readers verifying checksums would then be served them. With readahead, blocks missing from the cache are served
+    /// from `readahead`, refilled with `readahead_bytes` of the following blocks at once.
+    /// With async I/O, the following blocks are instead read concurrently and kept decoded.
+    pub(crate) fn read_block_with_options(
+        &self,
+        block_idx: usize,
+        options: &ReadOptions,
+        readahead: &mut ReadaheadBuffer,
+    ) -> Result<Arc<Block>> {
+        let (offset, offset_end) = self.block_range(block_idx)?;
+        let mut read = || {
+            // a mapped file is read ahead by the page cache.
+            if self.file.is_mmap() {
+                return self.read_block_at(offset, offset_end, options);
+            }
+            if options.async_io {
+                return self.prefetch_blocks(block_idx, options, readahead);
+            }
+            if options.readahead_bytes == 0 {
+                return self.read_block_at(offset, offset_end, options);
+            }
+            let data_end = self
+                .index
+                .last()
+                .map_or(offset_end, |handle| handle.data_end);
+            let read_end = (offset + options.readahead_bytes).clamp(offset_end, data_end);
+            let raw =
+                readahead.read(&self.file, offset, offset_end, read_end, options.direct_io)?;
+            Self::decode_block(raw, options.verify_checksums)
+        };
+        let cached = match self.block_cache {
+            Some(ref block_cache) if options.fill_cache && options.verify_checksums => {
+                let key = (self.cache_id, offset);
+                block_cache.try_get_with(key, false, || Ok(CachedBlock::Data(read()?)))?
+            }
+            Some(ref block_cache) => match block_cache.get((self.cache_id, offset), false) {
+                Some(block) => block,
+                None => return read(),
+            },
+            None => return read(),
+        };
+        match cached {
+            CachedBlock::Data(block) => Ok(block),
+            _ => bail!("cached block at {} is not a data block", offset),
+        }
+    }
+
+    /// get block `block_idx` from `readahead`, reading it together with the blocks after it
+    /// when it was not prefetched. The window covers `readahead_bytes`, at least two blocks.
+    fn prefetch_blocks(
+        &self,
+        block_idx: usize,
+        options: &ReadOptions,
+        readahead: &mut ReadaheadBuffer,
+    ) -> Result<Arc<Block>> {
+        if let Some(block) = readahead.take_prefetched(block_idx) {
+            return Ok(block);
+        }
+        let window = (options.readahead_bytes / self.properties.block_size.max(1))
+            .clamp(2, MAX_PREFETCH_BLOCKS)
+            .min(self.num_blocks - block_idx);
+        let block_idxs = (block_idx..block_idx + window).collect::<Vec<_>>();
+        let blocks = self.read_blocks_uncached(&block_idxs, options)?;
+        readahead.prefetched = block_idxs.into_iter().zip(blocks).collect();
+        Ok(readahead.prefetched.pop_front().unwrap().1)
+    }
+
+    /// Read several blocks at once. Blocks missing from the block cache are read together,
+    /// concurrently with `async_io`, and inserted into the cache with `fill_cache` once
+    /// their checksum is verified.
+    pub fn read_blocks(
+        &self,
+        block_idxs: &[usize],
+        options: &ReadOptions,
+    ) -> Result<Vec<Arc<Block>>> {
+        let Some(ref block_cache) = self.block_cache else {
+            return self.read_blocks_uncached(block_idxs, options);
+        };
+        let mut blocks = Vec::with_capacity(block_idxs.len());
+        let mut missed = Vec::new();
+        for &block_idx in block_idxs {
+            let (offset, _) = self.block_range(block_idx)?;
+            match block_cache.get((self.cache_id, offset), false) {
+                Some(CachedBlock::Data(block)) => blocks.push(Some(block)),
+                Some(_) => bail!("cached block at {} is not a data block", offset),
+                None => {
+                    blocks.push(None);
+                    missed.push(block_idx);
+                }
+            }
+        }
+        let mut read = self.read_blocks_uncached(&missed, options)?.into_iter();
+        for (block, block_idx) in blocks
+            .iter_mut()
+            .filter(|block| block.is_none())
+            .zip(missed)
+        {
+            let read_block = read.next().unwrap();
+            if options.fill_cache && options.verify_checksums {
+                let (offset, _) = self.block_range(block_idx)?;
+                block_cache.insert(
+                    (self.cache_id, offset),
+                    false,
+                    CachedBlock::Data(read_block.clone()),
+                );
+            }
+            *block = Some(read_block);
+        }
+        Ok(blocks.into_iter().map(Option::unwrap).collect())
+    }
+
+    /// read the blocks from the file, bypassing the block cache.
+    fn read_blocks_uncached(
+        &self,
+        block_idxs: &[usize],
+        options: &ReadOptions,
+    ) -> Result<Vec<Arc<Block>>> {
+        let ranges = block_idxs
+            .iter()
+            .map(|&block_idx| self.block_range(block_idx))
+            .collect::<Result<Vec<_>>>()?;
+        if self.file.is_mmap() {
+            return ranges
+                .into_iter()
+                .map(|(offset, offset_end)| self.read_block_at(offset, offset_end, options))
+                .collect();
+        }
+        let reads = ranges
+            .iter()
+            .map(|&(offset, offset_end)| (offset as u64, (offset_end - offset) as u64))
+            .collect::<Vec<_>>();
+        self.file
+            .read_many(&reads, options.async_io, options.direct_io)?
+            .iter()
+            .map(|raw| Self::decode_block(raw, options.verify_checksums))
+            .collect()
+    }
+
+    /// Find the index of the block that many contain `Key`
+    /// key: the Key to search for, usize: the index of the block.
+    pub fn find_block_idx(&self, key: KeySlice) -> Result<usize> {
+        let partition_idx = self
+            .index
+            .partition_point(|handle| handle.first_key.as_key_slice() <= key)
+            .saturating_sub(1);
+        let block_meta = self.read_index_partition(partition_idx)?;
+        let local_idx = block_meta
+            .partition_point(|meta| meta.first_key.as_key_slice() <= key)
+            .saturating_sub(1);
+        Ok(self.index[partition_idx].first_block_idx + local_idx)
+    }
+
+    /// check the filter partition of the first partition that may hold `key`.
+    /// the versions of one key may spill over into the next partition,
+    /// but the first partition always has the key hashed in.
+    pub fn may_contain_key(&self, key: &[u8]) -> Result<bool> {
+        if self.index.is_empty() {
+            return Ok(true);
+        }
+        let partition_idx = self
+            .index
+            .partition_point(|handle| handle.last_key.key_ref() < key);
+        if partition_idx == self.index.len() {
+            return Ok(false);
+        }
+        self.partition_may_contain(partition_idx, farmhash::fingerprint32(key))
+    }
+
+    /// check the filter partitions of every partition that may hold a key starting
+    /// with `prefix` for `prefix_hash`.
+    pub fn may_contain_prefix(&self, prefix: &[u8], prefix_hash: u32) -> Result<bool> {
+        if self.index.is_empty() {
+            return Ok(true);
+        }
+        let start = self
+            .index
+            .partition_point(|handle| handle.last_key.key_ref() < prefix);
+        for (partition_idx, handle) in self.index.iter().enumerate().skip(start) {
+            let first_key = handle.first_key.key_ref();
+            if first_key > prefix && !first_key.starts_with(prefix) {
+                break;
+            }
+            if self.partition_may_contain(partition_idx, prefix_hash)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// check one filter partition, partitions written without a filter may contain anything.
+    fn partition_may_contain(&self, partition_idx: usize, hash: u32) -> Result<bool> {
+        if self.index[partition_idx].filter_len == 0 {
+            return Ok(true);
+        }
+        Ok(self.read_filter_partition(partition_idx)?.may_contain(hash))
+    }
+
+    /*-----------------------Accessor--------------------------- */
+    /// the user key each block starts with, used to cut compactions into sub-ranges.
+    pub(crate) fn block_first_keys(&self) -> Result<Vec<Bytes>> {
+        let mut first_keys = Vec::with_capacity(self.num_blocks);
+        for partition_idx in 0..self.index.len() {
+            let block_meta = self.read_index_partition(partition_idx)?;
+            first_keys.extend(
+                block_meta
+                    .iter()
+                    .map(|meta| meta.first_key.clone().into_inner()),
+            );
+        }
+        Ok(first_keys)
+    }
+
+    pub fn num_of_blocks(&self) -> usize {
+        self.num_blocks
+    }
+
+    pub fn properties(&self) -> &TableProperties {
+        &self.properties
     }
 }
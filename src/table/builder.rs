@@ -1,18 +1,28 @@
 #![allow(unused)]
 
 use crate::{
-    block::builder::BlockBuilder,
+    block::{builder::BlockBuilder, RESTART_INTERVAL},
+    block_cache::BlockCache,
     key::{Key, KeySlice, KeyVec},
-    lsm_storage::BlockCache,
 };
 use anyhow::Result;
 use bytes::BufMut;
 
-use super::{bloom::Bloom, BlockMeta, FileObject, SsTable};
+use super::{
+    BlockMeta, FileObject, Filter, FilterPolicy, Footer, PartitionHandle, PinnedCharge,
+    PrefixExtractor, SsTable, TableProperties, TableReader, FORMAT_VERSION,
+};
 use farmhash::FarmHasher;
-use std::{path::Path, sync::Arc};
+use parking_lot::RwLock;
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 /// Builds an SsTable from key-value pairs.
+/// Consecutive blocks are grouped into partitions of about `block_size` bytes of
+/// block metas, each partition gets its own index and filter block.
 pub struct SsTableBuilder {
     // Builder fields
     builder: BlockBuilder,
@@ -23,8 +33,21 @@ pub struct SsTableBuilder {
     data: Vec<u8>,
     // Metadata fields
     pub(crate) meta: Vec<BlockMeta>,
+    properties: TableProperties,
+    // Partition fields: the finished partitions as (first block index, key hashes),
+    // and the first block, hashes and meta size of the one being filled.
+    partitions: Vec<(usize, Vec<u32>)>,
+    partition_first_block: usize,
     key_hashes: Vec<u32>,
-    max_ts: u64,
+    partition_meta_size: usize,
+    // Prefix bloom fields
+    prefix_extractor: Option<PrefixExtractor>,
+    last_prefix_hash: Option<u32>,
+    // append a hash index to every data block.
+    block_hash_index: bool,
+    filter_policy: FilterPolicy,
+    // write the file with O_DIRECT.
+    direct_io: bool,
 }
 
 impl SsTableBuilder {
@@ -37,11 +60,51 @@ impl SsTableBuilder {
             last_key: KeyVec::new(),
             data: Vec::new(),
             meta: Vec::new(),
+            properties: TableProperties {
+                min_ts: u64::MAX,
+                ..Default::default()
+            },
+            partitions: Vec::new(),
+            partition_first_block: 0,
             key_hashes: Vec::new(),
-            max_ts: 0,
+            partition_meta_size: 0,
+            prefix_extractor: None,
+            last_prefix_hash: None,
+            block_hash_index: false,
+            filter_policy: FilterPolicy::default(),
+            direct_io: false,
         }
     }
 
+    /// choose how the filter partitions are built.
+    pub fn with_filter_policy(mut self, filter_policy: FilterPolicy) -> Self {
+        self.filter_policy = filter_policy;
+        self
+    }
+
+    /// write the SST with O_DIRECT, so it does not go through the page cache.
+    pub fn with_direct_io(mut self, direct_io: bool) -> Self {
+        self.direct_io = direct_io;
+        self
+    }
+
+    /// also build an in-block hash index for point lookups.
+    pub fn with_block_hash_index(mut self, block_hash_index: bool) -> Self {
+        self.block_hash_index = block_hash_index;
+        self.builder = self.new_block_builder();
+        self
+    }
+
+    fn new_block_builder(&self) -> BlockBuilder {
+        BlockBuilder::new(self.block_size).with_hash_index(self.block_hash_index)
+    }
+
+    /// also hash the prefix of every key into the bloom filter.
+    pub fn with_prefix_extractor(mut self, prefix_extractor: Option<PrefixExtractor>) -> Self {
+        self.prefix_extractor = prefix_extractor;
+        self
+    }
+
     /*-----------Executors(core functional API)--------------*/
 
     /// adds a Key-value pair to the SsTable
@@ -50,18 +113,35 @@ impl SsTableBuilder {
             self.first_key.set_from_slice(key);
         }
 
-        self.key_hashes.push(farmhash::fingerprint32(key.key_ref()));
-
-        if self.builder.add(key, value) {
-            self.last_key.set_from_slice(key);
-            return;
+        if !self.builder.add(key, value) {
+            self.finish_block();
+            assert!(self.builder.add(key, value));
+            self.first_key.set_from_slice(key);
         }
-
-        self.finish_block();
-
-        assert!(self.builder.add(key, value));
-        self.first_key.set_from_slice(key);
         self.last_key.set_from_slice(key);
+        self.properties.num_entries += 1;
+        if value.is_empty() {
+            self.properties.num_deletions += 1;
+        }
+        self.properties.raw_key_size += key.raw_len() as u64;
+        self.properties.raw_value_size += value.len() as u64;
+        self.properties.min_ts = self.properties.min_ts.min(key.ts());
+        self.properties.max_ts = self.properties.max_ts.max(key.ts());
+
+        // hash after the block is settled, so the key lands in its partition's filter.
+        self.key_hashes.push(farmhash::fingerprint32(key.key_ref()));
+        if let Some(prefix) = self
+            .prefix_extractor
+            .as_ref()
+            .and_then(|extractor| extractor.extract(key.key_ref()))
+        {
+            // keys arrive sorted, so every prefix only needs to be hashed once in a row.
+            let prefix_hash = farmhash::fingerprint32(prefix);
+            if self.last_prefix_hash != Some(prefix_hash) {
+                self.key_hashes.push(prefix_hash);
+                self.last_prefix_hash = Some(prefix_hash);
+            }
+        }
     }
 
     /// builds the SSTable and writes it to the given path
@@ -72,29 +152,74 @@ impl SsTableBuilder {
         path: impl AsRef<Path>,
     ) -> Result<SsTable> {
         self.finish_block();
+        self.finish_partition();
         let mut buf = self.data;
-        let meta_offset = buf.len();
-        BlockMeta::encode_block_meta(&self.meta, self.max_ts, &mut buf);
-        buf.put_u32(meta_offset as u32);
-        let bloom = Bloom::build_from_key_hashes(
-            &self.key_hashes,
-            Bloom::bloom_bits_per_key(self.key_hashes.len(), 0.01),
-        );
-        let bloom_offset = buf.len();
-        bloom.encode(&mut buf);
-        buf.put_u32(bloom_offset as u32);
-        let file = FileObject::create(path.as_ref(), buf)?;
-        Ok(SsTable {
-            id,
+        let data_len = buf.len();
+        let mut index = Vec::with_capacity(self.partitions.len());
+        for (i, (first_block_idx, key_hashes)) in self.partitions.iter().enumerate() {
+            let end_block_idx = self
+                .partitions
+                .get(i + 1)
+                .map_or(self.meta.len(), |(idx, _)| *idx);
+            let block_meta = &self.meta[*first_block_idx..end_block_idx];
+            let index_offset = buf.len();
+            BlockMeta::encode_block_meta(block_meta, &mut buf);
+            let filter_offset = buf.len();
+            if let Some(filter) = Filter::build(&self.filter_policy, key_hashes) {
+                filter.encode(&mut buf);
+            }
+            index.push(PartitionHandle {
+                first_block_idx: *first_block_idx,
+                data_end: self.meta.get(end_block_idx).map_or(data_len, |x| x.offset),
+                index_offset,
+                index_len: filter_offset - index_offset,
+                filter_offset,
+                filter_len: buf.len() - filter_offset,
+                first_key: block_meta.first().unwrap().first_key.clone(),
+                last_key: block_meta.last().unwrap().last_key.clone(),
+            });
+        }
+        let index_offset = buf.len();
+        PartitionHandle::encode_top_index(&index, self.meta.len(), &mut buf);
+        let properties = TableProperties {
+            creation_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |x| x.as_secs()),
+            compression: "none".to_string(),
+            block_size: self.block_size,
+            restart_interval: RESTART_INTERVAL,
+            block_hash_index: self.block_hash_index,
+            prefix_extractor: self.prefix_extractor.clone(),
+            filter_policy: self.filter_policy.clone(),
+            ..self.properties
+        };
+        let properties_offset = buf.len();
+        properties.encode(&mut buf);
+        let footer = Footer {
+            index_offset: index_offset as u64,
+            index_len: (properties_offset - index_offset) as u64,
+            properties_offset: properties_offset as u64,
+            properties_len: (buf.len() - properties_offset) as u64,
+            format_version: FORMAT_VERSION,
+        };
+        footer.encode(&mut buf);
+        let file = if self.direct_io {
+            FileObject::create_direct(path.as_ref(), buf)?
+        } else {
+            FileObject::create(path.as_ref(), buf)?
+        };
+        let reader = TableReader {
             file,
-            first_key: self.meta.first().unwrap().first_key.clone(),
-            last_key: self.meta.last().unwrap().last_key.clone(),
-            block_meta: self.meta,
-            block_meta_offset: meta_offset,
+            index,
+            num_blocks: self.meta.len(),
+            properties,
+            cache_id: block_cache.as_ref().map_or(0, |cache| cache.new_table_id()),
             block_cache,
-            bloom: Some(bloom),
-            max_ts: self.max_ts,
-        })
+            cache_meta_blocks: true,
+            pinned: RwLock::new(None),
+            pinned_charge: PinnedCharge::default(),
+        };
+        Ok(SsTable::from_reader(id, reader))
     }
 
     /*-----------------Accessor------------------*/
@@ -110,7 +235,8 @@ impl SsTableBuilder {
 
     /// Fanalize the current block being built
     fn finish_block(&mut self) {
-        let builder = std::mem::replace(&mut self.builder, BlockBuilder::new(self.block_size));
+        let new_builder = self.new_block_builder();
+        let builder = std::mem::replace(&mut self.builder, new_builder);
         let encoded_block = builder.build().encode();
         self.meta.push(BlockMeta {
             offset: self.data.len(),
@@ -120,6 +246,24 @@ impl SsTableBuilder {
         let checksum = crc32fast::hash(&encoded_block);
         self.data.extend(encoded_block);
         self.data.put_u32(checksum);
+
+        self.partition_meta_size += self.meta.last().unwrap().encoded_size();
+        if self.partition_meta_size >= self.block_size {
+            self.finish_partition();
+        }
+    }
+
+    /// close the partition being filled, the next block starts a new one.
+    fn finish_partition(&mut self) {
+        if self.meta.len() == self.partition_first_block {
+            return;
+        }
+        let key_hashes = std::mem::take(&mut self.key_hashes);
+        self.partitions
+            .push((self.partition_first_block, key_hashes));
+        self.partition_first_block = self.meta.len();
+        self.partition_meta_size = 0;
+        self.last_prefix_hash = None;
     }
 
     #[cfg(test)]
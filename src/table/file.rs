@@ -0,0 +1,238 @@
+use std::{
+    alloc::Layout,
+    fs::File,
+    io::Write,
+    os::unix::fs::{FileExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{bail, Result};
+use bytes::Bytes;
+
+use super::{mmap::Mmap, uring};
+
+/// offsets, lengths and buffers of direct I/O are aligned to this.
+const DIRECT_IO_ALIGNMENT: usize = 4096;
+
+/// A file object, optionally read through a memory map of the whole file.
+pub struct FileObject {
+    file: Option<File>,
+    size: u64,
+    path: Option<PathBuf>,
+    // the whole file, borrowing the mapping.
+    mmap: Option<Bytes>,
+    // a second handle bypassing the page cache, `None` when the file system refuses O_DIRECT.
+    direct: OnceLock<Option<File>>,
+}
+
+impl FileObject {
+    /// open the file lies in the Given Path and return the File object
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::options().read(true).write(false).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self::new(Some(file), size, Some(path)))
+    }
+
+    /// open the file and map it into memory, blocks are then read without copying.
+    pub fn open_mmap(path: &Path) -> Result<Self> {
+        Self::open(path)?.into_mmap()
+    }
+
+    /// a file object without a file underneath, only its size is known.
+    pub(crate) fn meta_only(size: u64) -> Self {
+        Self::new(None, size, None)
+    }
+
+    fn new(file: Option<File>, size: u64, path: Option<&Path>) -> Self {
+        Self {
+            file,
+            size,
+            path: path.map(Path::to_path_buf),
+            mmap: None,
+            direct: OnceLock::new(),
+        }
+    }
+
+    /// Write given data to the path
+    pub fn create(path: &Path, data: Vec<u8>) -> Result<Self> {
+        std::fs::write(path, &data)?;
+        File::open(path)?.sync_all()?;
+        Self::open(path)
+    }
+
+    /// Write given data to the path with O_DIRECT, so that writing it does not evict
+    /// anything from the page cache. Falls back to `create` where O_DIRECT is not supported.
+    pub fn create_direct(path: &Path, data: Vec<u8>) -> Result<Self> {
+        let file = match File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(path)
+        {
+            Ok(file) => file,
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => return Self::create(path, data),
+            Err(e) => return Err(e.into()),
+        };
+        let mut buf = AlignedBuf::new(data.len().next_multiple_of(DIRECT_IO_ALIGNMENT));
+        buf[..data.len()].copy_from_slice(&data);
+        (&file).write_all(&buf)?;
+        // drop the padding written to keep the last write aligned.
+        file.set_len(data.len() as u64)?;
+        file.sync_all()?;
+        Self::open(path)
+    }
+
+    /// map the opened file into memory.
+    pub fn into_mmap(mut self) -> Result<Self> {
+        let Some(file) = self.file.as_ref() else {
+            bail!("no file to map");
+        };
+        self.mmap = Some(Bytes::from_owner(Mmap::map(file, self.size as usize)?));
+        Ok(self)
+    }
+
+    pub fn is_mmap(&self) -> bool {
+        self.mmap.is_some()
+    }
+
+    /// borrow `len` bytes from `offset` of the mapping, `None` when the file is not mapped.
+    pub(crate) fn read_mapped(&self, offset: u64, len: u64) -> Result<Option<Bytes>> {
+        let Some(map) = self.mmap.as_ref() else {
+            return Ok(None);
+        };
+        match offset.checked_add(len) {
+            Some(end) if end <= map.len() as u64 => {
+                Ok(Some(map.slice(offset as usize..end as usize)))
+            }
+            _ => bail!(
+                "read of {} bytes at {} is out of the mapped file of {} bytes",
+                len,
+                offset,
+                map.len()
+            ),
+        }
+    }
+
+    // Executor
+    /// read the file from: `offset`,  read `len` bytes.
+    pub fn read(&self, offset: u64, len: u64) -> Result<Vec<u8>> {
+        if let Some(data) = self.read_mapped(offset, len)? {
+            return Ok(data.to_vec());
+        }
+        let mut data = vec![0; len as usize];
+        self.file
+            .as_ref()
+            .unwrap()
+            .read_exact_at(&mut data[..], offset)?;
+        Ok(data)
+    }
+
+    /// `read`, bypassing the page cache with `direct`.
+    pub fn read_with(&self, offset: u64, len: u64, direct: bool) -> Result<Vec<u8>> {
+        match self.direct_file(direct) {
+            Some(file) => read_direct(file, offset, len),
+            None => self.read(offset, len),
+        }
+    }
+
+    /// read all `(offset, len)` ranges, concurrently through io_uring when `async_io`
+    /// is set and io_uring works on this system, one after another otherwise.
+    pub fn read_many(
+        &self,
+        reads: &[(u64, u64)],
+        async_io: bool,
+        direct: bool,
+    ) -> Result<Vec<Vec<u8>>> {
+        // direct reads need aligned buffers, they keep the synchronous path.
+        if async_io && !self.is_mmap() && self.direct_file(direct).is_none() {
+            if let Some(result) = uring::read_many(self.file.as_ref().unwrap(), reads) {
+                return result;
+            }
+        }
+        reads
+            .iter()
+            .map(|&(offset, len)| self.read_with(offset, len, direct))
+            .collect()
+    }
+
+    /// the O_DIRECT handle when `direct` reads are asked for and possible.
+    fn direct_file(&self, direct: bool) -> Option<&File> {
+        if !direct || self.is_mmap() {
+            return None;
+        }
+        self.direct
+            .get_or_init(|| {
+                File::options()
+                    .read(true)
+                    .custom_flags(libc::O_DIRECT)
+                    .open(self.path.as_ref()?)
+                    .ok()
+            })
+            .as_ref()
+    }
+
+    // Accessor
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// read `[offset, offset + len)` with O_DIRECT: the aligned range around it is read
+/// into an aligned buffer, then the requested bytes are copied out.
+fn read_direct(file: &File, offset: u64, len: u64) -> Result<Vec<u8>> {
+    let alignment = DIRECT_IO_ALIGNMENT as u64;
+    let start = offset / alignment * alignment;
+    let end = (offset + len).next_multiple_of(alignment);
+    let mut buf = AlignedBuf::new((end - start) as usize);
+    let wanted = (offset + len - start) as usize;
+    let mut read = 0;
+    while read < wanted {
+        // the last read stops short at the end of the file.
+        let n = file.read_at(&mut buf[read..], start + read as u64)?;
+        if n == 0 {
+            bail!("unexpected end of file in direct read");
+        }
+        read += n;
+    }
+    Ok(buf[(offset - start) as usize..wanted].to_vec())
+}
+
+/// a zeroed buffer aligned for direct I/O.
+struct AlignedBuf {
+    ptr: *mut u8,
+    layout: Layout,
+}
+
+impl AlignedBuf {
+    fn new(len: usize) -> Self {
+        let layout = Layout::from_size_align(len.max(DIRECT_IO_ALIGNMENT), DIRECT_IO_ALIGNMENT)
+            .expect("valid direct I/O buffer layout");
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout) };
+        if ptr.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+        Self { ptr, layout }
+    }
+}
+
+impl std::ops::Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.layout.size()) }
+    }
+}
+
+impl std::ops::DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.ptr, self.layout) }
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::{bail, Result};
+use bytes::BufMut;
+use serde::{Deserialize, Serialize};
+
+use super::{bloom::Bloom, xor::Xor8};
+
+const FILTER_TYPE_BLOOM: u8 = 1;
+const FILTER_TYPE_XOR8: u8 = 2;
+
+/// How the filters of an SST are built.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterPolicy {
+    /// no filter, every lookup in the key range reads the SST.
+    Disabled,
+    /// standard bloom filter, 10 bits per key give about 1% false positives.
+    Bloom { bits_per_key: usize },
+    /// xor filter: about 9.84 bits per key for 0.39% false positives,
+    /// a bloom filter needs about 11.5 bits per key for that rate.
+    Xor8,
+}
+
+impl Default for FilterPolicy {
+    fn default() -> Self {
+        FilterPolicy::Bloom { bits_per_key: 10 }
+    }
+}
+
+/// A filter partition of an SST, any type the policies can produce.
+pub enum Filter {
+    Bloom(Bloom),
+    Xor8(Xor8),
+}
+
+impl Filter {
+    /// build the filter `policy` asks for, `None` when filters are disabled.
+    pub fn build(policy: &FilterPolicy, key_hashes: &[u32]) -> Option<Self> {
+        match policy {
+            FilterPolicy::Disabled => None,
+            FilterPolicy::Bloom { bits_per_key } => Some(Filter::Bloom(
+                Bloom::build_from_key_hashes(key_hashes, *bits_per_key),
+            )),
+            FilterPolicy::Xor8 => Some(Filter::Xor8(Xor8::build_from_key_hashes(key_hashes))),
+        }
+    }
+
+    /// Filter = filter type + the filter's own encoding.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Filter::Bloom(bloom) => {
+                buf.put_u8(FILTER_TYPE_BLOOM);
+                bloom.encode(buf);
+            }
+            Filter::Xor8(xor) => {
+                buf.put_u8(FILTER_TYPE_XOR8);
+                xor.encode(buf);
+            }
+        }
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        match buf.first() {
+            Some(&FILTER_TYPE_BLOOM) => Ok(Filter::Bloom(Bloom::decode(&buf[1..])?)),
+            Some(&FILTER_TYPE_XOR8) => Ok(Filter::Xor8(Xor8::decode(&buf[1..])?)),
+            Some(filter_type) => bail!("unknown filter type {}", filter_type),
+            None => bail!("empty filter block"),
+        }
+    }
+
+    /// the bytes the filter takes in memory.
+    pub fn size(&self) -> usize {
+        match self {
+            Filter::Bloom(bloom) => bloom.filter.len(),
+            Filter::Xor8(xor) => xor.size(),
+        }
+    }
+
+    pub fn may_contain(&self, h: u32) -> bool {
+        match self {
+            Filter::Bloom(bloom) => bloom.may_contain(h),
+            Filter::Xor8(xor) => xor.may_contain(h),
+        }
+    }
+}
@@ -1,67 +1,114 @@
-use crate::{block::iterator::BlockIterator, iterators::StorageIterator, key::KeySlice};
+use crate::{
+    block::iterator::BlockIterator, iterators::StorageIterator, key::KeySlice,
+    lsm_storage::ReadOptions,
+};
 use anyhow::{Ok, Result};
 use std::sync::Arc;
 
-use super::SsTable;
+use super::{ReadaheadBuffer, SsTable, TableReader};
 
-// An iterator over the contents of an SSTable
+// An iterator over the contents of an SSTable, holding its reader open.
 pub struct SsTableIterator {
-    table: Arc<SsTable>,
+    table: Arc<TableReader>,
     block_iter: BlockIterator,
     block_idx: usize,
+    options: ReadOptions,
+    readahead: ReadaheadBuffer,
 }
 
 impl SsTableIterator {
     pub fn create_and_seek_to_first(table: Arc<SsTable>) -> Result<Self> {
-        let (block_idx, block_iter) = Self::seek_to_first_inner(&table)?;
+        Self::create_and_seek_to_first_with_options(table, ReadOptions::default())
+    }
+
+    /// `create_and_seek_to_first` reading the blocks as `options` ask.
+    pub fn create_and_seek_to_first_with_options(
+        table: Arc<SsTable>,
+        options: ReadOptions,
+    ) -> Result<Self> {
+        let table = table.reader()?;
+        let mut readahead = ReadaheadBuffer::default();
+        let (block_idx, block_iter) = Self::seek_to_first_inner(&table, &options, &mut readahead)?;
         let iter = Self {
             block_iter,
             block_idx,
             table,
+            options,
+            readahead,
         };
         Ok(iter)
     }
 
     pub fn seek_to_first(&mut self) -> Result<()> {
-        let (blk_idx, blk_iter) = Self::seek_to_first_inner(&self.table)?;
+        let (blk_idx, blk_iter) =
+            Self::seek_to_first_inner(&self.table, &self.options, &mut self.readahead)?;
         self.block_idx = blk_idx;
         self.block_iter = blk_iter;
         Ok(())
     }
 
-    fn seek_to_first_inner(table: &Arc<SsTable>) -> Result<(usize, BlockIterator)> {
+    fn seek_to_first_inner(
+        table: &TableReader,
+        options: &ReadOptions,
+        readahead: &mut ReadaheadBuffer,
+    ) -> Result<(usize, BlockIterator)> {
         Ok((
             0,
-            BlockIterator::create_and_seek_to_first(table.read_block_cached(0)?),
+            BlockIterator::create_and_seek_to_first(
+                table.read_block_with_options(0, options, readahead)?,
+            ),
         ))
     }
 
     pub fn create_and_seek_to_key(table: Arc<SsTable>, key: KeySlice) -> Result<Self> {
-        let (block_idx, block_iter) = Self::seek_to_key_inner(&table, key)?;
+        Self::create_and_seek_to_key_with_options(table, key, ReadOptions::default())
+    }
+
+    /// `create_and_seek_to_key` reading the blocks as `options` ask.
+    pub fn create_and_seek_to_key_with_options(
+        table: Arc<SsTable>,
+        key: KeySlice,
+        options: ReadOptions,
+    ) -> Result<Self> {
+        let table = table.reader()?;
+        let mut readahead = ReadaheadBuffer::default();
+        let (block_idx, block_iter) =
+            Self::seek_to_key_inner(&table, key, &options, &mut readahead)?;
         let iter = Self {
             block_idx,
             block_iter,
             table,
+            options,
+            readahead,
         };
         Ok(iter)
     }
 
     pub fn seek_to_key(&mut self, key: KeySlice) -> Result<()> {
-        let (block_idx, block_iter) = Self::seek_to_key_inner(&self.table, key)?;
+        let (block_idx, block_iter) =
+            Self::seek_to_key_inner(&self.table, key, &self.options, &mut self.readahead)?;
         self.block_iter = block_iter;
         self.block_idx = block_idx;
         Ok(())
     }
 
-    fn seek_to_key_inner(table: &Arc<SsTable>, key: KeySlice) -> Result<(usize, BlockIterator)> {
-        let mut block_index = table.find_block_idx(key);
-        let mut block_iter =
-            BlockIterator::create_and_seek_to_key(table.read_block_cached(block_index)?, key);
+    fn seek_to_key_inner(
+        table: &TableReader,
+        key: KeySlice,
+        options: &ReadOptions,
+        readahead: &mut ReadaheadBuffer,
+    ) -> Result<(usize, BlockIterator)> {
+        let mut block_index = table.find_block_idx(key)?;
+        let mut block_iter = BlockIterator::create_and_seek_to_key(
+            table.read_block_with_options(block_index, options, readahead)?,
+            key,
+        );
         if !block_iter.is_valid() {
             block_index += 1;
             if block_index < table.num_of_blocks() {
-                block_iter =
-                    BlockIterator::create_and_seek_to_first(table.read_block_cached(block_index)?);
+                block_iter = BlockIterator::create_and_seek_to_first(
+                    table.read_block_with_options(block_index, options, readahead)?,
+                );
             }
         }
         Ok((block_index, block_iter))
@@ -75,7 +122,7 @@ impl StorageIterator for SsTableIterator {
         self.block_iter.value()
     }
 
-    fn key(&self) -> KeySlice {
+    fn key(&self) -> KeySlice<'_> {
         self.block_iter.key()
     }
 
@@ -88,9 +135,12 @@ impl StorageIterator for SsTableIterator {
         if !self.block_iter.is_valid() {
             self.block_idx += 1;
             if self.block_idx < self.table.num_of_blocks() {
-                self.block_iter = BlockIterator::create_and_seek_to_first(
-                    self.table.read_block_cached(self.block_idx)?,
-                );
+                self.block_iter =
+                    BlockIterator::create_and_seek_to_first(self.table.read_block_with_options(
+                        self.block_idx,
+                        &self.options,
+                        &mut self.readahead,
+                    )?);
             }
         }
         Ok(())
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// Maps a user key to the prefix hashed into the SST bloom filters,
+/// so that a scan over one prefix can skip the SSTs that do not contain it.
+/// The extractor is recorded in the SST properties, SSTs built with another
+/// extractor are never pruned.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrefixExtractor {
+    /// the first `n` bytes, keys shorter than that have no prefix.
+    FixedLength(usize),
+    /// everything up to and including the `count`-th `delimiter`,
+    /// e.g. `Delimited { delimiter: b'/', count: 1 }` maps `tenant/entity/1` to `tenant/`.
+    /// keys with fewer delimiters have no prefix.
+    Delimited { delimiter: u8, count: usize },
+}
+
+impl PrefixExtractor {
+    /// get the prefix of a user key, `None` when the key is out of the extractor's domain.
+    pub fn extract<'a>(&self, key: &'a [u8]) -> Option<&'a [u8]> {
+        match *self {
+            PrefixExtractor::FixedLength(len) => key.get(..len),
+            PrefixExtractor::Delimited { delimiter, count } => {
+                if count == 0 {
+                    return None;
+                }
+                key.iter()
+                    .enumerate()
+                    .filter(|(_, byte)| **byte == delimiter)
+                    .nth(count - 1)
+                    .map(|(idx, _)| &key[..=idx])
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use anyhow::{bail, Result};
+use bytes::{Buf, BufMut};
+use serde::{Deserialize, Serialize};
+
+use super::{FilterPolicy, PrefixExtractor};
+
+/// Statistics and build settings of one SST, stored in its properties block
+/// and kept in memory while the table is open.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableProperties {
+    pub num_entries: u64,
+    /// entries with an empty value.
+    pub num_deletions: u64,
+    /// sizes of all keys (timestamps included) and values before encoding.
+    pub raw_key_size: u64,
+    pub raw_value_size: u64,
+    pub min_ts: u64,
+    pub max_ts: u64,
+    /// seconds since the unix epoch.
+    pub creation_time: u64,
+    pub compression: String,
+    // builder options
+    pub block_size: usize,
+    pub restart_interval: usize,
+    pub block_hash_index: bool,
+    pub prefix_extractor: Option<PrefixExtractor>,
+    pub filter_policy: FilterPolicy,
+}
+
+impl TableProperties {
+    /// properties block = json + checksum.
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        let json = serde_json::to_vec(self).expect("table properties are serializable");
+        buf.put_slice(&json);
+        buf.put_u32(crc32fast::hash(&json));
+    }
+
+    pub(crate) fn decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 4 {
+            bail!("properties block too short: {} bytes", buf.len());
+        }
+        let (json, mut checksum) = buf.split_at(buf.len() - 4);
+        if checksum.get_u32() != crc32fast::hash(json) {
+            bail!("properties checksum mismatched");
+        }
+        Ok(serde_json::from_slice(json)?)
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    key::{KeySlice, TS_DEFAULT},
+    lsm_storage::LsmStorageOptions,
+};
+
+use super::SsTableBuilder;
+
+/// SstFileWriter writes sorted entries to an SST outside of any storage, to attach it
+/// to one later with `MiniLsm::ingest_external_files`. Keys must be added in strictly
+/// increasing order, their timestamp is assigned on ingestion.
+pub struct SstFileWriter {
+    builder: SsTableBuilder,
+    path: PathBuf,
+    last_key: Option<Vec<u8>>,
+}
+
+impl SstFileWriter {
+    pub fn create(path: impl AsRef<Path>, options: &LsmStorageOptions) -> Self {
+        Self {
+            builder: SsTableBuilder::new(options.block_size)
+                .with_filter_policy(options.filter_policy.clone()),
+            path: path.as_ref().to_path_buf(),
+            last_key: None,
+        }
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        if value.is_empty() {
+            bail!("value cannot be empty");
+        }
+        self.add(key, value)
+    }
+
+    /// write a tombstone of `key`, deleting it from the storage the file is ingested into.
+    pub fn delete(&mut self, key: &[u8]) -> Result<()> {
+        self.add(key, b"")
+    }
+
+    fn add(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        if key.is_empty() {
+            bail!("key cannot be empty");
+        }
+        if self.last_key.as_deref().is_some_and(|last| last >= key) {
+            bail!("keys must be added in increasing order");
+        }
+        self.builder
+            .add(KeySlice::from_slice(key, TS_DEFAULT), value);
+        self.last_key = Some(key.to_vec());
+        Ok(())
+    }
+
+    /// write the file, which must hold at least one entry.
+    pub fn finish(self) -> Result<()> {
+        if self.last_key.is_none() {
+            bail!("cannot write an empty SST");
+        }
+        self.builder.build(0, None, &self.path)?;
+        Ok(())
+    }
+}
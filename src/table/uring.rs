@@ -0,0 +1,145 @@
+//! Batched reads through io_uring: submit a batch of reads, wait for all of them.
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io,
+    os::unix::{fs::FileExt, io::AsRawFd},
+};
+
+use anyhow::Result;
+use io_uring::{opcode, types, IoUring};
+
+const QUEUE_DEPTH: u32 = 64;
+// marks the user data of a cancellation, the low bits hold the read it cancels.
+const CANCEL_TAG: u64 = 1 << 63;
+
+thread_local! {
+    // one ring per thread, `None` when io_uring is not available on this system,
+    // or once the ring of this thread failed.
+    static RING: RefCell<Option<IoUring>> = RefCell::new(IoUring::new(QUEUE_DEPTH).ok());
+}
+
+enum BatchError {
+    // a read failed, the ring is still usable.
+    Read(io::Error),
+    // waiting on the ring failed, the reads in flight were cancelled.
+    Ring(io::Error),
+}
+
+/// read `(offset, len)` ranges of `file` concurrently through io_uring,
+/// `None` when io_uring is not available on this thread.
+pub(crate) fn read_many(file: &File, reads: &[(u64, u64)]) -> Option<Result<Vec<Vec<u8>>>> {
+    RING.with(|slot| {
+        let mut slot = slot.borrow_mut();
+        let ring = slot.as_mut()?;
+        let mut bufs = reads
+            .iter()
+            .map(|&(_, len)| vec![0; len as usize])
+            .collect::<Vec<_>>();
+        match read_batch(ring, file, reads, &mut bufs) {
+            Ok(()) => Some(Ok(bufs)),
+            Err(BatchError::Read(e)) => Some(Err(e.into())),
+            // drop the ring, this and later reads of the thread go through pread.
+            Err(BatchError::Ring(_)) => {
+                *slot = None;
+                None
+            }
+        }
+    })
+}
+
+/// read every range of `reads` into the buffer at the same index, at most the queue depth
+/// at a time. A failed read is reported once every read of its chunk completed, so the
+/// kernel never writes into a buffer that was given back.
+fn read_batch(
+    ring: &mut IoUring,
+    file: &File,
+    reads: &[(u64, u64)],
+    bufs: &mut [Vec<u8>],
+) -> Result<(), BatchError> {
+    let fd = types::Fd(file.as_raw_fd());
+    let depth = ring.params().sq_entries() as usize;
+    for (chunk_reads, chunk_bufs) in reads.chunks(depth).zip(bufs.chunks_mut(depth)) {
+        for (i, (&(offset, _), buf)) in chunk_reads.iter().zip(chunk_bufs.iter_mut()).enumerate() {
+            let entry = opcode::Read::new(fd, buf.as_mut_ptr(), buf.len() as u32)
+                .offset(offset)
+                .build()
+                .user_data(i as u64);
+            // SAFETY: the buffer outlives the read, every read of the chunk completes
+            // before the buffers are touched again.
+            unsafe { ring.submission().push(&entry) }
+                .expect("a chunk fits in the submission queue");
+        }
+        let mut in_flight = vec![true; chunk_reads.len()];
+        let mut completed = 0;
+        let mut failed = None;
+        while completed < chunk_reads.len() {
+            if let Err(e) = ring.submit_and_wait(1) {
+                match e.raw_os_error() {
+                    Some(libc::EINTR | libc::EAGAIN | libc::EBUSY) => {}
+                    _ => {
+                        cancel_in_flight(ring, &mut in_flight, chunk_bufs);
+                        return Err(BatchError::Ring(e));
+                    }
+                }
+            }
+            for cqe in ring.completion() {
+                completed += 1;
+                let i = cqe.user_data() as usize;
+                in_flight[i] = false;
+                let res = cqe.result();
+                if res < 0 {
+                    failed.get_or_insert(io::Error::from_raw_os_error(-res));
+                    continue;
+                }
+                // finish a short read synchronously.
+                let (res, buf) = (res as usize, &mut chunk_bufs[i]);
+                if res < buf.len() {
+                    if let Err(e) =
+                        file.read_exact_at(&mut buf[res..], chunk_reads[i].0 + res as u64)
+                    {
+                        failed.get_or_insert(e);
+                    }
+                }
+            }
+        }
+        if let Some(e) = failed {
+            return Err(BatchError::Read(e));
+        }
+    }
+    Ok(())
+}
+
+/// cancel the reads still in flight after waiting on the ring failed, and wait for them.
+/// The buffers of reads that could not be waited for are leaked, the kernel may still
+/// write into them.
+fn cancel_in_flight(ring: &mut IoUring, in_flight: &mut [bool], bufs: &mut [Vec<u8>]) {
+    for i in (0..in_flight.len()).filter(|&i| in_flight[i]) {
+        let entry = opcode::AsyncCancel::new(i as u64)
+            .build()
+            .user_data(CANCEL_TAG | i as u64);
+        // SAFETY: a cancellation references no memory.
+        if unsafe { ring.submission().push(&entry) }.is_err() {
+            break;
+        }
+    }
+    let mut failed_waits = 0;
+    while in_flight.contains(&true) && failed_waits < 16 {
+        if ring.submit_and_wait(1).is_err() {
+            failed_waits += 1;
+        }
+        for cqe in ring.completion() {
+            if cqe.user_data() & CANCEL_TAG == 0 {
+                in_flight[cqe.user_data() as usize] = false;
+            }
+        }
+    }
+    for (buf, _) in bufs
+        .iter_mut()
+        .zip(in_flight)
+        .filter(|(_, in_flight)| **in_flight)
+    {
+        std::mem::forget(std::mem::take(buf));
+    }
+}
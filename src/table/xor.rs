@@ -0,0 +1,155 @@
+use anyhow::{bail, Result};
+use bytes::{Buf, BufMut};
+
+/// Implements a xor filter with 8-bit fingerprints (Graf and Lemire, 2020).
+/// Every key maps to three slots whose fingerprints xor to the key's fingerprint,
+/// which takes about 1.23 * 8 bits per key for a false positive rate of 1/256.
+pub struct Xor8 {
+    seed: u64,
+    block_length: u32,
+    fingerprints: Vec<u8>,
+}
+
+fn murmur64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    h
+}
+
+/// map a 32-bit hash onto `[0, n)` without a division.
+fn reduce(hash: u32, n: u32) -> u32 {
+    ((hash as u64 * n as u64) >> 32) as u32
+}
+
+fn fingerprint(hash: u64) -> u8 {
+    (hash ^ (hash >> 32)) as u8
+}
+
+impl Xor8 {
+    fn hash(seed: u64, key_hash: u32) -> u64 {
+        murmur64((key_hash as u64).wrapping_add(seed))
+    }
+
+    /// the three slots of a hash, one in each third of the fingerprints.
+    fn slots(block_length: u32, hash: u64) -> [usize; 3] {
+        [
+            reduce(hash as u32, block_length) as usize,
+            (reduce(hash.rotate_left(21) as u32, block_length) + block_length) as usize,
+            (reduce(hash.rotate_left(42) as u32, block_length) + 2 * block_length) as usize,
+        ]
+    }
+
+    /// Build xor filter from key hashes
+    pub fn build_from_key_hashes(keys: &[u32]) -> Self {
+        // peeling never finishes with duplicated keys.
+        let mut keys = keys.to_vec();
+        keys.sort_unstable();
+        keys.dedup();
+        let block_length = ((32 + (keys.len() as f64 * 1.23).ceil() as usize) / 3) as u32;
+        let capacity = block_length as usize * 3;
+        let mut seed = 0x9e37_79b9_7f4a_7c15;
+        loop {
+            if let Some(fingerprints) = Self::try_build(&keys, seed, block_length, capacity) {
+                return Self {
+                    seed,
+                    block_length,
+                    fingerprints,
+                };
+            }
+            seed = murmur64(seed.wrapping_add(1));
+        }
+    }
+
+    /// peel the keys off the slots they are alone in, then assign the fingerprints
+    /// in reverse order. fails when the keys cannot all be peeled with this seed.
+    fn try_build(keys: &[u32], seed: u64, block_length: u32, capacity: usize) -> Option<Vec<u8>> {
+        // per slot: xor of the hashes mapped to it and their count.
+        let mut slots = vec![(0u64, 0u32); capacity];
+        for key in keys {
+            let hash = Self::hash(seed, *key);
+            for slot in Self::slots(block_length, hash) {
+                slots[slot].0 ^= hash;
+                slots[slot].1 += 1;
+            }
+        }
+        let mut queue = (0..capacity)
+            .filter(|slot| slots[*slot].1 == 1)
+            .collect::<Vec<_>>();
+        let mut peeled = Vec::with_capacity(keys.len());
+        while let Some(slot) = queue.pop() {
+            if slots[slot].1 != 1 {
+                continue;
+            }
+            let hash = slots[slot].0;
+            peeled.push((slot, hash));
+            for other in Self::slots(block_length, hash) {
+                slots[other].0 ^= hash;
+                slots[other].1 -= 1;
+                if slots[other].1 == 1 {
+                    queue.push(other);
+                }
+            }
+        }
+        if peeled.len() != keys.len() {
+            return None;
+        }
+        let mut fingerprints = vec![0u8; capacity];
+        for (slot, hash) in peeled.into_iter().rev() {
+            let [a, b, c] = Self::slots(block_length, hash);
+            fingerprints[slot] =
+                fingerprint(hash) ^ fingerprints[a] ^ fingerprints[b] ^ fingerprints[c];
+        }
+        Some(fingerprints)
+    }
+
+    /// Check if a xor filter may contain some data
+    pub fn may_contain(&self, key_hash: u32) -> bool {
+        let hash = Self::hash(self.seed, key_hash);
+        let [a, b, c] = Self::slots(self.block_length, hash);
+        fingerprint(hash) == self.fingerprints[a] ^ self.fingerprints[b] ^ self.fingerprints[c]
+    }
+
+    pub fn size(&self) -> usize {
+        self.fingerprints.len()
+    }
+
+    /// Encode a xor filter
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let offset = buf.len();
+        buf.put_u64(self.seed);
+        buf.put_u32(self.block_length);
+        buf.extend(&self.fingerprints);
+        let checksum = crc32fast::hash(&buf[offset..]);
+        buf.put_u32(checksum);
+    }
+
+    /// Decode a xor filter
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        // the seed, the block length and the checksum.
+        if buf.len() < 8 + 4 + 4 {
+            bail!("xor filter too short: {} bytes", buf.len());
+        }
+        let checksum = (&buf[buf.len() - 4..]).get_u32();
+        let mut buf = &buf[..buf.len() - 4];
+        if checksum != crc32fast::hash(buf) {
+            bail!("checksum mismatched for xor filters");
+        }
+        let seed = buf.get_u64();
+        let block_length = buf.get_u32();
+        if buf.len() != block_length as usize * 3 {
+            bail!(
+                "xor filter of block length {} has {} fingerprints",
+                block_length,
+                buf.len()
+            );
+        }
+        Ok(Self {
+            seed,
+            block_length,
+            fingerprints: buf.to_vec(),
+        })
+    }
+}
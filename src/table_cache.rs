@@ -0,0 +1,159 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+
+use crate::{
+    block_cache::BlockCache,
+    lsm_storage::LsmStorageInner,
+    table::{FileObject, ReaderSettings, TableReader},
+};
+
+/// open readers by SST id, with the tick of their last use.
+#[derive(Default)]
+struct Lru {
+    readers: HashMap<usize, (Arc<TableReader>, u64)>,
+    // SST ids by the tick of their last use, the least recently used first.
+    by_use: BTreeMap<u64, usize>,
+    tick: u64,
+}
+
+impl Lru {
+    fn touch(&mut self, id: usize) -> Option<Arc<TableReader>> {
+        self.tick += 1;
+        let (reader, last_use) = self.readers.get_mut(&id)?;
+        self.by_use.remove(last_use);
+        self.by_use.insert(self.tick, id);
+        *last_use = self.tick;
+        Some(reader.clone())
+    }
+
+    fn remove(&mut self, id: usize) {
+        if let Some((_, last_use)) = self.readers.remove(&id) {
+            self.by_use.remove(&last_use);
+        }
+    }
+}
+
+/// TableCache keeps the readers of at most `capacity` SST files open, closing the
+/// least recently used one to open another. Iterators hold the reader they read
+/// through, so a closed reader only releases its file once they are done with it.
+pub struct TableCache {
+    path: PathBuf,
+    block_cache: Arc<BlockCache>,
+    capacity: usize,
+    lru: Mutex<Lru>,
+    opens: AtomicU64,
+}
+
+impl TableCache {
+    /// create a cache of the SSTs in the directory `path`.
+    pub fn new(path: impl AsRef<Path>, capacity: usize, block_cache: Arc<BlockCache>) -> Self {
+        assert!(capacity > 0, "the table cache must hold at least one file");
+        Self {
+            path: path.as_ref().to_path_buf(),
+            block_cache,
+            capacity,
+            lru: Mutex::new(Lru::default()),
+            opens: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn block_cache(&self) -> &Arc<BlockCache> {
+        &self.block_cache
+    }
+
+    /// the reader of SST `id`, opening its file as `settings` ask when it is not cached.
+    pub(crate) fn get_or_open(
+        &self,
+        id: usize,
+        cache_id: usize,
+        settings: ReaderSettings,
+    ) -> Result<Arc<TableReader>> {
+        if let Some(reader) = self.lru.lock().touch(id) {
+            return Ok(reader);
+        }
+        // open without the lock, a concurrent reader may open the same file meanwhile.
+        let file = FileObject::open(&LsmStorageInner::path_of_sst_static(&self.path, id))
+            .context("failed to open SST")?;
+        let reader = Arc::new(TableReader::open_with_settings(
+            file,
+            Some(self.block_cache.clone()),
+            cache_id,
+            settings,
+        )?);
+        self.opens.fetch_add(1, Ordering::Relaxed);
+        let mut lru = self.lru.lock();
+        if let Some(reader) = lru.touch(id) {
+            return Ok(reader);
+        }
+        self.insert_locked(&mut lru, id, reader.clone());
+        Ok(reader)
+    }
+
+    /// the reader of SST `id` if it is open, without opening it.
+    pub(crate) fn get(&self, id: usize) -> Option<Arc<TableReader>> {
+        self.lru.lock().touch(id)
+    }
+
+    /// cache the reader of a newly written SST.
+    pub(crate) fn insert(&self, id: usize, reader: Arc<TableReader>) {
+        let mut lru = self.lru.lock();
+        lru.remove(id);
+        self.insert_locked(&mut lru, id, reader);
+    }
+
+    fn insert_locked(&self, lru: &mut Lru, id: usize, reader: Arc<TableReader>) {
+        while lru.readers.len() >= self.capacity {
+            let (_, evicted) = lru.by_use.pop_first().unwrap();
+            lru.readers.remove(&evicted);
+        }
+        lru.tick += 1;
+        lru.by_use.insert(lru.tick, id);
+        lru.readers.insert(id, (reader, lru.tick));
+    }
+
+    /// close the reader of an SST no longer in use and delete its file.
+    pub(crate) fn remove_file(&self, id: usize) -> Result<()> {
+        self.lru.lock().remove(id);
+        std::fs::remove_file(LsmStorageInner::path_of_sst_static(&self.path, id))?;
+        Ok(())
+    }
+
+    /// the number of files kept open at most.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// the number of readers currently cached.
+    pub fn len(&self) -> usize {
+        self.lru.lock().readers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the number of files opened since the cache was created.
+    pub fn open_count(&self) -> u64 {
+        self.opens.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for TableCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TableCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .field("open_count", &self.open_count())
+            .finish()
+    }
+}
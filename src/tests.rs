@@ -6,3 +6,28 @@ mod week3_day4;
 mod week3_day5;
 mod week3_day6;
 mod week3_day7;
+mod week4_day1;
+mod week4_day2;
+mod week4_day3;
+mod week4_day4;
+mod week4_day5;
+mod week4_day6;
+mod week4_day7;
+mod week5_day1;
+mod week5_day2;
+mod week5_day3;
+mod week5_day4;
+mod week5_day5;
+mod week5_day6;
+mod week5_day7;
+mod week6_day1;
+mod week6_day2;
+mod week6_day3;
+mod week6_day4;
+mod week6_day5;
+mod week6_day6;
+mod week6_day7;
+mod week7_day1;
+mod week7_day2;
+mod week7_day3;
+mod week7_day4;
@@ -55,7 +55,7 @@ impl StorageIterator for MockIterator {
         Ok(())
     }
 
-    fn key(&self) -> KeySlice {
+    fn key(&self) -> KeySlice<'_> {
         if let Some(error_when) = self.error_when {
             if self.index >= error_when {
                 panic!("invalid access after next returns an error!");
@@ -87,6 +87,16 @@ pub fn as_bytes(x: &[u8]) -> Bytes {
     Bytes::copy_from_slice(x)
 }
 
+pub fn key_of(idx: usize) -> Bytes {
+    Bytes::from(format!("key{:05}", idx))
+}
+
+pub fn generate_data(n: usize) -> Vec<(Bytes, Bytes)> {
+    (0..n)
+        .map(|idx| (key_of(idx), Bytes::from(format!("value{:05}", idx))))
+        .collect()
+}
+
 pub fn check_iter_result_by_key<I>(iter: &mut I, expected: Vec<(Bytes, Bytes)>)
 where
     I: for<'a> StorageIterator<KeyType<'a> = KeySlice<'a>>,
@@ -0,0 +1,66 @@
+use std::ops::Bound;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    tests::harness::check_lsm_iter_result_by_key,
+};
+
+/// Testing: parallel sub-compactions.
+/// A full compaction is cut into several key ranges that are merged on their own threads,
+/// the outputs must form one sorted, non-overlapping level holding the latest version of every key.
+#[test]
+fn test_task1_sub_compactions() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.target_sst_size = 16 << 10;
+    options.max_subcompactions = 4;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    let key_of = |i: usize| format!("key{:05}", i);
+    for round in 0..3 {
+        for i in (0..1000).step_by(round + 1) {
+            storage
+                .put(
+                    key_of(i).as_bytes(),
+                    format!("value{}@{}", i, round).as_bytes(),
+                )
+                .unwrap();
+        }
+        storage.force_flush().unwrap();
+    }
+    storage.force_full_compaction().unwrap();
+    storage.dump_structure();
+
+    let state = storage.inner.state.read().clone();
+    assert!(state.l0_sstables.is_empty());
+    let l1 = &state.levels[0].1;
+    assert!(l1.len() > 1);
+    for window in l1.windows(2) {
+        assert!(state.sstables[&window[0]].last_key() < state.sstables[&window[1]].first_key());
+    }
+
+    let latest_round = |i: usize| match (i % 3, i % 2) {
+        (0, _) => 2,
+        (_, 0) => 1,
+        _ => 0,
+    };
+    let expected = (0..1000)
+        .map(|i| {
+            (
+                Bytes::from(key_of(i)),
+                Bytes::from(format!("value{}@{}", i, latest_round(i))),
+            )
+        })
+        .collect::<Vec<_>>();
+    check_lsm_iter_result_by_key(
+        &mut storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+        expected,
+    );
+    assert_eq!(
+        storage.get(key_of(500).as_bytes()).unwrap(),
+        Some(Bytes::from("value500@1"))
+    );
+}
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::{CompactionOptions, LeveledCompactionController, LeveledCompactionOptions},
+    key::KeyBytes,
+    lsm_storage::{LsmStorageOptions, LsmStorageState, MiniLsm},
+    mem_table::MemTable,
+    table::SsTable,
+};
+
+use super::harness::{check_compaction_ratio, compaction_bench};
+
+fn add_meta_only_sst(state: &mut LsmStorageState, id: usize, size_mb: u64, range: (&str, &str)) {
+    state.sstables.insert(
+        id,
+        Arc::new(SsTable::create_meta_only(
+            id,
+            size_mb << 20,
+            KeyBytes::for_testing_from_bytes_no_ts(Bytes::copy_from_slice(range.0.as_bytes())),
+            KeyBytes::for_testing_from_bytes_no_ts(Bytes::copy_from_slice(range.1.as_bytes())),
+        )),
+    );
+}
+
+/// Testing: the leveled picker never hands out an SST that a running compaction owns.
+/// L0 is compacted first, once one of its overlapping L1 SSTs is busy the picker
+/// falls back to the next free L1 SST, and gives up when every candidate is busy.
+#[test]
+fn test_task1_picker_skips_busy_ssts() {
+    let controller = LeveledCompactionController::new(LeveledCompactionOptions {
+        level_size_multiplier: 2,
+        level0_file_num_compaction_trigger: 2,
+        max_levels: 3,
+        base_level_size_mb: 1,
+    });
+    let mut state = LsmStorageState {
+        memtable: Arc::new(MemTable::create(0)),
+        imm_memtables: Vec::new(),
+        l0_sstables: vec![6, 5],
+        levels: vec![(1, vec![1, 2]), (2, vec![3, 4]), (3, vec![7])],
+        sstables: Default::default(),
+        compacting_sstables: Default::default(),
+    };
+    add_meta_only_sst(&mut state, 1, 2, ("a", "c"));
+    add_meta_only_sst(&mut state, 2, 2, ("d", "f"));
+    add_meta_only_sst(&mut state, 3, 1, ("a", "c"));
+    add_meta_only_sst(&mut state, 4, 1, ("d", "f"));
+    add_meta_only_sst(&mut state, 5, 1, ("a", "b"));
+    add_meta_only_sst(&mut state, 6, 1, ("b", "c"));
+    add_meta_only_sst(&mut state, 7, 10, ("a", "f"));
+
+    let task = controller.generate_compaction_task(&state).unwrap();
+    assert_eq!(task.upper_level, None);
+    assert_eq!(task.lower_level, 1);
+    assert_eq!(task.lower_level_sst_ids, vec![1]);
+
+    state.compacting_sstables.extend([1, 3]);
+    let task = controller.generate_compaction_task(&state).unwrap();
+    assert_eq!(task.upper_level, Some(1));
+    assert_eq!(task.upper_level_sst_ids, vec![2]);
+    assert_eq!(task.lower_level_sst_ids, vec![4]);
+
+    state.compacting_sstables.extend([2, 4]);
+    assert!(controller.generate_compaction_task(&state).is_none());
+}
+
+#[test]
+fn test_task2_concurrent_compactions_integration() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::Leveled(
+        LeveledCompactionOptions {
+            level0_file_num_compaction_trigger: 2,
+            level_size_multiplier: 2,
+            base_level_size_mb: 1,
+            max_levels: 4,
+        },
+    ));
+    options.max_background_compactions = 4;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+
+    compaction_bench(storage.clone());
+    check_compaction_ratio(storage.clone());
+    assert!(storage.inner.state.read().compacting_sstables.is_empty());
+}
+
+/// Testing: only levels over their target size are compacted, the bottom level never is,
+/// and applying a level compaction takes the SST out of the upper level.
+#[test]
+fn test_task3_picker_levels_over_target() {
+    let controller = LeveledCompactionController::new(LeveledCompactionOptions {
+        level_size_multiplier: 2,
+        level0_file_num_compaction_trigger: 2,
+        max_levels: 3,
+        base_level_size_mb: 1,
+    });
+    let mut state = LsmStorageState {
+        memtable: Arc::new(MemTable::create(0)),
+        imm_memtables: Vec::new(),
+        l0_sstables: Vec::new(),
+        levels: vec![(1, Vec::new()), (2, vec![1]), (3, vec![2])],
+        sstables: Default::default(),
+        compacting_sstables: Default::default(),
+    };
+    // targets of 2 MB for L2 and 4 MB for the bottom level: L2 is within its target and
+    // the bottom level far over it.
+    add_meta_only_sst(&mut state, 1, 2, ("a", "c"));
+    add_meta_only_sst(&mut state, 2, 4, ("a", "f"));
+    assert!(controller.generate_compaction_task(&state).is_none());
+
+    state.levels[1].1.push(3);
+    add_meta_only_sst(&mut state, 3, 1, ("d", "e"));
+    let task = controller.generate_compaction_task(&state).unwrap();
+    assert_eq!(task.upper_level, Some(2));
+    assert_eq!(task.upper_level_sst_ids, vec![1]);
+    assert_eq!(task.lower_level_sst_ids, vec![2]);
+    assert!(task.is_lower_level_bottom_level);
+
+    add_meta_only_sst(&mut state, 4, 5, ("a", "f"));
+    let (state, files_to_remove) = controller.apply_compaction_result(&state, &task, &[4], false);
+    assert_eq!(state.levels[1].1, vec![3]);
+    assert_eq!(state.levels[2].1, vec![4]);
+    assert_eq!(files_to_remove, vec![1, 2]);
+}
+
+/// Testing: leveled compaction needs at least one level.
+#[test]
+fn test_task4_reject_zero_max_levels() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::Leveled(
+        LeveledCompactionOptions {
+            level0_file_num_compaction_trigger: 2,
+            level_size_multiplier: 2,
+            base_level_size_mb: 1,
+            max_levels: 0,
+        },
+    ));
+    assert!(MiniLsm::open(&dir, options).is_err());
+}
@@ -0,0 +1,132 @@
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::{CompactionOptions, LeveledCompactionController, LeveledCompactionOptions},
+    iterators::StorageIterator,
+    key::KeyBytes,
+    lsm_storage::{LsmStorageOptions, LsmStorageState, MiniLsm},
+    mem_table::MemTable,
+    table::{SsTable, SsTableIterator},
+};
+
+fn leveled_options() -> LeveledCompactionOptions {
+    LeveledCompactionOptions {
+        level_size_multiplier: 2,
+        level0_file_num_compaction_trigger: 2,
+        max_levels: 3,
+        base_level_size_mb: 1,
+    }
+}
+
+fn meta_only_sst(id: usize, size_mb: u64, first_key: &str, last_key: &str) -> Arc<SsTable> {
+    Arc::new(SsTable::create_meta_only(
+        id,
+        size_mb << 20,
+        KeyBytes::for_testing_from_bytes_no_ts(Bytes::copy_from_slice(first_key.as_bytes())),
+        KeyBytes::for_testing_from_bytes_no_ts(Bytes::copy_from_slice(last_key.as_bytes())),
+    ))
+}
+
+/// Testing: the picker turns a task whose inputs overlap nothing below into a move,
+/// and applying it relinks the same SST ids without removing any file.
+#[test]
+fn test_task1_trivial_move_task() {
+    let controller = LeveledCompactionController::new(leveled_options());
+    let mut state = LsmStorageState {
+        memtable: Arc::new(MemTable::create(0)),
+        imm_memtables: Vec::new(),
+        l0_sstables: Vec::new(),
+        levels: vec![(1, vec![1]), (2, vec![2]), (3, vec![3])],
+        sstables: Default::default(),
+        compacting_sstables: Default::default(),
+    };
+    state.sstables.insert(1, meta_only_sst(1, 4, "m", "p"));
+    state.sstables.insert(2, meta_only_sst(2, 1, "a", "c"));
+    state.sstables.insert(3, meta_only_sst(3, 10, "a", "z"));
+
+    let task = controller.generate_compaction_task(&state).unwrap();
+    assert_eq!(task.upper_level, Some(1));
+    assert!(task.lower_level_sst_ids.is_empty());
+    assert!(task.is_trivial_move);
+
+    let (state, files_to_remove) =
+        controller.apply_compaction_result(&state, &task, &task.upper_level_sst_ids, false);
+    assert!(files_to_remove.is_empty());
+    assert!(state.levels[0].1.is_empty());
+    assert_eq!(state.levels[1].1, vec![2, 1]);
+}
+
+/// Testing: overlapping L0 SSTs are never moved, they have to be merged.
+#[test]
+fn test_task2_overlapping_l0_is_not_moved() {
+    let controller = LeveledCompactionController::new(leveled_options());
+    let mut state = LsmStorageState {
+        memtable: Arc::new(MemTable::create(0)),
+        imm_memtables: Vec::new(),
+        l0_sstables: vec![2, 1],
+        levels: vec![(1, vec![]), (2, vec![]), (3, vec![])],
+        sstables: Default::default(),
+        compacting_sstables: Default::default(),
+    };
+    state.sstables.insert(1, meta_only_sst(1, 1, "a", "m"));
+    state.sstables.insert(2, meta_only_sst(2, 1, "k", "z"));
+    let task = controller.generate_compaction_task(&state).unwrap();
+    assert_eq!(task.upper_level, None);
+    assert!(!task.is_trivial_move);
+}
+
+/// Testing: disjoint L0 SSTs entering the bottom level are rewritten rather than relinked,
+/// so their tombstones get purged, and the result survives a restart.
+#[test]
+fn test_task3_trivial_move_integration() {
+    let dir = tempdir().unwrap();
+    let options =
+        LsmStorageOptions::default_for_week2_test(CompactionOptions::Leveled(leveled_options()));
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    storage.put(b"a", b"1").unwrap();
+    storage.put(b"b", b"1").unwrap();
+    storage.force_flush().unwrap();
+    storage.put(b"x", b"2").unwrap();
+    storage.put(b"y", b"2").unwrap();
+    storage.delete(b"z").unwrap();
+    storage.force_flush().unwrap();
+    let mut flushed = storage.inner.state.read().l0_sstables.clone();
+    flushed.sort();
+    assert_eq!(flushed.len(), 2);
+
+    let mut waited = 0;
+    while !storage.inner.state.read().l0_sstables.is_empty() {
+        assert!(waited < 100, "compaction did not run");
+        std::thread::sleep(Duration::from_millis(50));
+        waited += 1;
+    }
+    let bottom_level = storage.inner.state.read().levels.last().unwrap().1.clone();
+    assert!(!bottom_level.is_empty());
+    assert!(bottom_level.iter().all(|id| !flushed.contains(id)));
+    let state = storage.inner.state.read();
+    for id in &bottom_level {
+        let mut iter =
+            SsTableIterator::create_and_seek_to_first(state.sstables[id].clone()).unwrap();
+        while iter.is_valid() {
+            assert!(
+                !iter.value().is_empty(),
+                "a tombstone reached the bottom level"
+            );
+            iter.next().unwrap();
+        }
+    }
+    drop(state);
+    storage.close().unwrap();
+    drop(storage);
+
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    let state = storage.inner.state.read().clone();
+    assert!(state.l0_sstables.is_empty());
+    assert_eq!(state.levels.last().unwrap().1, bottom_level);
+    assert_eq!(storage.get(b"a").unwrap(), Some(Bytes::from("1")));
+    assert_eq!(storage.get(b"y").unwrap(), Some(Bytes::from("2")));
+    assert_eq!(storage.get(b"z").unwrap(), None);
+}
@@ -0,0 +1,63 @@
+use std::ops::Bound;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::{CompactionOptions, LeveledCompactionOptions},
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    tests::harness::{check_iter_result_by_key, construct_merge_iterator_over_storage},
+};
+
+/// Testing: manual range compaction.
+/// Write a range, bulk delete part of it and compact that range down to the bottom level:
+/// the tombstones and deleted values are purged, while an SST outside the range stays in L0.
+#[test]
+fn test_task1_compact_range() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::Leveled(
+        LeveledCompactionOptions {
+            level0_file_num_compaction_trigger: 100,
+            level_size_multiplier: 2,
+            base_level_size_mb: 128,
+            max_levels: 3,
+        },
+    ));
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for i in 0..10 {
+        storage
+            .put(format!("key{}", i).as_bytes(), b"value")
+            .unwrap();
+    }
+    storage.force_flush().unwrap();
+    for i in 0..5 {
+        storage.delete(format!("key{}", i).as_bytes()).unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.put(b"zzz", b"untouched").unwrap();
+    storage.force_flush().unwrap();
+    let untouched_sst = storage.inner.state.read().l0_sstables[0];
+
+    assert!(storage
+        .compact_range(Bound::Included(b"key"), Bound::Included(b"key9"), 4)
+        .is_err());
+    storage
+        .compact_range(Bound::Included(b"key"), Bound::Included(b"key9"), 3)
+        .unwrap();
+
+    let state = storage.inner.state.read().clone();
+    assert_eq!(state.l0_sstables, vec![untouched_sst]);
+    assert!(state.levels[0].1.is_empty());
+    assert!(state.levels[1].1.is_empty());
+    assert!(!state.levels[2].1.is_empty());
+    assert!(state.compacting_sstables.is_empty());
+
+    let mut iter = construct_merge_iterator_over_storage(&state);
+    let mut expected = (5..10)
+        .map(|i| (Bytes::from(format!("key{}", i)), Bytes::from("value")))
+        .collect::<Vec<_>>();
+    expected.push((Bytes::from("zzz"), Bytes::from("untouched")));
+    check_iter_result_by_key(&mut iter, expected);
+    assert_eq!(storage.get(b"key3").unwrap(), None);
+    assert_eq!(storage.get(b"key7").unwrap(), Some(Bytes::from("value")));
+}
@@ -0,0 +1,191 @@
+use std::{
+    ops::Bound,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::{
+        CompactionFilter, CompactionFilterContext, CompactionFilterDecision,
+        CompactionFilterFactory, CompactionOptions, LeveledCompactionOptions,
+    },
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    tests::harness::{check_iter_result_by_key, construct_merge_iterator_over_storage},
+};
+
+/// Drops values marked as expired and upgrades `v1:` values to `v2:`,
+/// counting the keys it has seen in its own compaction.
+struct UpgradeFilter {
+    seen: usize,
+    seen_total: Arc<AtomicUsize>,
+}
+
+impl CompactionFilter for UpgradeFilter {
+    fn filter(&mut self, _key: &[u8], _ts: u64, value: &[u8]) -> CompactionFilterDecision {
+        self.seen += 1;
+        if value == b"expired" {
+            CompactionFilterDecision::Remove
+        } else if let Some(rest) = value.strip_prefix(b"v1:") {
+            CompactionFilterDecision::ChangeValue(Bytes::from([b"v2:", rest].concat()))
+        } else {
+            CompactionFilterDecision::Keep
+        }
+    }
+}
+
+impl Drop for UpgradeFilter {
+    fn drop(&mut self) {
+        self.seen_total.fetch_add(self.seen, Ordering::SeqCst);
+    }
+}
+
+struct UpgradeFilterFactory {
+    created: Arc<AtomicUsize>,
+    seen_total: Arc<AtomicUsize>,
+}
+
+impl CompactionFilterFactory for UpgradeFilterFactory {
+    fn create_compaction_filter(
+        &self,
+        context: CompactionFilterContext,
+    ) -> Box<dyn CompactionFilter> {
+        assert!(context.compact_to_bottom_level);
+        self.created.fetch_add(1, Ordering::SeqCst);
+        Box::new(UpgradeFilter {
+            seen: 0,
+            seen_total: self.seen_total.clone(),
+        })
+    }
+}
+
+/// Testing: a user-defined filter built by a factory for every compaction.
+/// It removes and rewrites values below the watermark, never sees tombstones,
+/// and leaves the versions a live snapshot can still read alone.
+#[test]
+fn test_task1_compaction_filter_factory() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    let created = Arc::new(AtomicUsize::new(0));
+    let seen_total = Arc::new(AtomicUsize::new(0));
+    storage.add_compaction_filter(UpgradeFilterFactory {
+        created: created.clone(),
+        seen_total: seen_total.clone(),
+    });
+
+    storage.put(b"a", b"v1:a").unwrap();
+    storage.put(b"b", b"expired").unwrap();
+    storage.put(b"c", b"v2:c").unwrap();
+    storage.put(b"d", b"v1:d").unwrap();
+    storage.delete(b"d").unwrap();
+    storage.force_flush().unwrap();
+    let snapshot = storage.new_txn().unwrap();
+    storage.put(b"e", b"v1:e").unwrap();
+    storage.force_flush().unwrap();
+    storage.force_full_compaction().unwrap();
+
+    assert_eq!(created.load(Ordering::SeqCst), 1);
+    assert_eq!(seen_total.load(Ordering::SeqCst), 3);
+    let mut iter = construct_merge_iterator_over_storage(&storage.inner.state.read());
+    check_iter_result_by_key(
+        &mut iter,
+        vec![
+            (Bytes::from("a"), Bytes::from("v2:a")),
+            (Bytes::from("c"), Bytes::from("v2:c")),
+            (Bytes::from("e"), Bytes::from("v1:e")),
+        ],
+    );
+    assert_eq!(snapshot.get(b"b").unwrap(), None);
+    assert_eq!(snapshot.get(b"e").unwrap(), None);
+
+    drop(snapshot);
+    storage.force_full_compaction().unwrap();
+    assert_eq!(created.load(Ordering::SeqCst), 2);
+    assert_eq!(storage.get(b"e").unwrap(), Some(Bytes::from("v2:e")));
+    assert_eq!(storage.get(b"a").unwrap(), Some(Bytes::from("v2:a")));
+}
+
+/// Drops every value marked as expired.
+struct RemoveExpiredFilter;
+
+impl CompactionFilter for RemoveExpiredFilter {
+    fn filter(&mut self, _key: &[u8], _ts: u64, value: &[u8]) -> CompactionFilterDecision {
+        if value == b"expired" {
+            CompactionFilterDecision::Remove
+        } else {
+            CompactionFilterDecision::Keep
+        }
+    }
+}
+
+struct RemoveExpiredFilterFactory;
+
+impl CompactionFilterFactory for RemoveExpiredFilterFactory {
+    fn create_compaction_filter(
+        &self,
+        _context: CompactionFilterContext,
+    ) -> Box<dyn CompactionFilter> {
+        Box::new(RemoveExpiredFilter)
+    }
+}
+
+/// Testing: a value removed by a filter above the bottom level leaves a tombstone,
+/// so an older version of the key in a deeper level does not come back.
+#[test]
+fn test_task1_remove_above_bottom_level() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::Leveled(
+        LeveledCompactionOptions {
+            level0_file_num_compaction_trigger: 100,
+            level_size_multiplier: 2,
+            base_level_size_mb: 128,
+            max_levels: 3,
+        },
+    ));
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    storage.put(b"a", b"old").unwrap();
+    storage.put(b"c", b"old").unwrap();
+    storage.force_flush().unwrap();
+    storage
+        .compact_range(Bound::Unbounded, Bound::Unbounded, 3)
+        .unwrap();
+    let bottom_ssts = storage.inner.state.read().levels[2].1.clone();
+    assert!(!bottom_ssts.is_empty());
+
+    storage.add_compaction_filter(RemoveExpiredFilterFactory);
+    storage.put(b"a", b"expired").unwrap();
+    storage.put(b"c", b"new").unwrap();
+    storage.force_flush().unwrap();
+    storage.put(b"b", b"new").unwrap();
+    storage.put(b"c", b"newer").unwrap();
+    storage.force_flush().unwrap();
+    storage
+        .compact_range(Bound::Unbounded, Bound::Unbounded, 1)
+        .unwrap();
+
+    let state = storage.inner.state.read().clone();
+    assert!(state.l0_sstables.is_empty());
+    assert!(!state.levels[0].1.is_empty());
+    assert_eq!(state.levels[2].1, bottom_ssts);
+    assert_eq!(storage.get(b"a").unwrap(), None);
+    assert_eq!(storage.get(b"b").unwrap(), Some(Bytes::from("new")));
+    assert_eq!(storage.get(b"c").unwrap(), Some(Bytes::from("newer")));
+
+    storage
+        .compact_range(Bound::Unbounded, Bound::Unbounded, 3)
+        .unwrap();
+    assert_eq!(storage.get(b"a").unwrap(), None);
+    let mut iter = construct_merge_iterator_over_storage(&storage.inner.state.read());
+    check_iter_result_by_key(
+        &mut iter,
+        vec![
+            (Bytes::from("b"), Bytes::from("new")),
+            (Bytes::from("c"), Bytes::from("newer")),
+        ],
+    );
+}
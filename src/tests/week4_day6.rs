@@ -0,0 +1,107 @@
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    iterators::StorageIterator,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    mem_table::prefix_upper_bound,
+    table::PrefixExtractor,
+    tests::harness::check_lsm_iter_result_by_key,
+};
+
+#[test]
+fn test_task1_prefix_extractor() {
+    let fixed = PrefixExtractor::FixedLength(3);
+    assert_eq!(fixed.extract(b"abcdef"), Some(&b"abc"[..]));
+    assert_eq!(fixed.extract(b"ab"), None);
+
+    let delimited = PrefixExtractor::Delimited {
+        delimiter: b'/',
+        count: 2,
+    };
+    assert_eq!(
+        delimited.extract(b"tenant/entity/1"),
+        Some(&b"tenant/entity/"[..])
+    );
+    assert_eq!(delimited.extract(b"tenant/entity"), None);
+
+    assert_eq!(prefix_upper_bound(b"ab"), Some(b"ac".to_vec()));
+    assert_eq!(prefix_upper_bound(b"a\xff"), Some(b"b".to_vec()));
+    assert_eq!(prefix_upper_bound(b"\xff\xff"), None);
+}
+
+/// Testing: prefix bloom filters.
+/// Every tenant is flushed into its own SST, a prefix scan only reads the SSTs whose
+/// filter holds the tenant prefix and still returns the keys left in the memtable.
+#[test]
+fn test_task2_prefix_scan() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.prefix_extractor = Some(PrefixExtractor::Delimited {
+        delimiter: b'/',
+        count: 1,
+    });
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    let tenants = ["alpha", "beta", "gamma"];
+    for tenant in tenants {
+        for i in 0..100 {
+            storage
+                .put(
+                    format!("{}/entity{:03}", tenant, i).as_bytes(),
+                    format!("{}{}", tenant, i).as_bytes(),
+                )
+                .unwrap();
+        }
+        storage.force_flush().unwrap();
+    }
+    storage.put(b"beta/entity100", b"beta100").unwrap();
+    storage.delete(b"beta/entity000").unwrap();
+
+    {
+        let state = storage.inner.state.read();
+        assert_eq!(state.l0_sstables.len(), 3);
+        let beta_hash = storage.inner.prefix_bloom_hash(b"beta/").unwrap();
+        let matching = state
+            .l0_sstables
+            .iter()
+            .filter(|id| {
+                state.sstables[*id]
+                    .may_contain_prefix(b"beta/", beta_hash)
+                    .unwrap()
+            })
+            .count();
+        assert_eq!(matching, 1);
+    }
+    assert_eq!(storage.inner.prefix_bloom_hash(b"bet"), None);
+
+    let expected = (1..=100)
+        .map(|i| {
+            (
+                Bytes::from(format!("beta/entity{:03}", i)),
+                Bytes::from(format!("beta{}", i)),
+            )
+        })
+        .collect::<Vec<_>>();
+    check_lsm_iter_result_by_key(&mut storage.prefix_scan(b"beta/").unwrap(), expected);
+    check_lsm_iter_result_by_key(
+        &mut storage.prefix_scan(b"gamma/entity09").unwrap(),
+        (90..100)
+            .map(|i| {
+                (
+                    Bytes::from(format!("gamma/entity{:03}", i)),
+                    Bytes::from(format!("gamma{}", i)),
+                )
+            })
+            .collect(),
+    );
+
+    let mut iter = storage.prefix_scan(b"a").unwrap();
+    let mut count = 0;
+    while iter.is_valid() {
+        count += 1;
+        iter.next().unwrap();
+    }
+    assert_eq!(count, 100);
+    assert!(!storage.prefix_scan(b"delta/").unwrap().is_valid());
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    iterators::StorageIterator,
+    key::KeySlice,
+    lsm_storage::BlockCache,
+    table::{FileObject, SsTable, SsTableIterator},
+};
+
+use super::harness::{check_iter_result_by_key_and_ts, generate_sst_with_ts, key_of};
+
+/// ten versions of every key, so that the versions of some keys are split
+/// across two blocks and partitions.
+fn generate_test_data() -> Vec<((Bytes, u64), Bytes)> {
+    (0..5000)
+        .map(|id| {
+            (
+                (key_of(id / 10), 10 - (id % 10) as u64),
+                Bytes::from(format!("value{:05}", id)),
+            )
+        })
+        .collect()
+}
+
+/// Testing: partitioned index and filter blocks.
+/// Opening an SST only reads the top-level index, the partitions are loaded through
+/// the block cache on the first lookup, and seeks and filter checks work across partitions.
+#[test]
+fn test_task1_partitioned_index_and_filter() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let data = generate_test_data();
+    generate_sst_with_ts(1, &path, data.clone(), None);
+
+    let block_cache = Arc::new(BlockCache::new(1 << 20));
+    let sst = Arc::new(
+        SsTable::open(
+            1,
+            Some(block_cache.clone()),
+            FileObject::open(&path).unwrap(),
+        )
+        .unwrap(),
+    );
+    block_cache.sync();
+    assert_eq!(block_cache.entry_count(), 0);
+    assert!(sst.reader().unwrap().index.len() > 1);
+    assert!(sst.num_of_blocks() > sst.reader().unwrap().index.len());
+
+    for idx in 0..500 {
+        assert!(sst.may_contain_key(&key_of(idx)).unwrap());
+    }
+    let false_positives = (500..1500)
+        .filter(|idx| sst.may_contain_key(&key_of(*idx)).unwrap())
+        .count();
+    assert!(false_positives < 50);
+    block_cache.sync();
+    // only filter partitions were read, one per partition at most.
+    let cached = block_cache.entry_count() as usize;
+    assert!(cached > 1 && cached <= sst.reader().unwrap().index.len());
+
+    check_iter_result_by_key_and_ts(
+        &mut SsTableIterator::create_and_seek_to_first(sst.clone()).unwrap(),
+        data.clone(),
+    );
+    for idx in (0..500).step_by(7) {
+        let iter = SsTableIterator::create_and_seek_to_key(
+            sst.clone(),
+            KeySlice::for_testing_from_slice_with_ts(&key_of(idx), 5),
+        )
+        .unwrap();
+        assert!(iter.is_valid());
+        assert_eq!(iter.key().key_ref(), &key_of(idx)[..]);
+        assert_eq!(iter.key().ts(), 5);
+        assert_eq!(iter.value(), &data[idx * 10 + 5].1[..]);
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use crate::{
+    block::{builder::BlockBuilder, iterator::BlockIterator, Block, RESTART_INTERVAL},
+    key::KeySlice,
+};
+
+fn key_of(idx: usize) -> Vec<u8> {
+    format!("tenant/entity/key_{:05}", idx * 2).into_bytes()
+}
+
+fn value_of(idx: usize) -> Vec<u8> {
+    format!("value_{}", idx).into_bytes()
+}
+
+fn build_block(num_entries: usize) -> (Block, usize) {
+    let mut builder = BlockBuilder::new(1 << 15);
+    let mut raw_size = 0;
+    for idx in 0..num_entries {
+        let key = key_of(idx);
+        assert!(builder.add(KeySlice::for_testing_from_slice_no_ts(&key), &value_of(idx)));
+        raw_size += key.len() + value_of(idx).len();
+    }
+    (builder.build(), raw_size)
+}
+
+/// Testing: restart points.
+/// A full key is only stored every `RESTART_INTERVAL` entries, the block survives an
+/// encode/decode round trip and is smaller than the raw keys and values it holds.
+#[test]
+fn test_task1_block_restart_points() {
+    let num_entries = 100;
+    let (block, raw_size) = build_block(num_entries);
+    assert_eq!(block.restarts.len(), num_entries.div_ceil(RESTART_INTERVAL));
+    let encoded = block.encode();
+    assert!(encoded.len() < raw_size);
+
+    let block = Arc::new(Block::decode(&encoded));
+    let mut iter = BlockIterator::create_and_seek_to_first(block);
+    for idx in 0..num_entries {
+        assert!(iter.is_valid());
+        assert_eq!(iter.key().key_ref(), &key_of(idx)[..]);
+        assert_eq!(iter.value(), &value_of(idx)[..]);
+        iter.next();
+    }
+    assert!(!iter.is_valid());
+}
+
+/// Testing: seeks binary search the restart array, then scan within one interval.
+#[test]
+fn test_task2_block_seek_with_restarts() {
+    let num_entries = 100;
+    let (block, _) = build_block(num_entries);
+    let block = Arc::new(block);
+    let mut iter = BlockIterator::create_and_seek_to_first(block.clone());
+    for idx in 0..num_entries {
+        iter.seek_to_key(KeySlice::for_testing_from_slice_no_ts(&key_of(idx)));
+        assert_eq!(iter.key().key_ref(), &key_of(idx)[..]);
+        assert_eq!(iter.value(), &value_of(idx)[..]);
+
+        // a key between two entries lands on the next one.
+        let mut missing = key_of(idx);
+        missing.push(b'0');
+        iter.seek_to_key(KeySlice::for_testing_from_slice_no_ts(&missing));
+        if idx + 1 < num_entries {
+            assert_eq!(iter.key().key_ref(), &key_of(idx + 1)[..]);
+        } else {
+            assert!(!iter.is_valid());
+        }
+    }
+    let iter =
+        BlockIterator::create_and_seek_to_key(block, KeySlice::for_testing_from_slice_no_ts(b"a"));
+    assert_eq!(iter.key().key_ref(), &key_of(0)[..]);
+}
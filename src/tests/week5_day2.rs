@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    block::{builder::BlockBuilder, iterator::BlockIterator, Block},
+    compact::CompactionOptions,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+};
+
+fn key_of(idx: usize) -> Vec<u8> {
+    format!("key_{:04}", idx * 2).into_bytes()
+}
+
+/// three versions of every key, ts 3 down to 1.
+fn build_block(hash_index: bool) -> Block {
+    let mut builder = BlockBuilder::new(1 << 14).with_hash_index(hash_index);
+    for idx in 0..100 {
+        for ts in (1..=3).rev() {
+            let value = format!("value_{}@{}", idx, ts);
+            assert!(builder.add(
+                KeySlice::for_testing_from_slice_with_ts(&key_of(idx), ts),
+                value.as_bytes()
+            ));
+        }
+    }
+    Block::decode(&builder.build().encode())
+}
+
+fn seek(block: &Arc<Block>, key: &[u8], ts: u64) -> Option<(Vec<u8>, u64, Vec<u8>)> {
+    let iter = BlockIterator::create_and_seek_to_key(
+        block.clone(),
+        KeySlice::for_testing_from_slice_with_ts(key, ts),
+    );
+    iter.is_valid().then(|| {
+        (
+            iter.key().key_ref().to_vec(),
+            iter.key().ts(),
+            iter.value().to_vec(),
+        )
+    })
+}
+
+/// Testing: in-block hash index.
+/// The index is recorded in the block trailer, and every seek through it lands on
+/// the same entry as the binary search over a block built without it.
+#[test]
+fn test_task1_block_hash_index() {
+    let hashed = Arc::new(build_block(true));
+    let plain = Arc::new(build_block(false));
+    assert!(hashed.hash_index.is_some());
+    assert!(plain.hash_index.is_none());
+    assert_eq!(hashed.data, plain.data);
+    let indexed = (0..100)
+        .filter(|idx| hashed.hash_index_lookup(&key_of(*idx)).is_some())
+        .count();
+    // at a 0.75 load factor a little less than half of the keys own their bucket.
+    assert!(indexed > 30);
+
+    for idx in 0..100 {
+        for ts in 0..=4 {
+            assert_eq!(
+                seek(&hashed, &key_of(idx), ts),
+                seek(&plain, &key_of(idx), ts)
+            );
+        }
+        let mut missing = key_of(idx);
+        missing.push(b'0');
+        assert_eq!(seek(&hashed, &missing, 3), seek(&plain, &missing, 3));
+    }
+    assert_eq!(
+        seek(&hashed, &key_of(7), 2),
+        Some((key_of(7), 2, b"value_7@2".to_vec()))
+    );
+}
+
+#[test]
+fn test_task2_get_with_block_hash_index() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.block_hash_index = true;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for i in 0..1000 {
+        storage
+            .put(format!("key{:04}", i).as_bytes(), b"old")
+            .unwrap();
+    }
+    storage.force_flush().unwrap();
+    let snapshot = storage.new_txn().unwrap();
+    for i in (0..1000).step_by(3) {
+        storage
+            .put(format!("key{:04}", i).as_bytes(), b"new")
+            .unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.force_full_compaction().unwrap();
+
+    for i in 0..1000 {
+        let key = format!("key{:04}", i);
+        let expected = if i % 3 == 0 { "new" } else { "old" };
+        assert_eq!(
+            storage.get(key.as_bytes()).unwrap(),
+            Some(Bytes::from(expected))
+        );
+        assert_eq!(
+            snapshot.get(key.as_bytes()).unwrap(),
+            Some(Bytes::from("old"))
+        );
+    }
+    assert_eq!(storage.get(b"key10000").unwrap(), None);
+}
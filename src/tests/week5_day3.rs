@@ -0,0 +1,142 @@
+use tempfile::tempdir;
+
+use crate::{
+    block::RESTART_INTERVAL,
+    key::KeySlice,
+    table::{
+        BlockMeta, FileObject, PartitionHandle, PrefixExtractor, SsTable, SsTableBuilder,
+        TableProperties,
+    },
+};
+
+fn build_sst(path: &std::path::Path) -> SsTable {
+    let mut builder = SsTableBuilder::new(128)
+        .with_prefix_extractor(Some(PrefixExtractor::FixedLength(3)))
+        .with_block_hash_index(true);
+    for i in 0..100u64 {
+        let key = format!("key{:03}", i);
+        let value = if i % 10 == 0 { "" } else { "value" };
+        builder.add(
+            KeySlice::for_testing_from_slice_with_ts(key.as_bytes(), 100 + i),
+            value.as_bytes(),
+        );
+    }
+    builder.build(1, None, path).unwrap()
+}
+
+/// Testing: table properties are collected while building, written into the
+/// properties block and read back on open.
+#[test]
+fn test_task1_table_properties() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let built = build_sst(&path);
+    let sst = SsTable::open(1, None, FileObject::open(&path).unwrap()).unwrap();
+    assert_eq!(sst.properties(), built.properties());
+
+    let properties = sst.properties();
+    assert_eq!(properties.num_entries, 100);
+    assert_eq!(properties.num_deletions, 10);
+    assert_eq!(properties.raw_key_size, 100 * (6 + 8));
+    assert_eq!(properties.raw_value_size, 90 * 5);
+    assert_eq!(properties.min_ts, 100);
+    assert_eq!(properties.max_ts, 199);
+    assert_eq!(sst.max_ts(), 199);
+    assert!(properties.creation_time > 0);
+    assert_eq!(properties.compression, "none");
+    assert_eq!(properties.block_size, 128);
+    assert_eq!(properties.restart_interval, RESTART_INTERVAL);
+    assert!(properties.block_hash_index);
+    assert_eq!(
+        properties.prefix_extractor,
+        Some(PrefixExtractor::FixedLength(3))
+    );
+}
+
+/// Testing: the footer rejects foreign, truncated and corrupted files instead of misparsing them.
+#[test]
+fn test_task2_footer_validation() {
+    let dir = tempdir().unwrap();
+    let open = |name: &str, data: Vec<u8>| {
+        let path = dir.path().join(name);
+        std::fs::write(&path, data).unwrap();
+        SsTable::open(1, None, FileObject::open(&path).unwrap())
+    };
+
+    let err = open("small.sst", vec![0; 10]).err().unwrap();
+    assert!(err.to_string().contains("too small"));
+    let err = open("foreign.sst", vec![7; 4096]).err().unwrap();
+    assert!(err.to_string().contains("magic"));
+
+    let path = dir.path().join("1.sst");
+    build_sst(&path);
+    let mut data = std::fs::read(&path).unwrap();
+    let len = data.len();
+    // the format version sits right before the checksum and the magic number.
+    data[len - 16] ^= 0xff;
+    let err = open("corrupted.sst", data).err().unwrap();
+    assert!(err.to_string().contains("footer checksum"));
+}
+
+/// Testing: the properties block and the top-level index reject short or corrupted
+/// input with an error instead of panicking.
+#[test]
+fn test_task3_short_metadata_blocks() {
+    for len in 0..4 {
+        assert!(TableProperties::decode(&vec![0; len]).is_err());
+    }
+    let mut buf = Vec::new();
+    TableProperties::default().encode(&mut buf);
+    assert!(TableProperties::decode(&buf[1..]).is_err());
+    assert!(TableProperties::decode(&buf).is_ok());
+
+    for len in 0..12 {
+        assert!(PartitionHandle::decode_top_index(&vec![0; len]).is_err());
+    }
+    // a partition count claiming more handles than the buffer holds, with a valid checksum.
+    let mut buf = vec![0, 0, 0, 9, 0, 0, 0, 0];
+    buf.extend(crc32fast::hash(&buf).to_be_bytes());
+    let err = PartitionHandle::decode_top_index(&buf).err().unwrap();
+    assert!(err.to_string().contains("truncated"));
+    buf[0] ^= 0xff;
+    let err = PartitionHandle::decode_top_index(&buf).err().unwrap();
+    assert!(err.to_string().contains("checksum"));
+    // a top-level index without any partition, as a user-supplied file may hold.
+    let mut buf = vec![0; 8];
+    buf.extend(crc32fast::hash(&buf).to_be_bytes());
+    let err = PartitionHandle::decode_top_index(&buf).err().unwrap();
+    assert!(err.to_string().contains("no partitions"));
+}
+
+/// Testing: an index partition is checked as a whole before its metas are decoded,
+/// so a short, truncated or corrupted partition is an error instead of a panic.
+#[test]
+fn test_task3_short_index_partition() {
+    for len in 0..8 {
+        assert!(BlockMeta::decode_block_meta(&vec![0; len]).is_err());
+    }
+    // one meta with empty keys: the offset, then a key length and a timestamp per key.
+    let mut metas = vec![0, 0, 0, 1];
+    metas.extend([0; 8 + 2 + 8 + 2 + 8]);
+    let with_checksum = |metas: &[u8]| {
+        let mut buf = metas.to_vec();
+        buf.extend(crc32fast::hash(&buf).to_be_bytes());
+        buf
+    };
+    assert_eq!(
+        BlockMeta::decode_block_meta(&with_checksum(&metas))
+            .unwrap()
+            .len(),
+        1
+    );
+    for len in 4..metas.len() {
+        let err = BlockMeta::decode_block_meta(&with_checksum(&metas[..len]))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("truncated"));
+    }
+    let mut buf = with_checksum(&metas);
+    buf[3] = 2;
+    let err = BlockMeta::decode_block_meta(&buf).err().unwrap();
+    assert!(err.to_string().contains("checksum"));
+}
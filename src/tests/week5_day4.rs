@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    table::{FileObject, Filter, FilterPolicy, SsTable, SsTableBuilder},
+};
+
+use super::harness::key_of;
+
+/// Testing: the xor filter never misses an inserted key, stays below its 1/256
+/// false positive rate, and survives an encode/decode round trip.
+#[test]
+fn test_task1_xor_filter() {
+    let hashes = (0..10000)
+        .map(|idx| farmhash::fingerprint32(&key_of(idx)))
+        .collect::<Vec<_>>();
+    let mut buf = Vec::new();
+    Filter::build(&FilterPolicy::Xor8, &hashes)
+        .unwrap()
+        .encode(&mut buf);
+    // about 1.23 bytes per key, a bloom filter with 1% false positives takes 1.2.
+    assert!(buf.len() < 10000 * 13 / 10);
+    let filter = Filter::decode(&buf).unwrap();
+    assert!(hashes.iter().all(|h| filter.may_contain(*h)));
+    let false_positives = (10000..110000)
+        .filter(|idx| filter.may_contain(farmhash::fingerprint32(&key_of(*idx))))
+        .count();
+    assert!(false_positives < 600, "{} false positives", false_positives);
+
+    // duplicated hashes do not break the construction.
+    let filter = Filter::build(&FilterPolicy::Xor8, &[1, 1, 2, 2, 3]).unwrap();
+    assert!([1, 2, 3].iter().all(|h| filter.may_contain(*h)));
+    assert!(Filter::build(&FilterPolicy::Disabled, &hashes).is_none());
+
+    // an xor filter too short for its header is an error, even with a valid checksum.
+    assert!(Filter::decode(&[2]).is_err());
+    for len in 0..12 {
+        let mut buf = vec![2];
+        buf.extend(vec![0; len]);
+        buf.extend(crc32fast::hash(&buf[1..]).to_be_bytes());
+        assert!(Filter::decode(&buf).is_err());
+    }
+}
+
+/// Testing: SSTs built with different filter policies can be read side by side,
+/// the policy is recorded in the table properties.
+#[test]
+fn test_task2_mixed_filter_policies() {
+    let dir = tempdir().unwrap();
+    let policies = [
+        FilterPolicy::Disabled,
+        FilterPolicy::Bloom { bits_per_key: 16 },
+        FilterPolicy::Xor8,
+    ];
+    for (id, policy) in policies.iter().enumerate() {
+        let mut builder = SsTableBuilder::new(128).with_filter_policy(policy.clone());
+        // even keys only, the odd ones fall inside the key range of the SST.
+        for idx in (0..1000).step_by(2) {
+            builder.add(
+                KeySlice::for_testing_from_slice_with_ts(&key_of(idx), 1),
+                b"value",
+            );
+        }
+        let path = dir.path().join(format!("{}.sst", id));
+        builder.build(id, None, &path).unwrap();
+
+        let sst = SsTable::open(id, None, FileObject::open(&path).unwrap()).unwrap();
+        assert_eq!(&sst.properties().filter_policy, policy);
+        assert!((0..1000)
+            .step_by(2)
+            .all(|idx| sst.may_contain_key(&key_of(idx)).unwrap()));
+        let false_positives = (1..999)
+            .step_by(2)
+            .filter(|idx| sst.may_contain_key(&key_of(*idx)).unwrap())
+            .count();
+        match policy {
+            FilterPolicy::Disabled => assert_eq!(false_positives, 499),
+            _ => assert!(false_positives < 20),
+        }
+    }
+}
+
+/// Testing: flushed SSTs use the level 0 policy, and the bottom level can go without filters.
+#[test]
+fn test_task3_filter_policy_per_level() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.filter_policy = FilterPolicy::Bloom { bits_per_key: 8 };
+    options.level_filter_policies = HashMap::from([(0, FilterPolicy::Xor8)]);
+    options.filter_bottommost_level = false;
+    assert_eq!(options.filter_policy_for(0, false), FilterPolicy::Xor8);
+    assert_eq!(
+        options.filter_policy_for(2, false),
+        FilterPolicy::Bloom { bits_per_key: 8 }
+    );
+    assert_eq!(options.filter_policy_for(2, true), FilterPolicy::Disabled);
+
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..100 {
+        storage.put(&key_of(idx), b"value").unwrap();
+    }
+    storage.force_flush().unwrap();
+    {
+        let state = storage.inner.state.read();
+        let sst = &state.sstables[&state.l0_sstables[0]];
+        assert_eq!(sst.properties().filter_policy, FilterPolicy::Xor8);
+    }
+
+    storage.force_full_compaction().unwrap();
+    {
+        let state = storage.inner.state.read();
+        assert!(state.l0_sstables.is_empty());
+        for id in state.levels[0].1.iter() {
+            assert_eq!(
+                state.sstables[id].properties().filter_policy,
+                FilterPolicy::Disabled
+            );
+        }
+    }
+    for idx in 0..100 {
+        assert_eq!(
+            storage.get(&key_of(idx)).unwrap(),
+            Some(Bytes::from_static(b"value"))
+        );
+    }
+    assert_eq!(storage.get(&key_of(100)).unwrap(), None);
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    iterators::StorageIterator,
+    lsm_storage::{BlockCache, LsmStorageOptions, MiniLsm},
+    table::SsTableIterator,
+};
+
+use super::harness::{generate_sst, key_of};
+
+/// Testing: the block cache capacity is a byte budget. Scanning an SST larger
+/// than the cache evicts blocks, and the counters follow the lookups.
+#[test]
+fn test_task1_block_cache_capacity_and_stats() {
+    let dir = tempdir().unwrap();
+    let data = (0..2000)
+        .map(|idx| (key_of(idx), Bytes::from(format!("value{:05}", idx))))
+        .collect::<Vec<_>>();
+    let block_cache = Arc::new(BlockCache::new(4096));
+    let sst = Arc::new(generate_sst(
+        1,
+        dir.path().join("1.sst"),
+        data,
+        Some(block_cache.clone()),
+    ));
+    assert!(sst.table_size() > 4 * 4096);
+
+    let scan = || {
+        let mut iter = SsTableIterator::create_and_seek_to_first(sst.clone()).unwrap();
+        while iter.is_valid() {
+            iter.next().unwrap();
+        }
+    };
+    scan();
+    block_cache.sync();
+    let stats = block_cache.stats();
+    assert!(stats.misses >= sst.num_of_blocks() as u64);
+    assert_eq!(stats.inserts, stats.misses);
+    assert!(stats.evictions > 0);
+    assert!(block_cache.usage() <= block_cache.capacity());
+    assert!(block_cache.entry_count() < sst.num_of_blocks() as u64);
+
+    // the first block and its index partition are read once, then stay cached.
+    let before = block_cache.stats();
+    for _ in 0..10 {
+        SsTableIterator::create_and_seek_to_first(sst.clone()).unwrap();
+    }
+    let after = block_cache.stats();
+    let misses = after.misses - before.misses;
+    assert!(misses <= 2);
+    assert_eq!(after.hits - before.hits, 20 - misses);
+}
+
+/// Testing: one block cache shared by two storage instances keeps their SSTs apart,
+/// although both number their SSTs from the same id.
+#[test]
+fn test_task2_shared_block_cache() {
+    let block_cache = Arc::new(BlockCache::new(1 << 20));
+    let dirs = [tempdir().unwrap(), tempdir().unwrap()];
+    let storages = dirs
+        .iter()
+        .enumerate()
+        .map(|(i, dir)| {
+            let mut options =
+                LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+            options.block_cache = Some(block_cache.clone());
+            let storage = MiniLsm::open(dir, options).unwrap();
+            for idx in 0..100 {
+                storage
+                    .put(&key_of(idx), format!("value{}_{}", i, idx).as_bytes())
+                    .unwrap();
+            }
+            storage.force_flush().unwrap();
+            storage
+        })
+        .collect::<Vec<_>>();
+
+    assert!(Arc::ptr_eq(
+        &storages[0].block_cache(),
+        &storages[1].block_cache()
+    ));
+    for _ in 0..2 {
+        for (i, storage) in storages.iter().enumerate() {
+            for idx in 0..100 {
+                assert_eq!(
+                    storage.get(&key_of(idx)).unwrap(),
+                    Some(Bytes::from(format!("value{}_{}", i, idx)))
+                );
+            }
+        }
+    }
+    let stats = block_cache.stats();
+    assert!(stats.hits > 0 && stats.misses > 0);
+    assert_eq!(stats.evictions, 0);
+}
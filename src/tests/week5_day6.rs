@@ -0,0 +1,200 @@
+use std::{ops::Bound, sync::Arc};
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::{CompactionOptions, LeveledCompactionOptions},
+    iterators::StorageIterator,
+    key::KeySlice,
+    lsm_storage::{BlockCache, LsmStorageOptions, MiniLsm},
+    table::SsTableIterator,
+};
+
+use super::harness::{generate_data, generate_sst, key_of};
+
+/// Testing: index and filter partitions go to the high priority pool, a scan
+/// streaming data blocks through the cache does not evict them.
+#[test]
+fn test_task1_high_priority_meta_blocks() {
+    let dir = tempdir().unwrap();
+    let block_cache = Arc::new(BlockCache::with_high_priority_ratio(64 << 10, 0.5));
+    let hot = generate_sst(
+        1,
+        dir.path().join("1.sst"),
+        generate_data(200),
+        Some(block_cache.clone()),
+    );
+    let cold = Arc::new(generate_sst(
+        2,
+        dir.path().join("2.sst"),
+        generate_data(2000),
+        Some(block_cache.clone()),
+    ));
+    let lookups = || {
+        for idx in 0..200 {
+            assert!(hot.may_contain_key(&key_of(idx)).unwrap());
+            hot.find_block_idx(KeySlice::for_testing_from_slice_no_ts(&key_of(idx)))
+                .unwrap();
+        }
+    };
+    lookups();
+    block_cache.sync();
+    assert!(block_cache.high_priority_usage() > 0);
+    assert_eq!(block_cache.usage(), block_cache.high_priority_usage());
+
+    let mut iter = SsTableIterator::create_and_seek_to_first(cold.clone()).unwrap();
+    while iter.is_valid() {
+        iter.next().unwrap();
+    }
+    block_cache.sync();
+    let stats = block_cache.stats();
+    assert!(stats.evictions > 0);
+    assert!(block_cache.usage() <= block_cache.capacity());
+
+    lookups();
+    assert_eq!(block_cache.stats().misses, stats.misses);
+}
+
+/// Testing: without caching index and filter blocks every SST holds its own partitions,
+/// the block cache only sees data blocks.
+#[test]
+fn test_task2_uncached_meta_blocks() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.cache_index_and_filter_blocks = false;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for (key, value) in generate_data(500) {
+        storage.put(&key, &value).unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.force_full_compaction().unwrap();
+    for (key, value) in generate_data(500) {
+        assert_eq!(storage.get(&key).unwrap(), Some(value));
+    }
+
+    let block_cache = storage.block_cache();
+    block_cache.sync();
+    assert!(block_cache.usage() > 0);
+    assert_eq!(block_cache.high_priority_usage(), 0);
+    let state = storage.inner.state.read();
+    assert!(!state.sstables.is_empty());
+    assert!(state.sstables.values().all(|sst| sst.meta_blocks_pinned()));
+}
+
+/// Testing: L0 SSTs pin their partitions, so repeated lookups skip the block cache;
+/// compaction outputs below L0 leave them to the cache.
+#[test]
+fn test_task3_pin_l0_meta_blocks() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.pin_l0_index_and_filter_blocks = true;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for (key, value) in generate_data(500) {
+        storage.put(&key, &value).unwrap();
+    }
+    storage.force_flush().unwrap();
+
+    let sst = {
+        let state = storage.inner.state.read();
+        state.sstables[&state.l0_sstables[0]].clone()
+    };
+    assert!(sst.meta_blocks_pinned());
+    let block_cache = storage.block_cache();
+    for idx in 0..1000 {
+        sst.may_contain_key(&key_of(idx)).unwrap();
+    }
+    // the pinned partitions are charged to the block cache while they are pinned.
+    block_cache.sync();
+    assert!(block_cache.reserved() > 0);
+    assert_eq!(block_cache.high_priority_usage(), 0);
+    let stats = block_cache.stats();
+    for idx in 0..1000 {
+        sst.may_contain_key(&key_of(idx)).unwrap();
+    }
+    assert_eq!(block_cache.stats(), stats);
+    drop(sst);
+
+    storage.force_full_compaction().unwrap();
+    assert_eq!(block_cache.reserved(), 0);
+    let state = storage.inner.state.read();
+    assert!(state.l0_sstables.is_empty());
+    assert!(state
+        .levels
+        .iter()
+        .flat_map(|(_, ssts)| ssts)
+        .all(|id| !state.sstables[id].meta_blocks_pinned()));
+}
+
+/// Testing: an L0 SST moved down to L1 as it is gives its pinned partitions back.
+#[test]
+fn test_task4_unpin_on_trivial_move() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::Leveled(
+        LeveledCompactionOptions {
+            level0_file_num_compaction_trigger: 100,
+            level_size_multiplier: 2,
+            base_level_size_mb: 128,
+            max_levels: 3,
+        },
+    ));
+    options.pin_l0_index_and_filter_blocks = true;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for (key, value) in generate_data(500) {
+        storage.put(&key, &value).unwrap();
+    }
+    storage.force_flush().unwrap();
+    let sst = {
+        let state = storage.inner.state.read();
+        state.sstables[&state.l0_sstables[0]].clone()
+    };
+    for idx in 0..500 {
+        assert_eq!(
+            storage.get(&key_of(idx)).unwrap(),
+            Some(Bytes::from(format!("value{:05}", idx)))
+        );
+    }
+    let block_cache = storage.block_cache();
+    assert!(block_cache.reserved() > 0);
+
+    storage
+        .compact_range(Bound::Unbounded, Bound::Unbounded, 1)
+        .unwrap();
+    assert_eq!(storage.inner.state.read().levels[0].1, vec![sst.sst_id()]);
+    assert!(!sst.meta_blocks_pinned());
+    assert_eq!(block_cache.reserved(), 0);
+    for idx in 0..500 {
+        sst.may_contain_key(&key_of(idx)).unwrap();
+    }
+    block_cache.sync();
+    assert!(block_cache.high_priority_usage() > 0);
+    assert_eq!(block_cache.reserved(), 0);
+}
+
+/// Testing: index and filter partitions the high priority pool has no room for
+/// overflow into the low priority pool instead of being dropped.
+#[test]
+fn test_task5_high_priority_overflow() {
+    let dir = tempdir().unwrap();
+    let block_cache = Arc::new(BlockCache::with_high_priority_ratio(64 << 10, 0.0));
+    let sst = generate_sst(
+        1,
+        dir.path().join("1.sst"),
+        generate_data(200),
+        Some(block_cache.clone()),
+    );
+    let lookups = || {
+        for idx in 0..200 {
+            assert!(sst.may_contain_key(&key_of(idx)).unwrap());
+        }
+    };
+    lookups();
+    block_cache.sync();
+    assert_eq!(block_cache.high_priority_usage(), 0);
+    assert!(block_cache.usage() > 0);
+    assert_eq!(block_cache.stats().evictions, 0);
+
+    let stats = block_cache.stats();
+    lookups();
+    assert_eq!(block_cache.stats().misses, stats.misses);
+}
@@ -0,0 +1,135 @@
+use std::{ops::Bound, sync::Arc};
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    key::KeySlice,
+    lsm_storage::{BlockCache, LsmStorageOptions, MiniLsm, ReadOptions},
+    table::{FileObject, SsTable, SsTableIterator},
+};
+
+use super::harness::{
+    check_iter_result_by_key, check_lsm_iter_result_by_key, generate_data, generate_sst, key_of,
+};
+
+/// Testing: a scan without `fill_cache` uses the blocks already cached but inserts none.
+#[test]
+fn test_task1_scan_without_fill_cache() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    let data = generate_data(1000);
+    for (key, value) in &data {
+        storage.put(key, value).unwrap();
+    }
+    storage.force_flush().unwrap();
+    let block_cache = storage.block_cache();
+
+    let bulk = ReadOptions {
+        fill_cache: false,
+        ..Default::default()
+    };
+    let inserts = block_cache.stats().inserts;
+    let mut iter = storage
+        .scan_with_options(&bulk, Bound::Unbounded, Bound::Unbounded)
+        .unwrap();
+    check_lsm_iter_result_by_key(&mut iter, data.clone());
+    assert_eq!(
+        storage.get_with_options(&bulk, b"key00500").unwrap(),
+        Some(Bytes::from("value00500"))
+    );
+    // only index and filter partitions were inserted.
+    block_cache.sync();
+    assert_eq!(block_cache.usage(), block_cache.high_priority_usage());
+    let after_bulk = block_cache.stats();
+    assert!(after_bulk.misses > 0);
+
+    let mut iter = storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap();
+    check_lsm_iter_result_by_key(&mut iter, data.clone());
+    block_cache.sync();
+    assert!(block_cache.usage() > block_cache.high_priority_usage());
+    assert!(block_cache.stats().inserts > inserts);
+
+    // the second bulk scan is served from the blocks cached by the normal one.
+    let before = block_cache.stats();
+    let mut iter = storage
+        .scan_with_options(&bulk, Bound::Unbounded, Bound::Unbounded)
+        .unwrap();
+    check_lsm_iter_result_by_key(&mut iter, data);
+    let after = block_cache.stats();
+    assert_eq!(after.misses, before.misses);
+    assert_eq!(after.inserts, before.inserts);
+}
+
+/// Testing: readahead returns the same entries, whether blocks come from the
+/// readahead buffer, the block cache or a fresh read after a seek.
+#[test]
+fn test_task2_readahead() {
+    let dir = tempdir().unwrap();
+    let data = generate_data(2000);
+    let block_cache = Arc::new(BlockCache::new(1 << 20));
+    let sst = Arc::new(generate_sst(
+        1,
+        dir.path().join("1.sst"),
+        data.clone(),
+        Some(block_cache.clone()),
+    ));
+    for readahead_bytes in [1, 100, 4096, 1 << 20] {
+        for fill_cache in [false, true] {
+            let options = ReadOptions {
+                fill_cache,
+                readahead_bytes,
+                ..Default::default()
+            };
+            let mut iter =
+                SsTableIterator::create_and_seek_to_first_with_options(sst.clone(), options)
+                    .unwrap();
+            check_iter_result_by_key(&mut iter, data.clone());
+            for idx in (0..2000).step_by(97).rev() {
+                let key = key_of(idx);
+                iter.seek_to_key(KeySlice::for_testing_from_slice_no_ts(&key))
+                    .unwrap();
+                check_iter_result_by_key(&mut iter, data[idx..].to_vec());
+            }
+        }
+    }
+}
+
+/// Testing: skipping checksum verification reads a block whose checksum is broken,
+/// without caching it for the readers that do verify checksums.
+#[test]
+fn test_task3_verify_checksums() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let data = generate_data(100);
+    let sst = generate_sst(1, &path, data.clone(), None);
+    let block_len = sst.read_block(0).unwrap().encode().len();
+    drop(sst);
+    let mut raw = std::fs::read(&path).unwrap();
+    raw[block_len] ^= 0xff;
+    std::fs::write(&path, raw).unwrap();
+
+    let block_cache = Arc::new(BlockCache::new(1 << 20));
+    let sst =
+        Arc::new(SsTable::open(1, Some(block_cache), FileObject::open(&path).unwrap()).unwrap());
+    let err = SsTableIterator::create_and_seek_to_first(sst.clone())
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("checksum"));
+    let options = ReadOptions {
+        verify_checksums: false,
+        ..Default::default()
+    };
+    let mut iter =
+        SsTableIterator::create_and_seek_to_first_with_options(sst.clone(), options).unwrap();
+    check_iter_result_by_key(&mut iter, data);
+    sst.read_blocks(&[0, 1], &options).unwrap();
+
+    let err = SsTableIterator::create_and_seek_to_first(sst.clone())
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("checksum"));
+    assert!(sst.read_blocks(&[0, 1], &ReadOptions::default()).is_err());
+}
@@ -0,0 +1,97 @@
+use std::{ops::Bound, sync::Arc};
+
+use tempfile::tempdir;
+
+use crate::{
+    block::BlockData,
+    compact::CompactionOptions,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    table::{FileObject, SsTable, SsTableIterator},
+};
+
+use super::harness::{
+    check_iter_result_by_key, check_lsm_iter_result_by_key, generate_data, generate_sst,
+};
+
+/// Testing: a memory-mapped SST serves its blocks straight from the mapping,
+/// and still verifies their checksums.
+#[test]
+fn test_task1_mmap_sst() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let data = generate_data(1000);
+    generate_sst(1, &path, data.clone(), None);
+
+    let sst = Arc::new(SsTable::open(1, None, FileObject::open_mmap(&path).unwrap()).unwrap());
+    assert!(sst.reader().unwrap().file.is_mmap());
+    let block = sst.read_block(0).unwrap();
+    assert!(matches!(block.data, BlockData::Mapped(_)));
+    let unmapped = SsTable::open(1, None, FileObject::open(&path).unwrap()).unwrap();
+    assert_eq!(block.encode(), unmapped.read_block(0).unwrap().encode());
+    let mut iter = SsTableIterator::create_and_seek_to_first(sst.clone()).unwrap();
+    check_iter_result_by_key(&mut iter, data);
+
+    // flip a checksum byte of the first block.
+    let block_len = block.encode().len();
+    drop((iter, block, sst, unmapped));
+    let mut raw = std::fs::read(&path).unwrap();
+    raw[block_len] ^= 0xff;
+    std::fs::write(&path, raw).unwrap();
+    let sst = SsTable::open(1, None, FileObject::open_mmap(&path).unwrap()).unwrap();
+    let err = sst.read_block(0).err().unwrap();
+    assert!(err.to_string().contains("checksum"));
+}
+
+/// Testing: reads out of a mapped file fail instead of panicking.
+#[test]
+fn test_task1_mmap_out_of_range() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    generate_sst(1, &path, generate_data(100), None);
+    let file = FileObject::open_mmap(&path).unwrap();
+    let size = file.size();
+    assert_eq!(file.read(size - 4, 4).unwrap().len(), 4);
+    assert!(file.read(size - 4, 5).is_err());
+    assert!(file.read(size + 1, 0).is_err());
+    assert!(file.read(u64::MAX, 2).is_err());
+}
+
+/// Testing: with `mmap_reads` every SST is mapped, whether it is flushed,
+/// written by a compaction or opened again on recovery.
+#[test]
+fn test_task2_mmap_reads_option() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.mmap_reads = true;
+    let data = generate_data(1000);
+    let check = |storage: &MiniLsm| {
+        let state = storage.inner.state.read().clone();
+        assert!(!state.sstables.is_empty());
+        assert!(state
+            .sstables
+            .values()
+            .all(|sst| sst.reader().unwrap().file.is_mmap()));
+        for (key, value) in data.iter().step_by(37) {
+            assert_eq!(storage.get(key).unwrap(), Some(value.clone()));
+        }
+        let mut iter = storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap();
+        check_lsm_iter_result_by_key(&mut iter, data.clone());
+    };
+
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for (key, value) in &data[..500] {
+        storage.put(key, value).unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.force_full_compaction().unwrap();
+    for (key, value) in &data[500..] {
+        storage.put(key, value).unwrap();
+    }
+    storage.force_flush().unwrap();
+    check(&storage);
+    storage.close().unwrap();
+    drop(storage);
+
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    check(&storage);
+}
@@ -0,0 +1,182 @@
+use std::{ops::Bound, sync::Arc};
+
+use tempfile::tempdir;
+
+use crate::{
+    block_cache::BlockCache,
+    compact::CompactionOptions,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm, ReadOptions},
+    table::{FileObject, SsTable, SsTableBuilder, SsTableIterator},
+};
+
+use super::harness::{
+    check_iter_result_by_key, check_lsm_iter_result_by_key, generate_data, generate_sst, key_of,
+};
+
+/// Testing: batched reads return the same bytes as one read at a time,
+/// whether they go through io_uring, O_DIRECT or the plain pread path.
+#[test]
+fn test_task1_read_many() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let data = (0..100_000u32)
+        .flat_map(u32::to_le_bytes)
+        .collect::<Vec<_>>();
+    let file = FileObject::create(&path, data.clone()).unwrap();
+    // more ranges than the io_uring queue depth, unaligned and up to the end of the file.
+    let reads = (0..150u64)
+        .map(|i| (i * 2651 % 390_000, 1 + i * 37 % 9000))
+        .chain([(data.len() as u64 - 10, 10)])
+        .collect::<Vec<_>>();
+    for (async_io, direct) in [(false, false), (true, false), (false, true), (true, true)] {
+        let bufs = file.read_many(&reads, async_io, direct).unwrap();
+        assert_eq!(bufs.len(), reads.len());
+        for (&(offset, len), buf) in reads.iter().zip(bufs) {
+            assert_eq!(buf, &data[offset as usize..(offset + len) as usize]);
+        }
+    }
+    assert!(file
+        .read_many(&[(data.len() as u64, 10)], true, false)
+        .is_err());
+}
+
+/// Testing: an SST written with O_DIRECT holds the same blocks as a buffered one,
+/// and reads back the same through direct and buffered reads.
+#[test]
+fn test_task2_direct_io_sst() {
+    let dir = tempdir().unwrap();
+    let data = generate_data(1000);
+    let mut builder = SsTableBuilder::new(128).with_direct_io(true);
+    for (key, value) in &data {
+        builder.add(KeySlice::for_testing_from_slice_no_ts(key), value);
+    }
+    let path = dir.path().join("1.sst");
+    let sst = Arc::new(builder.build(1, None, &path).unwrap());
+    let buffered = generate_sst(2, dir.path().join("2.sst"), data.clone(), None);
+    // the files only differ in the creation time of their properties.
+    let num_blocks = sst.num_of_blocks();
+    assert_eq!(num_blocks, buffered.num_of_blocks());
+    for block_idx in 0..num_blocks {
+        assert_eq!(
+            sst.read_block(block_idx).unwrap().encode(),
+            buffered.read_block(block_idx).unwrap().encode()
+        );
+    }
+    let mut properties = sst.properties().clone();
+    properties.creation_time = buffered.properties().creation_time;
+    assert_eq!(&properties, buffered.properties());
+    assert_eq!(sst.table_size(), std::fs::metadata(&path).unwrap().len());
+
+    let options = ReadOptions {
+        direct_io: true,
+        ..Default::default()
+    };
+    for readahead_bytes in [0, 1000] {
+        let options = ReadOptions {
+            readahead_bytes,
+            ..options
+        };
+        let mut iter =
+            SsTableIterator::create_and_seek_to_first_with_options(sst.clone(), options).unwrap();
+        check_iter_result_by_key(&mut iter, data.clone());
+    }
+}
+
+/// Testing: iterators prefetching with async I/O and `read_blocks` see the same blocks
+/// as synchronous reads, and only `read_blocks` fills the cache with data blocks here.
+#[test]
+fn test_task3_async_io_reads() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("1.sst");
+    let data = generate_data(1000);
+    let block_cache = Arc::new(BlockCache::new(1 << 20));
+    generate_sst(1, &path, data.clone(), None);
+    let sst = Arc::new(
+        SsTable::open(
+            1,
+            Some(block_cache.clone()),
+            FileObject::open(&path).unwrap(),
+        )
+        .unwrap(),
+    );
+    assert!(sst.num_of_blocks() > 10);
+
+    for readahead_bytes in [0, 1000] {
+        let options = ReadOptions {
+            async_io: true,
+            fill_cache: false,
+            readahead_bytes,
+            ..Default::default()
+        };
+        let mut iter =
+            SsTableIterator::create_and_seek_to_first_with_options(sst.clone(), options).unwrap();
+        check_iter_result_by_key(&mut iter, data.clone());
+        let mut iter = SsTableIterator::create_and_seek_to_key_with_options(
+            sst.clone(),
+            KeySlice::for_testing_from_slice_no_ts(&key_of(500)),
+            options,
+        )
+        .unwrap();
+        check_iter_result_by_key(&mut iter, data[500..].to_vec());
+    }
+    block_cache.sync();
+    assert_eq!(block_cache.usage(), block_cache.high_priority_usage());
+
+    let block_idxs = [7, 0, 3, 7, sst.num_of_blocks() - 1];
+    let options = ReadOptions {
+        async_io: true,
+        ..Default::default()
+    };
+    let blocks = sst.read_blocks(&block_idxs, &options).unwrap();
+    for (&block_idx, block) in block_idxs.iter().zip(&blocks) {
+        assert_eq!(block.encode(), sst.read_block(block_idx).unwrap().encode());
+    }
+    block_cache.sync();
+    assert!(block_cache.usage() > block_cache.high_priority_usage());
+    let inserts = block_cache.stats().inserts;
+    sst.read_blocks(&block_idxs, &options).unwrap();
+    assert_eq!(block_cache.stats().inserts, inserts);
+}
+
+/// Testing: a storage compacting with direct I/O reads its data back unchanged,
+/// and its compactions keep data blocks out of the block cache.
+#[test]
+fn test_task4_direct_io_compaction() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.use_direct_io_for_compaction = true;
+    let data = generate_data(1000);
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for chunk in data.chunks(250) {
+        for (key, value) in chunk {
+            storage.put(key, value).unwrap();
+        }
+        storage.force_flush().unwrap();
+    }
+    let block_cache = storage.block_cache();
+    storage.force_full_compaction().unwrap();
+    block_cache.sync();
+    assert_eq!(block_cache.usage(), block_cache.high_priority_usage());
+
+    let check = |storage: &MiniLsm| {
+        for (key, value) in data.iter().step_by(37) {
+            assert_eq!(storage.get(key).unwrap(), Some(value.clone()));
+        }
+        let mut iter = storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap();
+        check_lsm_iter_result_by_key(&mut iter, data.clone());
+        let options = ReadOptions {
+            async_io: true,
+            ..Default::default()
+        };
+        let mut iter = storage
+            .scan_with_options(&options, Bound::Unbounded, Bound::Unbounded)
+            .unwrap();
+        check_lsm_iter_result_by_key(&mut iter, data.clone());
+    };
+    check(&storage);
+    storage.close().unwrap();
+    drop(storage);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    check(&storage);
+}
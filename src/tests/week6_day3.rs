@@ -0,0 +1,153 @@
+use std::{ops::Bound, sync::Arc};
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    block_cache::BlockCache,
+    compact::CompactionOptions,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    table::SsTable,
+    table_cache::TableCache,
+};
+
+use super::harness::{check_lsm_iter_result_by_key, generate_data, generate_sst, key_of};
+
+/// Testing: a lazy SST opens its file on first read only, and the table cache
+/// closes the least recently used file to stay within its capacity.
+#[test]
+fn test_task1_table_cache_lru() {
+    let dir = tempdir().unwrap();
+    let block_cache = Arc::new(BlockCache::new(1 << 20));
+    let table_cache = Arc::new(TableCache::new(&dir, 2, block_cache));
+    let data = generate_data(100);
+    let ssts = (1..=3)
+        .map(|id| {
+            let path = dir.path().join(format!("{:05}.sst", id));
+            let sst = generate_sst(id, path, data.clone(), None);
+            SsTable::open_lazy(
+                id,
+                sst.first_key().clone(),
+                sst.last_key().clone(),
+                sst.table_size(),
+                sst.num_of_blocks(),
+                sst.properties().clone(),
+                table_cache.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(table_cache.open_count(), 0);
+    assert!(table_cache.is_empty());
+
+    ssts[0].read_block(0).unwrap();
+    ssts[1].read_block(0).unwrap();
+    ssts[0].read_block(1).unwrap();
+    assert_eq!(table_cache.open_count(), 2);
+    // the third table closes the second, the least recently used.
+    ssts[2].read_block(0).unwrap();
+    assert_eq!(table_cache.len(), 2);
+    ssts[0].read_block(0).unwrap();
+    assert_eq!(table_cache.open_count(), 3);
+    ssts[1].read_block(0).unwrap();
+    assert_eq!(table_cache.open_count(), 4);
+    assert_eq!(table_cache.len(), 2);
+    assert_eq!(ssts[1].num_of_blocks(), ssts[2].num_of_blocks());
+}
+
+/// Testing: with `max_open_files`, recovery opens no SST until it is read,
+/// and the storage never keeps more files open than allowed.
+#[test]
+fn test_task2_max_open_files() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.max_open_files = Some(3);
+    let data = generate_data(1000);
+    let check = |storage: &MiniLsm| {
+        for (key, value) in data.iter().step_by(37) {
+            assert_eq!(storage.get(key).unwrap(), Some(value.clone()));
+        }
+        let mut iter = storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap();
+        check_lsm_iter_result_by_key(&mut iter, data.clone());
+        drop(iter);
+        let table_cache = storage.inner.table_cache.as_ref().unwrap();
+        assert!(table_cache.len() <= 3);
+    };
+
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for chunk in data.chunks(100) {
+        for (key, value) in chunk {
+            storage.put(key, value).unwrap();
+        }
+        storage.force_flush().unwrap();
+    }
+    assert_eq!(storage.inner.state.read().l0_sstables.len(), 10);
+    check(&storage);
+    storage.close().unwrap();
+    drop(storage);
+
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    let table_cache = storage.inner.table_cache.clone().unwrap();
+    assert_eq!(table_cache.open_count(), 0);
+    check(&storage);
+    assert!(table_cache.open_count() >= 10);
+
+    // compaction outputs go through the table cache, and its inputs leave it.
+    storage.force_full_compaction().unwrap();
+    assert!(table_cache.len() <= 3);
+    check(&storage);
+    storage.close().unwrap();
+    drop(storage);
+
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    assert_eq!(storage.inner.table_cache.as_ref().unwrap().open_count(), 0);
+    check(&storage);
+}
+
+/// Testing: with a single open file, a scan keeps reading the SSTs a compaction replaced
+/// after it started; their files are only deleted once the scan is done.
+#[test]
+fn test_task3_scan_across_compaction() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.max_open_files = Some(1);
+    options.block_size = 256;
+    options.target_sst_size = 1024;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    // the small target size freezes memtables as they fill, flush all of them.
+    let flush_all = |storage: &MiniLsm| {
+        storage.force_flush().unwrap();
+        while !storage.inner.state.read().imm_memtables.is_empty() {
+            storage.inner.force_flush_next_imm_memtable().unwrap();
+        }
+    };
+    let data = generate_data(1000);
+    for (key, value) in &data {
+        storage.put(key, value).unwrap();
+    }
+    flush_all(&storage);
+    storage.force_full_compaction().unwrap();
+    assert!(storage.inner.state.read().levels[0].1.len() > 1);
+
+    let mut iter = storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap();
+    for (key, _) in data.iter().step_by(2) {
+        storage.put(key, b"new").unwrap();
+    }
+    flush_all(&storage);
+    storage.force_full_compaction().unwrap();
+    check_lsm_iter_result_by_key(&mut iter, data.clone());
+    drop(iter);
+
+    let sst_files = std::fs::read_dir(&dir)
+        .unwrap()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .unwrap()
+                .path()
+                .extension()
+                .is_some_and(|ext| ext == "sst")
+        })
+        .count();
+    assert_eq!(sst_files, storage.inner.state.read().sstables.len());
+    assert_eq!(storage.get(&key_of(0)).unwrap(), Some(Bytes::from("new")));
+}
@@ -0,0 +1,133 @@
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    lsm_storage::{LsmStorageOptions, MiniLsm, ReadOptions},
+};
+
+use super::harness::key_of;
+
+/// Testing: `multi_get` returns what `get` does for every key, in the order asked,
+/// whether the key lives in a memtable, in L0, in a level, or nowhere.
+#[test]
+fn test_task1_multi_get_matches_get() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..1000 {
+        storage
+            .put(&key_of(idx), format!("v1_{}", idx).as_bytes())
+            .unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.force_full_compaction().unwrap();
+    // newer versions and tombstones in L0 hide the older ones in L1.
+    for idx in (0..1000).step_by(3) {
+        storage
+            .put(&key_of(idx), format!("v2_{}", idx).as_bytes())
+            .unwrap();
+    }
+    for idx in (0..1000).step_by(7) {
+        storage.delete(&key_of(idx)).unwrap();
+    }
+    storage.force_flush().unwrap();
+    for idx in (0..1000).step_by(5) {
+        storage
+            .put(&key_of(idx), format!("v3_{}", idx).as_bytes())
+            .unwrap();
+    }
+    for idx in (0..1000).step_by(11) {
+        storage.delete(&key_of(idx)).unwrap();
+    }
+
+    // unsorted, with duplicates and keys that were never written.
+    let keys = (0..1200)
+        .rev()
+        .step_by(2)
+        .chain([4, 4, 999, 0])
+        .map(key_of)
+        .collect::<Vec<_>>();
+    let keys = keys.iter().map(|key| &key[..]).collect::<Vec<_>>();
+    let expected = keys
+        .iter()
+        .map(|key| storage.get(key).unwrap())
+        .collect::<Vec<_>>();
+    assert!(expected.iter().any(Option::is_none));
+    assert_eq!(storage.multi_get(&keys).unwrap(), expected);
+    let options = ReadOptions {
+        async_io: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        storage.multi_get_with_options(&options, &keys).unwrap(),
+        expected
+    );
+    assert!(storage.multi_get(&[]).unwrap().is_empty());
+}
+
+/// Testing: keys living in the same block share one read of it.
+#[test]
+fn test_task2_multi_get_shares_blocks() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    for idx in 0..1000 {
+        storage
+            .put(&key_of(idx), format!("value{:05}", idx).as_bytes())
+            .unwrap();
+    }
+    storage.force_flush().unwrap();
+    let block_cache = storage.block_cache();
+
+    let keys = (0..200).map(key_of).collect::<Vec<_>>();
+    let keys = keys.iter().map(|key| &key[..]).collect::<Vec<_>>();
+    let inserts = block_cache.stats().inserts;
+    let values = storage.multi_get(&keys).unwrap();
+    for (idx, value) in values.into_iter().enumerate() {
+        assert_eq!(value.unwrap(), format!("value{:05}", idx).as_bytes());
+    }
+    let inserted = block_cache.stats().inserts - inserts;
+    assert!(
+        inserted > 0 && inserted < 20,
+        "{} blocks inserted",
+        inserted
+    );
+    // every block is cached now.
+    let inserts = block_cache.stats().inserts;
+    storage.multi_get(&keys).unwrap();
+    assert_eq!(block_cache.stats().inserts, inserts);
+}
+
+/// Testing: a transaction's `multi_get` sees its own writes and deletes.
+#[test]
+fn test_task3_txn_multi_get() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    storage.put(b"a", b"1").unwrap();
+    storage.put(b"b", b"1").unwrap();
+    storage.force_flush().unwrap();
+    let txn = storage.new_txn().unwrap();
+    txn.put(b"b", b"2");
+    txn.delete(b"a");
+    txn.put(b"c", b"2");
+    // written after the transaction started, so not visible to it.
+    storage.put(b"d", b"1").unwrap();
+    assert_eq!(
+        txn.multi_get(&[b"d", b"c", b"b", b"a"]).unwrap(),
+        vec![
+            None,
+            Some(Bytes::from_static(b"2")),
+            Some(Bytes::from_static(b"2")),
+            None
+        ]
+    );
+    assert_eq!(
+        storage.multi_get(&[b"a", b"d"]).unwrap(),
+        vec![
+            Some(Bytes::from_static(b"1")),
+            Some(Bytes::from_static(b"1"))
+        ]
+    );
+}
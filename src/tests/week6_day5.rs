@@ -0,0 +1,75 @@
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+};
+
+/// Testing: a point lookup finds the version visible at every timestamp, even when the
+/// versions of a key span several blocks, and stops at the newest source holding one.
+#[test]
+fn test_task1_point_lookup_versions() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.block_size = 64;
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    storage.put(b"a", b"a").unwrap();
+    storage.put(b"c", b"c").unwrap();
+    // a transaction keeps the old versions from being compacted away.
+    let _txn = storage.new_txn().unwrap();
+    let begin_ts = storage.inner.mvcc().latest_commit_ts();
+    for idx in 0..100 {
+        if idx % 10 == 9 {
+            storage.delete(b"b").unwrap();
+        } else {
+            storage
+                .put(b"b", format!("{:030}", idx).as_bytes())
+                .unwrap();
+        }
+    }
+    let expected = |ts: u64| {
+        let idx = (ts - begin_ts) as usize;
+        (idx > 0 && !idx.is_multiple_of(10)).then(|| Bytes::from(format!("{:030}", idx - 1)))
+    };
+    let check = |storage: &MiniLsm| {
+        for ts in begin_ts..=begin_ts + 100 {
+            assert_eq!(
+                storage.inner.get_with_ts(b"b", ts).unwrap(),
+                expected(ts),
+                "at ts {}",
+                ts
+            );
+            assert_eq!(
+                storage.inner.get_with_ts(b"a", ts).unwrap(),
+                Some(Bytes::from_static(b"a"))
+            );
+            assert_eq!(storage.inner.get_with_ts(b"bb", ts).unwrap(), None);
+        }
+    };
+    check(&storage);
+    storage.force_flush().unwrap();
+    let sst = storage
+        .inner
+        .state
+        .read()
+        .sstables
+        .values()
+        .next()
+        .unwrap()
+        .clone();
+    assert!(sst.num_of_blocks() > 10);
+    check(&storage);
+    storage.force_full_compaction().unwrap();
+    check(&storage);
+
+    // a newer version in a memtable hides the versions in L1.
+    storage.put(b"b", b"new").unwrap();
+    assert_eq!(storage.get(b"b").unwrap(), Some(Bytes::from_static(b"new")));
+    storage.force_flush().unwrap();
+    storage.delete(b"a").unwrap();
+    assert_eq!(storage.get(b"b").unwrap(), Some(Bytes::from_static(b"new")));
+    assert_eq!(storage.get(b"a").unwrap(), None);
+    assert_eq!(storage.get(b"c").unwrap(), Some(Bytes::from_static(b"c")));
+    check(&storage);
+}
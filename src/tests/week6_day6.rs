@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+};
+
+use super::harness::key_of;
+
+/// Testing: readers never wait for a writer preparing the next version of the state,
+/// and see the new version only once it is installed.
+#[test]
+fn test_task1_readers_do_not_block() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    storage.put(b"a", b"1").unwrap();
+    storage.force_flush().unwrap();
+    let old = storage.inner.state.read();
+
+    let mut guard = storage.inner.state.write();
+    assert_eq!(storage.get(b"a").unwrap(), Some(Bytes::from_static(b"1")));
+    let mut snapshot = guard.as_ref().clone();
+    snapshot.l0_sstables.clear();
+    *guard = Arc::new(snapshot);
+    assert_eq!(storage.inner.state.read().l0_sstables.len(), 1);
+    drop(guard);
+    assert!(storage.inner.state.read().l0_sstables.is_empty());
+    assert_eq!(storage.get(b"a").unwrap(), None);
+    // an old version stays readable as long as it is held.
+    assert_eq!(old.l0_sstables.len(), 1);
+}
+
+/// Testing: writes racing with freezes and flushes are never lost.
+#[test]
+fn test_task2_concurrent_writes_and_flushes() {
+    let dir = tempdir().unwrap();
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    std::thread::scope(|s| {
+        for thread in 0..4 {
+            let storage = &storage;
+            s.spawn(move || {
+                for idx in (thread..2000).step_by(4) {
+                    storage
+                        .put(&key_of(idx), format!("value{:05}", idx).as_bytes())
+                        .unwrap();
+                    if idx % 100 == 0 {
+                        assert!(storage.get(&key_of(idx)).unwrap().is_some());
+                    }
+                }
+            });
+        }
+        s.spawn(|| {
+            for _ in 0..20 {
+                storage.force_flush().unwrap();
+                std::thread::yield_now();
+            }
+        });
+    });
+    while !storage.inner.state.read().imm_memtables.is_empty() {
+        storage.force_flush().unwrap();
+    }
+    storage.force_flush().unwrap();
+    assert!(storage.inner.state.read().memtable.is_empty());
+    for idx in 0..2000 {
+        assert_eq!(
+            storage.get(&key_of(idx)).unwrap(),
+            Some(Bytes::from(format!("value{:05}", idx)))
+        );
+    }
+}
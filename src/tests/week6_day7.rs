@@ -0,0 +1,94 @@
+use std::{sync::mpsc, time::Duration};
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    lsm_storage::{LsmStorageOptions, MiniLsm, WriteBatchRecord},
+    mvcc::LsmMvccInner,
+};
+
+/// Testing: a commit finishing its inserts before an older one waits for it, and
+/// commit timestamps are published in order.
+#[test]
+fn test_task1_commits_published_in_order() {
+    let mvcc = LsmMvccInner::new(10);
+    let timestamps = (0..3).map(|_| mvcc.pipeline.allocate()).collect::<Vec<_>>();
+    assert_eq!(timestamps, vec![11, 12, 13]);
+    std::thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        for &commit_ts in timestamps[1..].iter().rev() {
+            let (mvcc, tx) = (&mvcc, tx.clone());
+            s.spawn(move || {
+                mvcc.finish_commit(commit_ts);
+                tx.send(commit_ts).unwrap();
+            });
+        }
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(mvcc.latest_commit_ts(), 10);
+        mvcc.finish_commit(11);
+        let mut done = vec![rx.recv().unwrap(), rx.recv().unwrap()];
+        done.sort();
+        assert_eq!(done, vec![12, 13]);
+    });
+    assert_eq!(mvcc.latest_commit_ts(), 13);
+    mvcc.pipeline.wait_idle();
+}
+
+/// Testing: concurrent pipelined writers lose no write, readers never see half a batch,
+/// and the WAL recovers every batch.
+#[test]
+fn test_task2_pipelined_writes() {
+    let dir = tempdir().unwrap();
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.enable_pipelined_write = true;
+    options.enable_wal = true;
+    options.target_sst_size = 16 << 10;
+    options.num_memtable_limit = 100;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    std::thread::scope(|s| {
+        for thread in 0..4 {
+            let storage = &storage;
+            s.spawn(move || {
+                for round in 0..300 {
+                    let value = format!("{}", round);
+                    storage
+                        .write_batch(&[
+                            WriteBatchRecord::Put(format!("x{}", thread), value.clone()),
+                            WriteBatchRecord::Put(format!("y{}", thread), value),
+                        ])
+                        .unwrap();
+                }
+            });
+        }
+        s.spawn(|| {
+            for _ in 0..300 {
+                let txn = storage.new_txn().unwrap();
+                for thread in 0..4 {
+                    assert_eq!(
+                        txn.get(format!("x{}", thread).as_bytes()).unwrap(),
+                        txn.get(format!("y{}", thread).as_bytes()).unwrap()
+                    );
+                }
+            }
+        });
+    });
+    assert!(!storage.inner.state.read().imm_memtables.is_empty());
+    let check = |storage: &MiniLsm| {
+        for thread in 0..4 {
+            for key in [format!("x{}", thread), format!("y{}", thread)] {
+                assert_eq!(
+                    storage.get(key.as_bytes()).unwrap(),
+                    Some(Bytes::from_static(b"299"))
+                );
+            }
+        }
+    };
+    check(&storage);
+    storage.close().unwrap();
+    drop(storage);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    check(&storage);
+}
@@ -0,0 +1,170 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tempfile::tempdir;
+
+use crate::{
+    block::builder::BlockBuilder,
+    block_cache::{BlockCache, CachedBlock},
+    compact::CompactionOptions,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    write_buffer_manager::WriteBufferManager,
+};
+
+use super::harness::key_of;
+
+fn wait_until(cond: impl Fn() -> bool) {
+    let start = Instant::now();
+    while !cond() {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn options_with(manager: &Arc<WriteBufferManager>) -> LsmStorageOptions {
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.target_sst_size = 16 << 20;
+    options.num_memtable_limit = 100;
+    options.write_buffer_manager = Some(manager.clone());
+    options
+}
+
+/// Testing: once the memtables of all instances pass the budget, the instance holding
+/// the most memtable memory flushes, even when another one is being written.
+#[test]
+fn test_task1_flush_largest_instance() {
+    let (dir1, dir2) = (tempdir().unwrap(), tempdir().unwrap());
+    let manager = Arc::new(WriteBufferManager::new(128 << 10));
+    let storage1 = MiniLsm::open(&dir1, options_with(&manager)).unwrap();
+    let storage2 = MiniLsm::open(&dir2, options_with(&manager)).unwrap();
+    let value = vec![b'v'; 100];
+    for idx in 0..400 {
+        storage1.put(&key_of(idx), &value).unwrap();
+    }
+    for idx in 0..100 {
+        storage2.put(&key_of(idx), &value).unwrap();
+    }
+    assert!(manager.memory_usage() < manager.buffer_size());
+    // the write over the budget goes to the second instance, the first one flushes.
+    for idx in 100..300 {
+        storage2.put(&key_of(idx), &value).unwrap();
+    }
+    wait_until(|| !storage1.inner.state.read().l0_sstables.is_empty());
+    wait_until(|| manager.memory_usage() < manager.buffer_size());
+    assert!(storage2.inner.state.read().l0_sstables.is_empty());
+    for idx in 0..400 {
+        assert_eq!(storage1.get(&key_of(idx)).unwrap().unwrap(), &value[..]);
+    }
+
+    // a closed instance gives its memtable memory back.
+    let usage = manager.memory_usage();
+    assert!(usage > 0);
+    storage2.close().unwrap();
+    drop(storage2);
+    assert!(manager.memory_usage() < usage);
+}
+
+/// Testing: charged against a block cache, memtable memory evicts cached blocks.
+#[test]
+fn test_task2_charge_block_cache() {
+    let dir = tempdir().unwrap();
+    let block_cache = Arc::new(BlockCache::new(1 << 20));
+    let manager = Arc::new(WriteBufferManager::with_block_cache(
+        4 << 20,
+        block_cache.clone(),
+    ));
+    let mut options = options_with(&manager);
+    options.block_cache = Some(block_cache.clone());
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    let value = vec![b'v'; 1000];
+    for idx in 0..800 {
+        storage.put(&key_of(idx), &value).unwrap();
+    }
+    storage.force_flush().unwrap();
+    assert_eq!(block_cache.reserved(), 0);
+    for idx in 0..800 {
+        storage.get(&key_of(idx)).unwrap();
+    }
+    block_cache.sync();
+    let cached = block_cache.usage();
+    assert!(cached > 512 << 10);
+
+    for idx in 0..600 {
+        storage.put(&key_of(idx), &value).unwrap();
+    }
+    assert!(block_cache.reserved() >= manager.memory_usage() as u64);
+    block_cache.sync();
+    assert!(block_cache.usage() + block_cache.reserved() <= block_cache.capacity());
+    assert!(block_cache.usage() < cached);
+    storage.force_flush().unwrap();
+    assert_eq!(block_cache.reserved(), 0);
+}
+
+/// Testing: memtables recovered from the WAL are charged again, and flushing them gives
+/// the memory back.
+#[test]
+fn test_task3_charge_recovered_memtables() {
+    let dir = tempdir().unwrap();
+    let manager = Arc::new(WriteBufferManager::new(16 << 20));
+    let mut options = options_with(&manager);
+    options.enable_wal = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    let value = vec![b'v'; 100];
+    for idx in 0..100 {
+        storage.put(&key_of(idx), &value).unwrap();
+    }
+    storage.close().unwrap();
+    drop(storage);
+    assert_eq!(manager.memory_usage(), 0);
+
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    let usage = manager.memory_usage();
+    assert!(usage > 0);
+    assert_eq!(storage.inner.write_buffer_usage(), usage);
+    storage.force_flush().unwrap();
+    assert!(storage.inner.state.read().imm_memtables.is_empty());
+    assert_eq!(manager.memory_usage(), 0);
+    assert_eq!(storage.inner.write_buffer_usage(), 0);
+    assert_eq!(storage.get(&key_of(7)).unwrap().unwrap(), &value[..]);
+}
+
+/// Testing: a reservation evicts the least recently used blocks first.
+#[test]
+fn test_task4_reserve_evicts_lru() {
+    let block_cache = BlockCache::with_high_priority_ratio(64 << 10, 0.0);
+    let table_id = block_cache.new_table_id();
+    for offset in 0..16 {
+        let mut builder = BlockBuilder::new(4096);
+        assert!(builder.add(
+            KeySlice::for_testing_from_slice_no_ts(b"key"),
+            &[b'v'; 3500],
+        ));
+        let block = CachedBlock::Data(Arc::new(builder.build()));
+        block_cache.insert((table_id, offset), false, block);
+    }
+    block_cache.sync();
+    assert_eq!(block_cache.entry_count(), 16);
+    for offset in 0..4 {
+        assert!(block_cache.get((table_id, offset), false).is_some());
+    }
+
+    block_cache.reserve(32 << 10);
+    block_cache.sync();
+    assert!(block_cache.usage() + block_cache.reserved() <= block_cache.capacity());
+    for offset in 0..4 {
+        assert!(block_cache.get((table_id, offset), false).is_some());
+    }
+    assert!(block_cache.get((table_id, 4), false).is_none());
+    assert!(block_cache.get((table_id, 15), false).is_some());
+
+    block_cache.release(32 << 10);
+    assert_eq!(block_cache.reserved(), 0);
+    block_cache.sync();
+    assert_eq!(
+        block_cache.entry_count(),
+        block_cache.stats().inserts - block_cache.stats().evictions
+    );
+}
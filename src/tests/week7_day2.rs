@@ -0,0 +1,101 @@
+use std::{ops::Bound, sync::Arc};
+
+use bytes::Bytes;
+
+use crate::{
+    key::{KeyBytes, KeySlice},
+    mem_table::{ArenaSkipList, MemTable, MemTableRep, MemTableRepKind},
+};
+
+use super::harness::key_of;
+
+/// Testing: concurrent inserts into the arena skiplist keep every entry, ordered by key
+/// then by descending timestamp, and a second insert of a key replaces its value.
+#[test]
+fn test_task1_arena_concurrent_inserts() {
+    let list = Arc::new(ArenaSkipList::new());
+    assert!(list.is_empty());
+    std::thread::scope(|s| {
+        for thread in 0..4 {
+            let list = &list;
+            s.spawn(move || {
+                for idx in (thread..2000).step_by(4) {
+                    for ts in [1, 2] {
+                        let value = format!("value{}@{}", idx, ts);
+                        list.insert(KeySlice::from_slice(&key_of(idx), ts), value.as_bytes());
+                    }
+                }
+            });
+        }
+    });
+    list.insert(KeySlice::from_slice(&key_of(7), 2), b"replaced");
+
+    let entries = list
+        .clone()
+        .scan(Bound::Unbounded, Bound::Unbounded)
+        .collect::<Vec<_>>();
+    assert_eq!(entries.len(), 4000);
+    for (idx, (key, value)) in entries.iter().enumerate() {
+        let (user_key, ts) = (key_of(idx / 2), 2 - idx as u64 % 2);
+        assert_eq!(key.key_ref(), &user_key[..]);
+        assert_eq!(key.ts(), ts);
+        if (idx / 2, ts) != (7, 2) {
+            assert_eq!(value, &format!("value{}@{}", idx / 2, ts));
+        }
+    }
+    assert_eq!(
+        list.get(KeySlice::from_slice(&key_of(7), 5)).unwrap(),
+        (2, Bytes::from_static(b"replaced"))
+    );
+    assert!(list.get(KeySlice::from_slice(&key_of(1999), 0)).is_none());
+
+    let bound = |idx, ts| KeyBytes::from_bytes_with_ts(key_of(idx), ts);
+    let range = list
+        .clone()
+        .scan(Bound::Excluded(bound(10, 2)), Bound::Included(bound(12, 2)))
+        .map(|(key, _)| (key.key_ref().to_vec(), key.ts()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        range,
+        vec![
+            (key_of(10).to_vec(), 1),
+            (key_of(11).to_vec(), 2),
+            (key_of(11).to_vec(), 1),
+            (key_of(12).to_vec(), 2),
+        ]
+    );
+}
+
+/// Testing: the memtable size is the memory of the arena blocks, close to the raw size of
+/// the entries, with a block of its own for a large value.
+#[test]
+fn test_task2_accurate_accounting() {
+    let memtable = MemTable::create_with_rep(0, MemTableRepKind::ArenaSkipList);
+    let mut raw_size = 0;
+    for idx in 0..1000 {
+        let user_key = key_of(idx);
+        let key = KeySlice::from_slice(&user_key, 1);
+        let value = vec![b'v'; 1 + idx % 200];
+        raw_size += key.raw_len() + value.len();
+        memtable.put(key, &value).unwrap();
+    }
+    let size = memtable.approximate_size();
+    assert!(size > raw_size);
+    assert!(size < raw_size + 1000 * 64 + (64 << 10));
+    // the unused tail of every block given up is charged too.
+    assert_eq!(size % (64 << 10), 0);
+
+    let large = vec![b'v'; 1 << 20];
+    let key = KeySlice::from_slice(b"large", 1);
+    raw_size += key.raw_len() + large.len();
+    assert!(memtable.put(key, &large).unwrap() > large.len());
+
+    let size = memtable.approximate_size();
+    assert!(size > raw_size);
+    assert!(size < raw_size + 1000 * 64 + 2 * (64 << 10));
+    assert_eq!(memtable.get(key).unwrap(), &large[..]);
+    assert_eq!(memtable.get_visible(&key_of(999), 5).unwrap().len(), 200);
+    assert!(memtable
+        .get(KeySlice::from_slice(&key_of(999), 2))
+        .is_none());
+}
@@ -0,0 +1,128 @@
+use std::ops::Bound;
+
+use bytes::Bytes;
+use tempfile::tempdir;
+
+use crate::{
+    compact::CompactionOptions,
+    iterators::StorageIterator,
+    key::KeySlice,
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    mem_table::{MemTable, MemTableRepKind},
+};
+
+use super::harness::{check_lsm_iter_result_by_key, key_of};
+
+const REPS: [MemTableRepKind; 4] = [
+    MemTableRepKind::ArenaSkipList,
+    MemTableRepKind::SkipList,
+    MemTableRepKind::Vector,
+    MemTableRepKind::HashLinkList {
+        prefix_len: 4,
+        bucket_count: 16,
+    },
+];
+
+/// Testing: every memtable representation orders the versions, replaces a rewritten
+/// entry, and answers point lookups and range scans, before and after being frozen.
+#[test]
+fn test_task1_memtable_reps() {
+    for rep in REPS {
+        let memtable = MemTable::create_with_rep(0, rep);
+        assert!(memtable.is_empty());
+        for idx in (0..100).rev() {
+            for ts in [3, 1, 2] {
+                let value = format!("value{}@{}", idx, ts);
+                memtable
+                    .put(KeySlice::from_slice(&key_of(idx), ts), value.as_bytes())
+                    .unwrap();
+            }
+        }
+        memtable
+            .put(KeySlice::from_slice(&key_of(42), 2), b"replaced")
+            .unwrap();
+        for frozen in [false, true] {
+            if frozen {
+                memtable.mark_immutable();
+            }
+            assert!(!memtable.is_empty());
+            assert_eq!(
+                memtable.get(KeySlice::from_slice(&key_of(42), 2)).unwrap(),
+                &b"replaced"[..]
+            );
+            assert!(memtable.get(KeySlice::from_slice(&key_of(42), 4)).is_none());
+            assert_eq!(
+                memtable.get_visible(&key_of(7), 5).unwrap(),
+                &b"value7@3"[..]
+            );
+            assert_eq!(
+                memtable.get_visible(&key_of(7), 2).unwrap(),
+                &b"value7@2"[..]
+            );
+            assert!(memtable.get_visible(&key_of(7), 0).is_none());
+            assert!(memtable.get_visible(&key_of(100), 5).is_none());
+
+            let mut iter = memtable.scan(
+                Bound::Included(KeySlice::from_slice(&key_of(10), 1)),
+                Bound::Excluded(KeySlice::from_slice(&key_of(11), 1)),
+            );
+            let mut found = Vec::new();
+            while iter.is_valid() {
+                found.push((iter.key().key_ref().to_vec(), iter.key().ts()));
+                iter.next().unwrap();
+            }
+            assert_eq!(
+                found,
+                vec![
+                    (key_of(10).to_vec(), 1),
+                    (key_of(11).to_vec(), 3),
+                    (key_of(11).to_vec(), 2),
+                ]
+            );
+        }
+    }
+}
+
+/// Testing: a storage using each representation serves its writes, through freezes,
+/// flushes and WAL recovery.
+#[test]
+fn test_task2_storage_with_reps() {
+    for rep in REPS {
+        let dir = tempdir().unwrap();
+        let mut options =
+            LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+        options.memtable_rep = rep;
+        options.enable_wal = true;
+        options.target_sst_size = 4 << 10;
+        options.num_memtable_limit = 100;
+        let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+        for idx in 0..300 {
+            storage
+                .put(&key_of(idx), format!("value{}", idx).as_bytes())
+                .unwrap();
+        }
+        for idx in (0..300).step_by(3) {
+            storage.delete(&key_of(idx)).unwrap();
+        }
+        assert!(!storage.inner.state.read().imm_memtables.is_empty());
+        let expected = (0..300)
+            .filter(|idx| idx % 3 != 0)
+            .map(|idx| (key_of(idx), Bytes::from(format!("value{}", idx))))
+            .collect::<Vec<_>>();
+        let check = |storage: &MiniLsm| {
+            assert_eq!(storage.get(&key_of(1)).unwrap().unwrap(), "value1");
+            assert!(storage.get(&key_of(3)).unwrap().is_none());
+            check_lsm_iter_result_by_key(
+                &mut storage.scan(Bound::Unbounded, Bound::Unbounded).unwrap(),
+                expected.clone(),
+            );
+        };
+        check(&storage);
+        storage.close().unwrap();
+        drop(storage);
+        let storage = MiniLsm::open(&dir, options).unwrap();
+        check(&storage);
+        storage.force_flush().unwrap();
+        check(&storage);
+    }
+}
@@ -0,0 +1,201 @@
+use std::path::{Path, PathBuf};
+
+use tempfile::tempdir;
+
+use crate::{
+    compact::{CompactionOptions, LeveledCompactionOptions},
+    lsm_storage::{LsmStorageOptions, MiniLsm},
+    table::SstFileWriter,
+};
+
+use super::harness::key_of;
+
+fn write_sst(
+    path: &Path,
+    options: &LsmStorageOptions,
+    range: std::ops::Range<usize>,
+    tag: &str,
+) -> PathBuf {
+    let path = path.join(format!("external-{}-{}.sst", range.start, tag));
+    let mut writer = SstFileWriter::create(&path, options);
+    for idx in range {
+        writer
+            .put(&key_of(idx), format!("{}{}", tag, idx).as_bytes())
+            .unwrap();
+    }
+    writer.finish().unwrap();
+    path
+}
+
+/// Testing: ingested files become visible at once at a new commit timestamp, in the
+/// lowest level holding nothing they overlap.
+#[test]
+fn test_task1_ingest_to_lowest_level() {
+    let (dir, external) = (tempdir().unwrap(), tempdir().unwrap());
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::Leveled(
+        LeveledCompactionOptions {
+            level0_file_num_compaction_trigger: 100,
+            max_levels: 3,
+            base_level_size_mb: 1,
+            level_size_multiplier: 2,
+        },
+    ));
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    storage.put(b"a", b"1").unwrap();
+
+    let mut writer = SstFileWriter::create(external.path().join("bad.sst"), &options);
+    writer.put(b"b", b"1").unwrap();
+    assert!(writer.put(b"b", b"2").is_err());
+    assert!(writer.put(b"a", b"2").is_err());
+    let writer = SstFileWriter::create(external.path().join("empty.sst"), &options);
+    assert!(writer.finish().is_err());
+
+    let first = write_sst(external.path(), &options, 0..500, "first");
+    let second = write_sst(external.path(), &options, 500..1000, "second");
+    let txn = storage.new_txn().unwrap();
+    let commit_ts = storage.ingest_external_files(&[&second, &first]).unwrap();
+    assert_eq!(commit_ts, 2);
+    assert!(first.exists() && second.exists());
+    {
+        let snapshot = storage.inner.state.read();
+        assert!(snapshot.l0_sstables.is_empty());
+        assert_eq!(snapshot.levels[2].1.len(), 2);
+        let ssts = &snapshot.levels[2].1;
+        assert!(snapshot.sstables[&ssts[0]].first_key() < snapshot.sstables[&ssts[1]].first_key());
+    }
+    assert!(txn.get(&key_of(7)).unwrap().is_none());
+    assert_eq!(storage.get(&key_of(7)).unwrap().unwrap(), "first7");
+    assert_eq!(storage.get(&key_of(700)).unwrap().unwrap(), "second700");
+    assert_eq!(storage.get(b"a").unwrap().unwrap(), "1");
+
+    // files overlapping each other are rejected.
+    let overlapping = write_sst(external.path(), &options, 400..600, "overlapping");
+    assert!(storage
+        .ingest_external_files(&[&first, &overlapping])
+        .is_err());
+}
+
+/// Testing: a file overlapping the memtable or L0 goes to L0 after flushing the memtable,
+/// its entries newer than those there, and the ingestion survives a restart.
+#[test]
+fn test_task2_ingest_overlapping_and_recover() {
+    let (dir, external) = (tempdir().unwrap(), tempdir().unwrap());
+    let mut options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    options.enable_wal = true;
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in 0..100 {
+        storage.put(&key_of(idx), b"old").unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.put(&key_of(200), b"old").unwrap();
+
+    // overlaps the memtable only: flushed to L0 first, the file then lands above it.
+    let upper = write_sst(external.path(), &options, 150..250, "upper");
+    storage.ingest_external_files(&[&upper]).unwrap();
+    // overlaps the SSTs in L0.
+    let mut writer = SstFileWriter::create(external.path().join("delete.sst"), &options);
+    writer.put(&key_of(10), b"new").unwrap();
+    writer.delete(&key_of(11)).unwrap();
+    writer.finish().unwrap();
+    storage
+        .ingest_external_files(&[external.path().join("delete.sst")])
+        .unwrap();
+    // overlaps nothing.
+    let lower = write_sst(external.path(), &options, 300..400, "lower");
+    storage.ingest_external_files(&[&lower]).unwrap();
+
+    let check = |storage: &MiniLsm| {
+        let snapshot = storage.inner.state.read();
+        assert!(snapshot.memtable.is_empty() && snapshot.imm_memtables.is_empty());
+        assert_eq!(snapshot.l0_sstables.len(), 4);
+        assert_eq!(snapshot.levels[0].1.len(), 1);
+        drop(snapshot);
+        assert_eq!(storage.get(&key_of(200)).unwrap().unwrap(), "upper200");
+        assert_eq!(storage.get(&key_of(10)).unwrap().unwrap(), "new");
+        assert!(storage.get(&key_of(11)).unwrap().is_none());
+        assert_eq!(storage.get(&key_of(12)).unwrap().unwrap(), "old");
+        assert_eq!(storage.get(&key_of(350)).unwrap().unwrap(), "lower350");
+    };
+    check(&storage);
+    storage.close().unwrap();
+    drop(storage);
+    let storage = MiniLsm::open(&dir, options).unwrap();
+    check(&storage);
+    assert_eq!(storage.inner.mvcc().latest_commit_ts(), 104);
+}
+
+/// Testing: when a later file fails to ingest, nothing is ingested and the SSTs already
+/// built for the earlier files are deleted.
+#[test]
+fn test_task3_ingest_failure_cleans_up() {
+    let (dir, external) = (tempdir().unwrap(), tempdir().unwrap());
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    let first = write_sst(external.path(), &options, 0..500, "first");
+    let second = write_sst(external.path(), &options, 500..1000, "second");
+    // corrupt the first data block of the second file.
+    let mut data = std::fs::read(&second).unwrap();
+    data[10] ^= 0xff;
+    std::fs::write(&second, data).unwrap();
+
+    assert!(storage.ingest_external_files(&[&first, &second]).is_err());
+    let sst_files = || {
+        std::fs::read_dir(&dir)
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .is_some_and(|ext| ext == "sst")
+            })
+            .count()
+    };
+    assert_eq!(sst_files(), 0);
+    assert!(storage.inner.state.read().sstables.is_empty());
+    assert!(storage.get(&key_of(7)).unwrap().is_none());
+
+    storage.ingest_external_files(&[&first]).unwrap();
+    assert_eq!(sst_files(), 1);
+    assert_eq!(storage.get(&key_of(7)).unwrap().unwrap(), "first7");
+}
+
+/// Testing: files ingested while a full compaction runs go above it, none is lost.
+#[test]
+fn test_task4_ingest_during_full_compaction() {
+    let (dir, external) = (tempdir().unwrap(), tempdir().unwrap());
+    let options = LsmStorageOptions::default_for_week2_test(CompactionOptions::NoCompaction);
+    let storage = MiniLsm::open(&dir, options.clone()).unwrap();
+    for idx in 0..100 {
+        storage.put(&key_of(idx), b"old").unwrap();
+    }
+    storage.force_flush().unwrap();
+    storage.force_full_compaction().unwrap();
+
+    let files = (0..20)
+        .map(|round| {
+            let start = 1000 + round * 10;
+            write_sst(external.path(), &options, start..start + 10, "new")
+        })
+        .collect::<Vec<_>>();
+    std::thread::scope(|s| {
+        let compaction = s.spawn(|| {
+            for _ in 0..20 {
+                storage.force_full_compaction().unwrap();
+            }
+        });
+        for file in &files {
+            storage.ingest_external_files(&[file]).unwrap();
+        }
+        compaction.join().unwrap();
+    });
+    storage.force_full_compaction().unwrap();
+    assert_eq!(storage.get(&key_of(7)).unwrap().unwrap(), "old");
+    for idx in 1000..1200 {
+        assert_eq!(
+            storage.get(&key_of(idx)).unwrap().unwrap(),
+            format!("new{}", idx)
+        );
+    }
+}
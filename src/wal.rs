@@ -11,9 +11,8 @@ use parking_lot::Mutex;
 use anyhow::{bail, Context, Ok, Result};
 
 use bytes::{Buf, BufMut, Bytes};
-use crossbeam_skiplist::SkipMap;
 
-use crate::key::{KeyBytes, KeySlice};
+use crate::key::KeySlice;
 
 pub struct Wal {
     file: Arc<Mutex<BufWriter<File>>>,
@@ -33,7 +32,11 @@ impl Wal {
         })
     }
 
-    pub fn recover(path: impl AsRef<Path>, skiplist: &SkipMap<KeyBytes, Bytes>) -> Result<Self> {
+    /// replay the records of the WAL at `path` into `insert`, and reopen it for appending.
+    pub fn recover(
+        path: impl AsRef<Path>,
+        mut insert: impl FnMut(KeySlice, &[u8]),
+    ) -> Result<Self> {
         let mut file = OpenOptions::new()
             .read(true)
             .append(true)
@@ -63,7 +66,7 @@ impl Wal {
             if hasher.finalize() != buf_ptr.get_u32() {
                 bail!("checksum mismatched!");
             }
-            skiplist.insert(KeyBytes::from_bytes_with_ts(key, ts), value);
+            insert(KeySlice::from_slice(&key, ts), &value);
         }
         Ok(Self {
             file: Arc::new(Mutex::new(BufWriter::new(file))),
@@ -74,6 +77,23 @@ impl Wal {
         let mut file = self.file.lock();
         let mut buf: Vec<u8> =
             Vec::with_capacity(key.raw_len() + value.len() + std::mem::size_of::<u16>());
+        Self::encode_record(&mut buf, key, value);
+        file.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// append the records of a batch with one write.
+    pub fn put_batch(&self, data: &[(KeySlice, &[u8])]) -> Result<()> {
+        let mut buf = Vec::new();
+        for (key, value) in data {
+            Self::encode_record(&mut buf, *key, value);
+        }
+        let mut file = self.file.lock();
+        file.write_all(&buf)?;
+        Ok(())
+    }
+
+    fn encode_record(buf: &mut Vec<u8>, key: KeySlice, value: &[u8]) {
         let mut hasher = crc32fast::Hasher::new();
         hasher.write_u16(key.key_len() as u16);
         buf.put_u16(key.key_len() as u16);
@@ -86,8 +106,6 @@ impl Wal {
         buf.put_slice(value);
         hasher.write(value);
         buf.put_u32(hasher.finalize());
-        file.write_all(&buf)?;
-        Ok(())
     }
 
     /// ensure that any data written to the Write-Ahead Log (WAL)
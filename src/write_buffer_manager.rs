@@ -0,0 +1,125 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+};
+
+use parking_lot::Mutex;
+
+use crate::{block_cache::BlockCache, lsm_storage::LsmStorageInner};
+
+/// memtable memory is charged against the block cache in units of this many bytes.
+pub const CACHE_RESERVATION_UNIT: u64 = 256 << 10;
+
+/// WriteBufferManager caps the memory taken by the memtables of every storage instance
+/// sharing it. Once their total passes `buffer_size`, the instance holding the most
+/// memtable memory is asked to flush, whichever instance is being written.
+///
+/// With a block cache, the memtable memory is also charged against its capacity,
+/// so memtables and cached blocks share one budget.
+pub struct WriteBufferManager {
+    buffer_size: usize,
+    memory_used: AtomicUsize,
+    instances: Mutex<Vec<Weak<LsmStorageInner>>>,
+    block_cache: Option<Arc<BlockCache>>,
+    // bytes currently reserved in the block cache.
+    cache_reserved: AtomicU64,
+}
+
+impl WriteBufferManager {
+    /// create a manager keeping the memtables under `buffer_size` bytes.
+    pub fn new(buffer_size: usize) -> Self {
+        assert!(buffer_size > 0, "the write buffer must hold some bytes");
+        Self {
+            buffer_size,
+            memory_used: AtomicUsize::new(0),
+            instances: Mutex::new(Vec::new()),
+            block_cache: None,
+            cache_reserved: AtomicU64::new(0),
+        }
+    }
+
+    /// create a manager charging the memtables against `block_cache` too.
+    pub fn with_block_cache(buffer_size: usize, block_cache: Arc<BlockCache>) -> Self {
+        Self {
+            block_cache: Some(block_cache),
+            ..Self::new(buffer_size)
+        }
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// the bytes written to the memtables of every instance and not flushed yet.
+    pub fn memory_usage(&self) -> usize {
+        self.memory_used.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn register(&self, inner: &Arc<LsmStorageInner>) {
+        let mut instances = self.instances.lock();
+        instances.retain(|instance| instance.strong_count() > 0);
+        instances.push(Arc::downgrade(inner));
+    }
+
+    pub(crate) fn reserve(&self, bytes: usize) {
+        self.memory_used.fetch_add(bytes, Ordering::Relaxed);
+        self.update_cache_reservation();
+    }
+
+    pub(crate) fn free(&self, bytes: usize) {
+        self.memory_used.fetch_sub(bytes, Ordering::Relaxed);
+        self.update_cache_reservation();
+    }
+
+    /// ask the instance holding the most memtable memory to flush when over the budget.
+    pub(crate) fn maybe_flush(&self) {
+        if self.memory_usage() <= self.buffer_size {
+            return;
+        }
+        let largest = self
+            .instances
+            .lock()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .max_by_key(|instance| instance.write_buffer_usage());
+        if let Some(instance) = largest {
+            instance.request_flush();
+        }
+    }
+
+    /// follow the memory usage in the block cache, a unit at a time.
+    fn update_cache_reservation(&self) {
+        let Some(ref block_cache) = self.block_cache else {
+            return;
+        };
+        let target =
+            (self.memory_usage() as u64).div_ceil(CACHE_RESERVATION_UNIT) * CACHE_RESERVATION_UNIT;
+        let current = self.cache_reserved.load(Ordering::Relaxed);
+        if current == target
+            || self
+                .cache_reserved
+                .compare_exchange(current, target, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            return;
+        }
+        if target > current {
+            block_cache.reserve(target - current);
+        } else {
+            block_cache.release(current - target);
+        }
+    }
+}
+
+impl fmt::Debug for WriteBufferManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteBufferManager")
+            .field("buffer_size", &self.buffer_size)
+            .field("memory_usage", &self.memory_usage())
+            .field("charged_to_cache", &self.block_cache.is_some())
+            .finish()
+    }
+}